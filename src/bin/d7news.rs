@@ -1,11 +1,23 @@
 use std::{
     convert::{TryFrom, TryInto},
     io::{Read, Write},
+    path::Path,
+    time::Duration,
 };
 
-use anyhow::Result;
+use anyhow::{Result, bail};
+use bp7::Bundle;
+use bp7::dtntime::DtnTimeHelpers;
 use clap::{ArgAction, Parser, crate_authors, crate_version};
-use dtn7_plus::news::{NewsBundle, new_news, reply_news};
+use dtn7_plus::client::{DtnClient, Message};
+use dtn7_plus::news::archive::{export_json_lines, export_mbox, import_json_lines, import_mbox};
+use dtn7_plus::news::digest::{DigestBundle, DigestPacker};
+use dtn7_plus::news::gateway::{FeedState, parse_feed, render_atom};
+use dtn7_plus::news::keyring::Keyring;
+use dtn7_plus::news::moderation::{ModeratorKey, ModeratorKeys, Moderators, approve_news};
+use dtn7_plus::news::spool::{Spool, ThreadNode};
+use dtn7_plus::news::subscription::Subscriptions;
+use dtn7_plus::news::{NewsBuilder, NewsBundle, NewsBundleBuilder, newsgroup_eid, sender_eid};
 
 #[derive(Parser)]
 #[clap(version = crate_version!(), author = crate_authors!())]
@@ -13,16 +25,94 @@ struct Opts {
     /// A level of verbosity, and can be used multiple times
     #[clap(short, long, action = ArgAction::Count)]
     verbose: u8,
+    /// Local web port (default = $DTN_WEB_PORT or 3000)
+    #[clap(short, long)]
+    port: Option<u16>,
+    /// Use IPv6
+    #[clap(short = '6', long)]
+    ipv6: bool,
+    /// File with moderator endpoints, one 'newsgroups:endpoint' per line
+    #[clap(short = 'M', long)]
+    moderators: Option<String>,
+    /// File with public keys of moderators, one 'endpoint base64-key' per line
+    #[clap(short = 'K', long)]
+    moderator_keys: Option<String>,
+    /// File with keys of private newsgroups, one 'group key-id base64-key' per line
+    #[clap(short, long)]
+    keyring: Option<String>,
     #[clap(subcommand)]
     subcmds: SubCommand,
 }
 
+impl Opts {
+    fn client(&self) -> DtnClient {
+        let localhost = if self.ipv6 { "[::1]" } else { "127.0.0.1" };
+        // prefer CLI, fall back to env, then 3000
+        let port = self.port.unwrap_or_else(|| {
+            std::env::var("DTN_WEB_PORT")
+                .ok()
+                .and_then(|s| s.parse::<u16>().ok())
+                .unwrap_or(3000)
+        });
+        DtnClient::with_host_and_port(localhost.into(), port)
+    }
+}
+
 #[derive(Parser)]
 enum SubCommand {
     //#[clap(version = "1.3", author = "Someone E. <someone_else@other.com>")]
     Post(PostCmd),
     Reply(ReplyCmd),
     Read(ReadCmd),
+    Subscribe(SubscribeCmd),
+    List(ListCmd),
+    Export(ExportCmd),
+    Import(ImportCmd),
+    Atom(AtomCmd),
+    Feed(FeedCmd),
+    Digest(DigestCmd),
+    Keygen(KeygenCmd),
+    Approve(ApproveCmd),
+}
+
+/// Output a new bundle as hex or CBOR, or submit it to the local dtnd
+fn emit(
+    mut news: NewsBundle,
+    hex: bool,
+    cbor: bool,
+    client: &DtnClient,
+    log_level: u8,
+) -> Result<()> {
+    let bin_bundle = news.to_cbor();
+    if hex {
+        println!("{}", bp7::helpers::hexify(&bin_bundle));
+    } else if cbor {
+        std::io::stdout().write_all(&bin_bundle).unwrap();
+    } else {
+        let res = client.insert_bundle(bin_bundle)?;
+        if log_level > 0 {
+            println!("Bundle-Id: {}", news.id());
+            println!("Result: {}", res);
+        }
+    }
+    Ok(())
+}
+
+/// Open a spool directory and report the files that could not be loaded
+fn open_spool<P: AsRef<Path>>(path: P) -> Result<Spool> {
+    let spool = Spool::open(path)?;
+    for (path, err) in spool.skipped() {
+        eprintln!("[!] Skipping {}: {}", path.display(), err);
+    }
+    Ok(spool)
+}
+
+/// Print an article, encrypted articles are decrypted if the keyring has their group key
+fn show(news: &NewsBundle, keyring: Option<&Keyring>) {
+    match keyring.map(|keyring| news.decrypted(keyring)) {
+        Some(Ok(plain)) => println!("{}", plain),
+        _ => println!("{}", news),
+    }
 }
 
 /// Create a new post
@@ -44,12 +134,34 @@ struct PostCmd {
     #[clap(short, long)]
     message: String,
 
-    /// Output bundle as hex
+    /// Tag, can be used multiple times
+    #[clap(short = 'T', long)]
+    tag: Vec<String>,
+
+    /// Bundle lifetime in the network, e.g., '3days'
+    #[clap(short, long, value_parser = humantime::parse_duration, default_value = "1h")]
+    lifetime: Duration,
+
+    /// How long readers should keep the article, e.g., '2weeks'
+    #[clap(short, long, value_parser = humantime::parse_duration)]
+    expires: Option<Duration>,
+
+    /// Output bundle as hex instead of submitting it
     #[clap(short = 'H', long)]
     hex: bool,
+
+    /// Output bundle as raw CBOR instead of submitting it
+    #[clap(short = 'C', long)]
+    cbor: bool,
 }
 
-fn cmd_post(opts: PostCmd, _log_level: u8) -> Result<()> {
+fn cmd_post(
+    opts: PostCmd,
+    moderators: &Moderators,
+    keyring: Option<&Keyring>,
+    client: DtnClient,
+    log_level: u8,
+) -> Result<()> {
     let msg = if opts.message == "-" {
         let mut raw_bytes: Vec<u8> = Vec::new();
         std::io::stdin()
@@ -59,25 +171,26 @@ fn cmd_post(opts: PostCmd, _log_level: u8) -> Result<()> {
     } else {
         opts.message
     };
-    let post = new_news(
-        &opts.src_node_name,
-        &opts.dst_group,
-        &opts.topic,
-        None,
-        None,
-        &msg,
-        Vec::new(),
-        true,
-    )?
-    .to_cbor();
-
-    if opts.hex {
-        println!("{}", bp7::helpers::hexify(&post));
-    } else {
-        std::io::stdout().write_all(&post).unwrap();
+    let mut payload = NewsBuilder::new()
+        .topic(&opts.topic)
+        .message(&msg)
+        .tags(opts.tag);
+    if let Some(expires) = opts.expires {
+        payload = payload.expires_in(expires);
+    }
+    // groups with a key in the keyring are private
+    if let Some(key) = keyring.and_then(|keyring| keyring.current(&opts.dst_group)) {
+        payload = payload.encrypt(key);
     }
+    let post = NewsBundleBuilder::new()
+        .source(sender_eid(&opts.src_node_name)?)
+        .newsgroup(&opts.dst_group)?
+        .lifetime(opts.lifetime)
+        .payload(payload.build()?)
+        .moderators(moderators)
+        .build()?;
 
-    Ok(())
+    emit(post, opts.hex, opts.cbor, &client, log_level)
 }
 
 /// Create a new post
@@ -95,12 +208,30 @@ struct ReplyCmd {
     #[clap(short, long)]
     input_newsbundle: String,
 
-    /// Original bundle as hex
+    /// Bundle lifetime in the network, e.g., '3days'
+    #[clap(short, long, value_parser = humantime::parse_duration, default_value = "1h")]
+    lifetime: Duration,
+
+    /// How long readers should keep the article, e.g., '2weeks'
+    #[clap(short, long, value_parser = humantime::parse_duration)]
+    expires: Option<Duration>,
+
+    /// Output bundle as hex instead of submitting it
     #[clap(short = 'H', long)]
     hex: bool,
+
+    /// Output bundle as raw CBOR instead of submitting it
+    #[clap(short = 'C', long)]
+    cbor: bool,
 }
 
-fn cmd_reply(opts: ReplyCmd, _log_level: u8) -> Result<()> {
+fn cmd_reply(
+    opts: ReplyCmd,
+    moderators: &Moderators,
+    keyring: Option<&Keyring>,
+    client: DtnClient,
+    log_level: u8,
+) -> Result<()> {
     let msg = if opts.message == "-" {
         let mut raw_bytes: Vec<u8> = Vec::new();
         std::io::stdin()
@@ -111,16 +242,35 @@ fn cmd_reply(opts: ReplyCmd, _log_level: u8) -> Result<()> {
         opts.message
     };
     let raw_bytes = bp7::helpers::unhexify(&opts.input_newsbundle)?;
-    let news_bundle: NewsBundle = raw_bytes.try_into()?;
-    let post = reply_news(&news_bundle, &opts.src_node_name, &msg, true)?.to_cbor();
-
-    if opts.hex {
-        println!("{}", bp7::helpers::hexify(&post));
-    } else {
-        std::io::stdout().write_all(&post).unwrap();
+    let mut news_bundle: NewsBundle = raw_bytes.try_into()?;
+    let encrypted = news_bundle.is_private();
+    if encrypted {
+        let Some(keyring) = keyring else {
+            bail!("[!] Replying to an encrypted article requires a keyring");
+        };
+        news_bundle = news_bundle.decrypted(keyring)?;
     }
+    let mut payload = NewsBuilder::new().reply_to(&news_bundle).message(&msg);
+    if let Some(expires) = opts.expires {
+        payload = payload.expires_in(expires);
+    }
+    // replies to private groups are encrypted with the current group key
+    if encrypted {
+        let group = news_bundle.newsgroup().unwrap_or_default();
+        match keyring.and_then(|keyring| keyring.current(&group)) {
+            Some(key) => payload = payload.encrypt(key),
+            None => bail!("[!] No key for newsgroup {}", group),
+        }
+    }
+    let post = NewsBundleBuilder::new()
+        .source(sender_eid(&opts.src_node_name)?)
+        .destination(news_bundle.bundle().primary.destination.clone())
+        .lifetime(opts.lifetime)
+        .payload(payload.build()?)
+        .moderators(moderators)
+        .build()?;
 
-    Ok(())
+    emit(post, opts.hex, opts.cbor, &client, log_level)
 }
 
 /// Decode news bundle in various forms
@@ -133,9 +283,11 @@ struct ReadCmd {
     #[clap(short, long)]
     path: Option<String>,
 }
-fn cmd_read(opts: ReadCmd, _log_level: u8) -> Result<()> {
+fn cmd_read(opts: ReadCmd, keyring: Option<&Keyring>, _log_level: u8) -> Result<()> {
     let bytes = if let Some(hex_str) = opts.hex {
         bp7::helpers::unhexify(&hex_str)?
+    } else if let Some(path) = opts.path.filter(|p| p != "-") {
+        std::fs::read(path)?
     } else {
         let mut raw_bytes: Vec<u8> = Vec::new();
         std::io::stdin()
@@ -143,25 +295,519 @@ fn cmd_read(opts: ReadCmd, _log_level: u8) -> Result<()> {
             .expect("Error reading from stdin.");
         raw_bytes
     };
-    let news = NewsBundle::try_from(bytes)?;
-    println!("{}", news);
+    for news in NewsBundle::decode_all(&bytes)? {
+        show(&news, keyring);
+    }
     Ok(())
 }
 
+/// Subscribe to newsgroups and print articles as they arrive
+#[derive(Parser)]
+struct SubscribeCmd {
+    /// Newsgroup patterns, e.g., 'de.hessen.*' or 'de.*,!de.test'
+    ///
+    /// Groups cannot be discovered in the network, dtnd only delivers bundles of subscribed
+    /// endpoints. Patterns match exact names right away, other groups once they are found in
+    /// the spool or in received articles, e.g., digests.
+    #[clap(short, long)]
+    groups: String,
+
+    /// Tag patterns articles must match, e.g., 'weather,traffic' or '!spam'
+    #[clap(short, long)]
+    tags: Option<String>,
+
+    /// Store received articles in this spool directory
+    #[clap(short, long)]
+    spool: Option<String>,
+}
+
+fn cmd_subscribe(
+    opts: SubscribeCmd,
+    moderators: &Moderators,
+    moderator_keys: &ModeratorKeys,
+    keyring: Option<&Keyring>,
+    client: DtnClient,
+    log_level: u8,
+) -> Result<()> {
+    let mut subs = Subscriptions::new(&opts.groups);
+    if let Some(tags) = &opts.tags {
+        subs = subs.with_tags(tags);
+    }
+    let mut spool = opts.spool.map(open_spool).transpose()?;
+    if let Some(spool) = &spool {
+        for group in spool.groups() {
+            subs.observe_group(&group);
+        }
+    }
+
+    let mut wscon = client.ws()?;
+    wscon.write_text("/bundle")?;
+    let msg = wscon.read_text()?;
+    if msg.starts_with("200 tx mode: bundle") {
+        if log_level > 0 {
+            eprintln!("[*] {}", msg);
+        }
+    } else {
+        bail!("[!] Failed to set mode to `bundle`");
+    }
+
+    loop {
+        for endpoint in subs.register(&client)? {
+            wscon.write_text(&format!("/subscribe {}", endpoint))?;
+            let msg = wscon.read_text()?;
+            if !msg.starts_with("200 subscribed") {
+                bail!("[!] Failed to subscribe to {}", endpoint);
+            }
+            if log_level > 0 {
+                eprintln!("[*] Subscribed to {}", endpoint);
+            }
+        }
+
+        match wscon.read_message()? {
+            Message::Text(txt) => {
+                eprintln!("[!] Unexpected response: {}", txt);
+                break;
+            }
+            Message::Binary(bin) => {
+                let bndl = Bundle::try_from(bin.to_vec())?;
+                let bid = bndl.id();
+                let articles = match NewsBundle::try_from(bndl.clone()) {
+                    Ok(news) => vec![news],
+                    Err(err) => match DigestBundle::try_from(bndl) {
+                        Ok(digest) => digest.articles(),
+                        Err(_) => {
+                            eprintln!(
+                                "[!] Not a news bundle {}: {:#}",
+                                bid,
+                                anyhow::Error::from(err)
+                            );
+                            continue;
+                        }
+                    },
+                };
+                for news in articles {
+                    if subs.observe(&news) && log_level > 0 {
+                        eprintln!("[*] New group {}", news.dst().unwrap_or_default());
+                    }
+                    if !subs.accepts(&news) {
+                        continue;
+                    }
+                    if !moderators.accepts(&news, moderator_keys) {
+                        if log_level > 0 {
+                            eprintln!("[!] Dropping unapproved article {}", news.id());
+                        }
+                        continue;
+                    }
+                    if let Some(spool) = spool.as_mut() {
+                        match spool.store(news.clone()) {
+                            Ok(true) => {}
+                            Ok(false) => continue,
+                            // still show the article, only the spool copy is missing
+                            Err(err) => eprintln!("[!] Failed to store {}: {}", news.id(), err),
+                        }
+                    }
+                    show(&news, keyring);
+                }
+            }
+            Message::Close(_) => {
+                if log_level > 0 {
+                    eprintln!("[<] Close")
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// List groups or the threads of a group from a local spool
+#[derive(Parser)]
+struct ListCmd {
+    /// Spool directory
+    #[clap(short, long, default_value = "news")]
+    spool: String,
+
+    /// Show threads of this newsgroup
+    #[clap(short, long)]
+    group: Option<String>,
+}
+
+fn print_thread(node: &ThreadNode, depth: usize, keyring: Option<&Keyring>) {
+    let news = node.article;
+    // encrypted articles without a key keep their place in the thread
+    let topic = match news.readable(keyring) {
+        Some(plain) => plain.topic(),
+        None => format!("[encrypted, key {}]", news.key_id().unwrap_or_default()),
+    };
+    println!(
+        "{}{} {} ({}) {}",
+        "  ".repeat(depth),
+        news.creation_timestamp().dtntime().string(),
+        topic,
+        news.src().unwrap_or_default(),
+        news.message_id()
+    );
+    for reply in &node.replies {
+        print_thread(reply, depth + 1, keyring);
+    }
+}
+
+fn cmd_list(opts: ListCmd, keyring: Option<&Keyring>, _log_level: u8) -> Result<()> {
+    let spool = open_spool(&opts.spool)?;
+    match opts.group {
+        Some(group) => {
+            for thread in spool.threads(&group) {
+                for root in &thread.roots {
+                    print_thread(root, 0, keyring);
+                }
+            }
+        }
+        None => {
+            for group in spool.groups() {
+                println!(
+                    "{} {} {}",
+                    group,
+                    spool.articles(&group).len(),
+                    newsgroup_eid(&group)?
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Export a group or a single thread from a local spool
+#[derive(Parser)]
+struct ExportCmd {
+    /// Spool directory
+    #[clap(short, long, default_value = "news")]
+    spool: String,
+
+    /// Newsgroup to export
+    #[clap(short, long)]
+    group: String,
+
+    /// Only export the thread with this thread ID
+    #[clap(short, long)]
+    thread: Option<String>,
+
+    /// Write JSON lines instead of mbox
+    #[clap(short, long)]
+    json: bool,
+}
+
+fn cmd_export(opts: ExportCmd, keyring: Option<&Keyring>, _log_level: u8) -> Result<()> {
+    let spool = open_spool(&opts.spool)?;
+    let articles = match opts.thread {
+        Some(tid) => spool
+            .threads(&opts.group)
+            .into_iter()
+            .find(|thread| thread.tid.to_string() == tid)
+            .map(|thread| thread.articles())
+            .ok_or_else(|| anyhow::anyhow!("[!] Unknown thread {}", tid))?,
+        None => spool.articles(&opts.group),
+    };
+    let mut stdout = std::io::stdout().lock();
+    if opts.json {
+        export_json_lines(&mut stdout, articles, keyring)?;
+    } else {
+        export_mbox(&mut stdout, articles, keyring)?;
+    }
+    Ok(())
+}
+
+/// Rebuild articles from an mbox or JSON lines archive and submit them
+///
+/// Articles of private groups are encrypted with the current group key of the keyring.
+#[derive(Parser)]
+struct ImportCmd {
+    /// Archive file or '-' for stdin
+    #[clap(short, long, default_value = "-")]
+    path: String,
+
+    /// Read JSON lines instead of mbox
+    #[clap(short, long)]
+    json: bool,
+
+    /// Output bundles as raw CBOR instead of submitting them
+    #[clap(short = 'C', long)]
+    cbor: bool,
+}
+
+fn cmd_import(
+    opts: ImportCmd,
+    keyring: Option<&Keyring>,
+    client: DtnClient,
+    log_level: u8,
+) -> Result<()> {
+    let reader: Box<dyn std::io::BufRead> = if opts.path == "-" {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(std::io::BufReader::new(std::fs::File::open(&opts.path)?))
+    };
+    let bundles = if opts.json {
+        import_json_lines(reader, keyring)?
+    } else {
+        import_mbox(reader, keyring)?
+    };
+    for news in bundles {
+        emit(news, false, opts.cbor, &client, log_level)?;
+    }
+    Ok(())
+}
+
+/// Render a newsgroup from a local spool as Atom feed
+#[derive(Parser)]
+struct AtomCmd {
+    /// Spool directory
+    #[clap(short, long, default_value = "news")]
+    spool: String,
+
+    /// Newsgroup to render
+    #[clap(short, long)]
+    group: String,
+}
+
+fn cmd_atom(opts: AtomCmd, keyring: Option<&Keyring>, _log_level: u8) -> Result<()> {
+    let spool = open_spool(&opts.spool)?;
+    print!("{}", render_atom(&spool, &opts.group, keyring)?);
+    Ok(())
+}
+
+/// Post the entries of an RSS or Atom file that have not been posted before
+#[derive(Parser)]
+struct FeedCmd {
+    /// Sender DTN node name
+    #[clap(short, long)]
+    src_node_name: String,
+
+    /// Destination newsgroup
+    #[clap(short, long)]
+    dst_group: String,
+
+    /// Feed file or '-' for stdin
+    #[clap(short, long, default_value = "-")]
+    path: String,
+
+    /// File keeping the keys of posted entries
+    #[clap(short = 'k', long)]
+    state: Option<String>,
+
+    /// Feed URL or id the entry keys belong to, defaults to the id or link given in the feed
+    #[clap(short, long)]
+    feed: Option<String>,
+
+    /// Output bundles as raw CBOR instead of submitting them
+    #[clap(short = 'C', long)]
+    cbor: bool,
+}
+
+fn cmd_feed(
+    opts: FeedCmd,
+    moderators: &Moderators,
+    client: DtnClient,
+    log_level: u8,
+) -> Result<()> {
+    let xml = if opts.path == "-" {
+        let mut xml = String::new();
+        std::io::stdin().read_to_string(&mut xml)?;
+        xml
+    } else {
+        std::fs::read_to_string(&opts.path)?
+    };
+    let mut state = match &opts.state {
+        Some(path) => FeedState::open(path)?,
+        None => FeedState::new(),
+    };
+    let mut entries = parse_feed(&xml)?;
+    if let Some(feed) = &opts.feed {
+        for entry in entries.iter_mut() {
+            entry.feed = feed.clone();
+        }
+    }
+    for entry in state.fresh(entries) {
+        let post = NewsBundleBuilder::new()
+            .source(sender_eid(&opts.src_node_name)?)
+            .newsgroup(&opts.dst_group)?
+            .payload(entry.to_news().build()?)
+            .moderators(moderators)
+            .build()?;
+        emit(post, false, opts.cbor, &client, log_level)?;
+        state.mark(&entry)?;
+        if log_level > 0 {
+            eprintln!("[*] Posted {}", entry.dedupe_key());
+        }
+    }
+    Ok(())
+}
+
+/// Pack the articles of a newsgroup from a local spool into digest bundles
+#[derive(Parser)]
+struct DigestCmd {
+    /// Sender DTN node name
+    #[clap(short = 'n', long)]
+    src_node_name: String,
+
+    /// Spool directory
+    #[clap(short, long, default_value = "news")]
+    spool: String,
+
+    /// Newsgroup to pack
+    #[clap(short, long)]
+    group: String,
+
+    /// Maximum size of a digest bundle in bytes
+    #[clap(short, long, default_value_t = 64 * 1024)]
+    budget: usize,
+
+    /// Output bundles as raw CBOR instead of submitting them
+    #[clap(short = 'C', long)]
+    cbor: bool,
+}
+
+fn cmd_digest(opts: DigestCmd, client: DtnClient, log_level: u8) -> Result<()> {
+    let spool = open_spool(&opts.spool)?;
+    let packer =
+        DigestPacker::new(sender_eid(&opts.src_node_name)?, &opts.group).budget(opts.budget);
+    for mut digest in packer.pack_spool(&spool)? {
+        let bin_bundle = digest.to_cbor();
+        if opts.cbor {
+            std::io::stdout().write_all(&bin_bundle)?;
+        } else {
+            let res = client.insert_bundle(bin_bundle)?;
+            if log_level > 0 {
+                println!("Bundle-Id: {} ({} articles)", digest.id(), digest.len());
+                println!("Result: {}", res);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Generate a moderator signing key
+#[derive(Parser)]
+struct KeygenCmd {
+    /// File to store the signing key in
+    #[clap(short, long)]
+    key: String,
+
+    /// Moderator endpoint, e.g., 'dtn://mod1/moderation'
+    #[clap(short, long)]
+    moderator: String,
+}
+
+/// Store a new signing key and print the line receivers add to their moderator keys file
+fn cmd_keygen(opts: KeygenCmd, _log_level: u8) -> Result<()> {
+    let moderator = bp7::EndpointID::try_from(opts.moderator.as_str())?;
+    if std::path::Path::new(&opts.key).exists() {
+        bail!("[!] Key file {} already exists", opts.key);
+    }
+    let key = ModeratorKey::generate();
+    key.save(&opts.key)?;
+    println!("{} {}", moderator, key.public_key());
+    Ok(())
+}
+
+/// Approve a submission to a moderated newsgroup and publish it
+#[derive(Parser)]
+struct ApproveCmd {
+    /// File with the moderator signing key
+    #[clap(short, long)]
+    key: String,
+
+    /// Submission bundle as hex string
+    #[clap(short, long)]
+    input_newsbundle: String,
+
+    /// Output bundle as hex instead of submitting it
+    #[clap(short = 'H', long)]
+    hex: bool,
+
+    /// Output bundle as raw CBOR instead of submitting it
+    #[clap(short = 'C', long)]
+    cbor: bool,
+}
+
+fn cmd_approve(
+    opts: ApproveCmd,
+    moderators: &Moderators,
+    client: DtnClient,
+    log_level: u8,
+) -> Result<()> {
+    let raw_bytes = bp7::helpers::unhexify(&opts.input_newsbundle)?;
+    let submission: NewsBundle = raw_bytes.try_into()?;
+    let Some(group) = submission.news().submission_group().map(String::from) else {
+        bail!("[!] Not a submission to a moderated newsgroup");
+    };
+    let Some(moderator) = moderators.moderator(&group) else {
+        bail!("[!] No moderator for newsgroup {}", group);
+    };
+    let key = ModeratorKey::load(&opts.key)?;
+    let approved = approve_news(&submission, moderator, &key)?;
+
+    emit(approved, opts.hex, opts.cbor, &client, log_level)
+}
+
 fn main() -> Result<()> {
     let opts: Opts = Opts::parse();
 
     let log_level = opts.verbose;
+    let client = opts.client();
+    let moderators = match &opts.moderators {
+        Some(path) => Moderators::parse(&std::fs::read_to_string(path)?)?,
+        None => Moderators::new(),
+    };
+    let moderator_keys = match &opts.moderator_keys {
+        Some(path) => ModeratorKeys::load(path)?,
+        None => ModeratorKeys::new(),
+    };
+    let keyring = opts.keyring.as_ref().map(Keyring::load).transpose()?;
 
     match opts.subcmds {
         SubCommand::Post(post) => {
-            cmd_post(post, log_level)?;
+            cmd_post(post, &moderators, keyring.as_ref(), client, log_level)?;
         }
         SubCommand::Read(read) => {
-            cmd_read(read, log_level)?;
+            cmd_read(read, keyring.as_ref(), log_level)?;
         }
         SubCommand::Reply(reply) => {
-            cmd_reply(reply, log_level)?;
+            cmd_reply(reply, &moderators, keyring.as_ref(), client, log_level)?;
+        }
+        SubCommand::Subscribe(subscribe) => {
+            cmd_subscribe(
+                subscribe,
+                &moderators,
+                &moderator_keys,
+                keyring.as_ref(),
+                client,
+                log_level,
+            )?;
+        }
+        SubCommand::List(list) => {
+            cmd_list(list, keyring.as_ref(), log_level)?;
+        }
+        SubCommand::Export(export) => {
+            cmd_export(export, keyring.as_ref(), log_level)?;
+        }
+        SubCommand::Import(import) => {
+            cmd_import(import, keyring.as_ref(), client, log_level)?;
+        }
+        SubCommand::Atom(atom) => {
+            cmd_atom(atom, keyring.as_ref(), log_level)?;
+        }
+        SubCommand::Feed(feed) => {
+            cmd_feed(feed, &moderators, client, log_level)?;
+        }
+        SubCommand::Digest(digest) => {
+            cmd_digest(digest, client, log_level)?;
+        }
+        SubCommand::Keygen(keygen) => {
+            cmd_keygen(keygen, log_level)?;
+        }
+        SubCommand::Approve(approve) => {
+            cmd_approve(approve, &moderators, client, log_level)?;
         }
     }
 
@@ -0,0 +1,137 @@
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::{ArgAction, Parser, crate_authors, crate_version};
+use dtn7_plus::client::DtnClient;
+use dtn7_plus::news::keyring::Keyring;
+use dtn7_plus::news::moderation::{ModeratorKeys, Moderators};
+use dtn7_plus::news::nntp::NntpServer;
+use dtn7_plus::news::spool::Spool;
+
+/// Local NNTP server giving newsreaders access to DTN news
+#[derive(Parser)]
+#[clap(version = crate_version!(), author = crate_authors!())]
+struct Opts {
+    /// A level of verbosity, and can be used multiple times
+    #[clap(short, long, action = ArgAction::Count)]
+    verbose: u8,
+    /// Local web port (default = $DTN_WEB_PORT or 3000)
+    #[clap(short, long)]
+    port: Option<u16>,
+    /// Use IPv6
+    #[clap(short = '6', long)]
+    ipv6: bool,
+    /// Address for the NNTP server to listen on
+    #[clap(short, long, default_value = "127.0.0.1:1119")]
+    listen: String,
+    /// Spool directory for received articles
+    #[clap(short, long, default_value = "news")]
+    spool: String,
+    /// Sender DTN node name for posted articles (default = local node name)
+    #[clap(short, long)]
+    node_name: Option<String>,
+    /// Newsgroup to subscribe to, can be used multiple times
+    #[clap(short, long)]
+    group: Vec<String>,
+    /// Do not compress posted articles
+    #[clap(short = 'u', long)]
+    uncompressed: bool,
+    /// Bundle lifetime of posted articles, e.g., '3days'
+    #[clap(short = 'L', long, value_parser = humantime::parse_duration, default_value = "1h")]
+    lifetime: Duration,
+    /// Keep articles without Expires header this long instead of their bundle lifetime, e.g., '2weeks'
+    #[clap(short, long, value_parser = humantime::parse_duration)]
+    retention: Option<Duration>,
+    /// File with moderator endpoints, one 'newsgroups:endpoint' per line
+    #[clap(short = 'M', long)]
+    moderators: Option<String>,
+    /// File with public keys of moderators, one 'endpoint base64-key' per line
+    #[clap(short = 'K', long)]
+    moderator_keys: Option<String>,
+    /// File with keys of private newsgroups, one 'group key-id base64-key' per line
+    #[clap(short, long)]
+    keyring: Option<String>,
+    /// Interval in seconds for removing expired articles
+    #[clap(short, long, default_value_t = 60)]
+    expire_interval: u64,
+}
+
+fn main() -> Result<()> {
+    let opts: Opts = Opts::parse();
+
+    let localhost = if opts.ipv6 { "[::1]" } else { "127.0.0.1" };
+    // prefer CLI, fall back to env, then 3000
+    let port = opts.port.unwrap_or_else(|| {
+        std::env::var("DTN_WEB_PORT")
+            .ok()
+            .and_then(|s| s.parse::<u16>().ok())
+            .unwrap_or(3000)
+    });
+    let client = DtnClient::with_host_and_port(localhost.into(), port);
+    let node_name = match opts.node_name {
+        Some(name) => name,
+        None => client
+            .local_node_id()?
+            .node()
+            .ok_or_else(|| anyhow::anyhow!("[!] Local node ID has no node name"))?,
+    };
+
+    let moderators = match &opts.moderators {
+        Some(path) => Moderators::parse(&std::fs::read_to_string(path)?)?,
+        None => Moderators::new(),
+    };
+    let moderator_keys = match &opts.moderator_keys {
+        Some(path) => ModeratorKeys::load(path)?,
+        None => ModeratorKeys::new(),
+    };
+
+    // articles of moderated groups without a valid approval are not stored
+    let mut spool =
+        Spool::open(&opts.spool)?.with_moderation(moderators.clone(), Arc::new(moderator_keys));
+    for (path, err) in spool.skipped() {
+        eprintln!("[!] Skipping {}: {}", path.display(), err);
+    }
+    if let Some(retention) = opts.retention {
+        spool = spool.with_retention(retention);
+    }
+    let spool = Arc::new(Mutex::new(spool));
+    let mut server = NntpServer::new(spool.clone(), Arc::new(client.clone()), &node_name)
+        .with_groups(opts.group)
+        .compression(!opts.uncompressed)
+        .lifetime(opts.lifetime)
+        .moderators(moderators)
+        .on_error(|msg| eprintln!("[!] {}", msg));
+    if let Some(path) = &opts.keyring {
+        server = server.keyring(Keyring::load(path)?);
+    }
+
+    let expire_interval = Duration::from_secs(opts.expire_interval);
+    let verbose = opts.verbose;
+    std::thread::spawn(move || {
+        loop {
+            std::thread::sleep(expire_interval);
+            match spool.lock().unwrap().expire() {
+                Ok(expired) if verbose > 0 && !expired.is_empty() => {
+                    eprintln!("[*] Expired {} articles", expired.len())
+                }
+                Err(err) => eprintln!("[!] Failed to expire articles: {}", err),
+                _ => {}
+            }
+        }
+    });
+
+    let receiver = server.clone();
+    std::thread::spawn(move || {
+        if let Err(err) = receiver.receive(&client) {
+            eprintln!("[!] Receiving news failed: {}", err);
+        }
+    });
+
+    if opts.verbose > 0 {
+        eprintln!("[*] Serving NNTP on {} as {}", opts.listen, node_name);
+    }
+    server.serve(TcpListener::bind(&opts.listen)?)?;
+    Ok(())
+}
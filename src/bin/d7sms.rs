@@ -0,0 +1,215 @@
+use std::{
+    convert::TryFrom,
+    io::{Read, Write},
+};
+
+use anyhow::{Result, bail};
+use bp7::Bundle;
+use clap::{ArgAction, Parser, crate_authors, crate_version};
+use dtn7_plus::client::{DtnClient, Message};
+use dtn7_plus::sms::{SMSBundle, new_sms};
+
+#[derive(Parser)]
+#[clap(version = crate_version!(), author = crate_authors!())]
+struct Opts {
+    /// A level of verbosity, and can be used multiple times
+    #[clap(short, long, action = ArgAction::Count)]
+    verbose: u8,
+    /// Local web port (default = $DTN_WEB_PORT or 3000)
+    #[clap(short, long)]
+    port: Option<u16>,
+    /// Use IPv6
+    #[clap(short = '6', long)]
+    ipv6: bool,
+    #[clap(subcommand)]
+    subcmds: SubCommand,
+}
+
+impl Opts {
+    fn client(&self) -> DtnClient {
+        let localhost = if self.ipv6 { "[::1]" } else { "127.0.0.1" };
+        // prefer CLI, fall back to env, then 3000
+        let port = self.port.unwrap_or_else(|| {
+            std::env::var("DTN_WEB_PORT")
+                .ok()
+                .and_then(|s| s.parse::<u16>().ok())
+                .unwrap_or(3000)
+        });
+        DtnClient::with_host_and_port(localhost.into(), port)
+    }
+}
+
+#[derive(Parser)]
+enum SubCommand {
+    Send(SendCmd),
+    Read(ReadCmd),
+    Listen(ListenCmd),
+}
+
+/// Create a new sms and submit it to the local dtnd
+#[derive(Parser)]
+struct SendCmd {
+    /// Sender IPN node number
+    #[clap(short, long)]
+    src: u64,
+
+    /// Receiver IPN node number
+    #[clap(short, long)]
+    dst: u64,
+
+    /// Message body or '-' to read from stdin
+    #[clap(short, long)]
+    message: String,
+
+    /// Do not compress the message text
+    #[clap(short = 'u', long)]
+    uncompressed: bool,
+
+    /// Output bundle as hex instead of submitting it
+    #[clap(short = 'H', long)]
+    hex: bool,
+
+    /// Output bundle as raw CBOR instead of submitting it
+    #[clap(short = 'C', long)]
+    cbor: bool,
+}
+
+fn cmd_send(opts: SendCmd, client: DtnClient, log_level: u8) -> Result<()> {
+    let msg = if opts.message == "-" {
+        let mut raw_bytes: Vec<u8> = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut raw_bytes)
+            .expect("Error reading from stdin.");
+        String::from_utf8(raw_bytes)?
+    } else {
+        opts.message
+    };
+    let mut sms = new_sms(opts.src, opts.dst, &msg, !opts.uncompressed)?;
+    let bin_bundle = sms.to_cbor();
+
+    if opts.hex {
+        println!("{}", bp7::helpers::hexify(&bin_bundle));
+    } else if opts.cbor {
+        std::io::stdout().write_all(&bin_bundle).unwrap();
+    } else {
+        let res = client.insert_bundle(bin_bundle)?;
+        if log_level > 0 {
+            println!("Bundle-Id: {}", sms.id());
+            println!("Result: {}", res);
+        }
+    }
+
+    Ok(())
+}
+
+/// Decode sms bundle in various forms
+#[derive(Parser)]
+struct ReadCmd {
+    /// Read bundle provided as hex string
+    #[clap(short = 'H', long)]
+    hex: Option<String>,
+    /// Read bundle from a file or '-' for stdin
+    #[clap(short, long)]
+    path: Option<String>,
+}
+
+fn cmd_read(opts: ReadCmd, _log_level: u8) -> Result<()> {
+    let bytes = if let Some(hex_str) = opts.hex {
+        bp7::helpers::unhexify(&hex_str)?
+    } else if let Some(path) = opts.path.filter(|p| p != "-") {
+        std::fs::read(path)?
+    } else {
+        let mut raw_bytes: Vec<u8> = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut raw_bytes)
+            .expect("Error reading from stdin.");
+        raw_bytes
+    };
+    let sms = SMSBundle::try_from(bytes)?;
+    println!("{}", sms);
+    Ok(())
+}
+
+/// Listen for incoming sms bundles on the local dtnd
+#[derive(Parser)]
+struct ListenCmd {
+    /// Local endpoint to subscribe to
+    #[clap(short, long, default_value = "sms")]
+    endpoint: String,
+}
+
+fn cmd_listen(opts: ListenCmd, client: DtnClient, log_level: u8) -> Result<()> {
+    client.register_application_endpoint(&opts.endpoint)?;
+    let mut wscon = client.ws()?;
+
+    wscon.write_text("/bundle")?;
+    let msg = wscon.read_text()?;
+    if msg.starts_with("200 tx mode: bundle") {
+        if log_level > 0 {
+            eprintln!("[*] {}", msg);
+        }
+    } else {
+        bail!("[!] Failed to set mode to `bundle`");
+    }
+
+    wscon.write_text(&format!("/subscribe {}", opts.endpoint))?;
+    let msg = wscon.read_text()?;
+    if msg.starts_with("200 subscribed") {
+        if log_level > 0 {
+            eprintln!("[*] {}", msg);
+        }
+    } else {
+        bail!("[!] Failed to subscribe to service");
+    }
+
+    loop {
+        match wscon.read_message()? {
+            Message::Text(txt) => {
+                eprintln!("[!] Unexpected response: {}", txt);
+                break;
+            }
+            Message::Binary(bin) => {
+                let bndl = Bundle::try_from(bin.to_vec())?;
+                let bid = bndl.id();
+                match SMSBundle::try_from(bndl) {
+                    Ok(sms) => println!("{}", sms),
+                    Err(err) => eprintln!(
+                        "[!] Not an sms bundle {}: {:#}",
+                        bid,
+                        anyhow::Error::from(err)
+                    ),
+                }
+            }
+            Message::Close(_) => {
+                if log_level > 0 {
+                    eprintln!("[<] Close")
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let opts: Opts = Opts::parse();
+
+    let log_level = opts.verbose;
+    let client = opts.client();
+
+    match opts.subcmds {
+        SubCommand::Send(send) => {
+            cmd_send(send, client, log_level)?;
+        }
+        SubCommand::Read(read) => {
+            cmd_read(read, log_level)?;
+        }
+        SubCommand::Listen(listen) => {
+            cmd_listen(listen, client, log_level)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use anyhow::Result;
+use clap::{ArgAction, Parser, crate_authors, crate_version};
+use dtn7_plus::client::DtnClient;
+use dtn7_plus::sms::mail::{MailGateway, MailGatewayConfig};
+
+/// Gateway forwarding sms to email and email replies back to sms
+#[derive(Parser)]
+#[clap(version = crate_version!(), author = crate_authors!())]
+struct Opts {
+    /// A level of verbosity, and can be used multiple times
+    #[clap(short, long, action = ArgAction::Count)]
+    verbose: u8,
+    /// Local web port (default = $DTN_WEB_PORT or 3000)
+    #[clap(short, long)]
+    port: Option<u16>,
+    /// Use IPv6
+    #[clap(short = '6', long)]
+    ipv6: bool,
+    /// Local endpoint to receive sms on
+    #[clap(short, long, default_value = "sms")]
+    endpoint: String,
+    /// Gateway configuration as JSON
+    #[clap(short, long)]
+    config: String,
+}
+
+fn main() -> Result<()> {
+    let opts: Opts = Opts::parse();
+
+    let localhost = if opts.ipv6 { "[::1]" } else { "127.0.0.1" };
+    // prefer CLI, fall back to env, then 3000
+    let port = opts.port.unwrap_or_else(|| {
+        std::env::var("DTN_WEB_PORT")
+            .ok()
+            .and_then(|s| s.parse::<u16>().ok())
+            .unwrap_or(3000)
+    });
+    let client = DtnClient::with_host_and_port(localhost.into(), port);
+
+    let config = MailGatewayConfig::load_json(&opts.config)?;
+    if opts.verbose > 0 {
+        eprintln!(
+            "[*] Forwarding sms from {} to {} via {}",
+            opts.endpoint,
+            config.recipients.join(", "),
+            config.smtp_relay
+        );
+    }
+    MailGateway::new(config)
+        .on_error(|msg| eprintln!("[!] {}", msg))
+        .run(&client, &opts.endpoint)
+}
@@ -1,21 +1,59 @@
 use anyhow::{Result, anyhow, bail};
 use bp7::dtntime::DtnTimeHelpers;
 use bp7::*;
-use clap::{Arg, ArgAction, Command, crate_authors, crate_version};
+use clap::{Arg, ArgAction, ArgGroup, Command, crate_authors, crate_version};
 use dtn7_plus::client::DtnClient;
+use dtn7_plus::location::geocast::{GeocastDecision, GeocastFilter};
 use dtn7_plus::location::*;
 use std::convert::TryFrom;
+use std::fs;
 use tungstenite::Message;
 
+fn parse_coords(text: &str) -> Result<(f32, f32)> {
+    let coords: Vec<f32> = text
+        .trim()
+        .split(',')
+        .map(|c| c.trim().parse())
+        .collect::<Result<_, _>>()?;
+    match coords[..] {
+        [a, b] => Ok((a, b)),
+        _ => bail!("expected two comma separated coordinates: {}", text),
+    }
+}
+
+fn read_pos_from_file(filename: &str, file_is_xy: bool) -> Result<Location> {
+    let coords = parse_coords(&fs::read_to_string(filename)?)?;
+    if file_is_xy {
+        Ok(Location::XY(coords))
+    } else {
+        Ok(Location::LatLon(coords))
+    }
+}
+
+/// Print the payload of a delivered geocast bundle, e.g., an alert
+fn handle_geocast_bundle(bndl: &Bundle) {
+    if let Some(payload) = bndl.payload().filter(|payload| !payload.is_empty()) {
+        println!(
+            "[*] Geocast {}: {}",
+            bndl.id(),
+            String::from_utf8_lossy(payload)
+        );
+    }
+}
+
 fn handle_incoming_bundle(
     bndl: &Bundle,
     rest: Option<String>,
     verbose: bool,
 ) -> anyhow::Result<()> {
-    let cblock = bndl
-        .extension_block_by_type(LOCATION_BLOCK)
-        .ok_or_else(|| anyhow!("no extension block"))?;
-    let loc_data = get_location_data(cblock)?;
+    // a bundle can carry a fence next to the position
+    let loc_data = bndl
+        .canonicals
+        .iter()
+        .filter(|cblock| cblock.block_type == LOCATION_BLOCK)
+        .filter_map(|cblock| get_location_data(cblock).ok())
+        .find(|data| matches!(data, LocationBlockData::Position(..)))
+        .ok_or_else(|| anyhow!("no position block"))?;
     if let LocationBlockData::Position(flags, pos) = loc_data {
         if let Location::LatLon(coords) = pos {
             let mut log_out = format!(
@@ -103,6 +141,43 @@ fn main() -> Result<()> {
                 .long("rest")
                 .help("REST endpoint to dump incoming location data, e.g., http://127.0.0.1:1880/dtnpos")
         )
+        .arg(
+            Arg::new("LATLON")
+                .short('l')
+                .long("latlon")
+                .value_name("LAT,LON")
+                .help("Own position for geocast bundles, e.g., '49.87,8.65'")
+        )
+        .arg(
+            Arg::new("XY")
+                .short('x')
+                .long("xy")
+                .value_name("X,Y")
+                .help("Own position in XY coordinates for geocast bundles")
+        )
+        .arg(
+            Arg::new("FILE")
+                .short('f')
+                .long("file")
+                .value_name("FILE")
+                .help("Read own position from file for every geocast bundle, e.g., updated by a GPS daemon")
+        )
+        .arg(
+            Arg::new("file_is_xy")
+                .short('X')
+                .long("file-is-xy")
+                .help("Coordinates in position file are XY")
+                .requires("FILE")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("relay")
+                .short('R')
+                .long("relay")
+                .help("Treat geocast bundles for other areas as carried towards their area by dtnd instead of dropped, they are never delivered locally")
+                .action(ArgAction::SetTrue),
+        )
+        .group(ArgGroup::new("POSITION").args(["LATLON", "XY", "FILE"]))
         .get_matches();
 
     let verbose: bool = matches.get_flag("verbose");
@@ -128,6 +203,18 @@ fn main() -> Result<()> {
         .to_owned();
     let rest: Option<String> = matches.get_one::<String>("rest").cloned();
 
+    // without an own position all geocast bundles are treated as being for other areas
+    let position_file = matches.get_one::<String>("FILE").cloned();
+    let file_is_xy = matches.get_flag("file_is_xy");
+    let mut geocast = if let Some(latlon) = matches.get_one::<String>("LATLON") {
+        GeocastFilter::new(Location::LatLon(parse_coords(latlon)?))
+    } else if let Some(xy) = matches.get_one::<String>("XY") {
+        GeocastFilter::new(Location::XY(parse_coords(xy)?))
+    } else {
+        GeocastFilter::unknown()
+    }
+    .relay(matches.get_flag("relay"));
+
     client.register_application_endpoint(&endpoint)?;
     let mut wscon = client.ws()?;
 
@@ -159,8 +246,51 @@ fn main() -> Result<()> {
                     Bundle::try_from(bin.to_vec()).expect("Error decoding bundle from server");
                 if bndl.is_administrative_record() {
                     eprintln!("[!] Handling of administrative records not yet implemented!");
-                } else if handle_incoming_bundle(&bndl, rest.clone(), verbose).is_err() && verbose {
-                    eprintln!("[!] Not a position bundle: {}", bndl.id());
+                    continue;
+                }
+                if let Some(filename) = &position_file {
+                    match read_pos_from_file(filename, file_is_xy) {
+                        Ok(pos) => geocast.set_position(pos),
+                        Err(err) => eprintln!("[!] Failed to read position: {}", err),
+                    }
+                }
+                match geocast.decide(&bndl) {
+                    GeocastDecision::Deliver => {
+                        if verbose {
+                            for hop in trace::trace_path(&bndl) {
+                                eprintln!(
+                                    "[*] Trace {}: {} {:?} {:?}",
+                                    bndl.id(),
+                                    hop.node,
+                                    hop.location,
+                                    hop.timestamp.map(|ts| ts.string())
+                                );
+                            }
+                        }
+                        let is_geocast = geocast::get_fence(&bndl).is_some();
+                        if is_geocast {
+                            handle_geocast_bundle(&bndl);
+                        }
+                        if handle_incoming_bundle(&bndl, rest.clone(), verbose).is_err()
+                            && verbose
+                            && !is_geocast
+                        {
+                            eprintln!("[!] Not a position bundle: {}", bndl.id());
+                        }
+                    }
+                    GeocastDecision::Forward => {
+                        if verbose {
+                            eprintln!(
+                                "[*] Not delivering geocast bundle for another area: {}",
+                                bndl.id()
+                            );
+                        }
+                    }
+                    GeocastDecision::Drop => {
+                        if verbose {
+                            eprintln!("[*] Dropped geocast bundle for another area: {}", bndl.id());
+                        }
+                    }
                 }
             }
             Message::Ping(_) => {
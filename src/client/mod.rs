@@ -93,6 +93,16 @@ impl DtnClient {
         Ok(())
     }
 
+    /// Insert a complete CBOR encoded bundle into the local node via rest interface
+    pub fn insert_bundle(&self, bundle: Vec<u8>) -> Result<String, ClientError> {
+        Ok(
+            attohttpc::post(format!("http://{}:{}/insert", self.localhost, self.port))
+                .bytes(bundle)
+                .send()?
+                .text()?,
+        )
+    }
+
     /// Constructs a new websocket connection to the configured dtn7 client
     pub fn ws(&self) -> anyhow::Result<DtnWsConnection<std::net::TcpStream>> {
         let stream = std::net::TcpStream::connect(format!("{}:{}", self.localhost, self.port))?;
@@ -0,0 +1,201 @@
+//! Compression of short text payloads used by sms and news
+//!
+//! The compression identifier is encoded backwards compatible to the former `comp: bool` flag:
+//! `false` means no compression, `true` means smaz and any other algorithm is encoded as an
+//! unsigned integer.
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use thiserror::Error;
+
+/// Raw content dictionary for zstd tuned for short German and English messages
+const ZSTD_DICTIONARY: &[u8] = include_bytes!("dictionary.txt");
+const ZSTD_LEVEL: i32 = 19;
+/// Upper bound for decompressed payloads to protect against decompression bombs
+const MAX_DECOMPRESSED_SIZE: usize = 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum CompressionError {
+    #[error("failed to decompress smaz data: {0}")]
+    Smaz(#[from] smaz::DecompressError),
+    #[error("failed to decompress zstd data: {0}")]
+    Zstd(#[from] std::io::Error),
+    #[error("decompressed data exceeds {MAX_DECOMPRESSED_SIZE} bytes")]
+    TooLarge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(u8)]
+pub enum Compression {
+    /// Plain data
+    #[default]
+    None = 0,
+    /// smaz, tuned for short English text
+    Smaz = 1,
+    /// zstd with a shipped dictionary for German and English text
+    Zstd = 2,
+}
+
+impl Compression {
+    /// All supported algorithms, ordered by preference on equal output size
+    pub const ALL: [Compression; 3] = [Compression::None, Compression::Smaz, Compression::Zstd];
+
+    pub fn compress(&self, indata: &[u8]) -> Vec<u8> {
+        match self {
+            Compression::None => indata.to_vec(),
+            Compression::Smaz => smaz::compress(indata),
+            Compression::Zstd => {
+                zstd::bulk::Compressor::with_dictionary(ZSTD_LEVEL, ZSTD_DICTIONARY)
+                    .and_then(|mut c| c.compress(indata))
+                    .expect("Fatal failure, could not zstd compress data")
+            }
+        }
+    }
+
+    pub fn decompress(&self, indata: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        match self {
+            Compression::None => Ok(indata.to_vec()),
+            Compression::Smaz => Ok(smaz::decompress(indata)?),
+            Compression::Zstd => {
+                let size = zstd::zstd_safe::get_frame_content_size(indata)
+                    .ok()
+                    .flatten()
+                    .unwrap_or(MAX_DECOMPRESSED_SIZE as u64);
+                if size > MAX_DECOMPRESSED_SIZE as u64 {
+                    return Err(CompressionError::TooLarge);
+                }
+                Ok(zstd::bulk::Decompressor::with_dictionary(ZSTD_DICTIONARY)?
+                    .decompress(indata, size as usize)?)
+            }
+        }
+    }
+
+    /// Choose the algorithm producing the smallest combined output for all inputs
+    pub fn smallest(inputs: &[&[u8]]) -> Compression {
+        Compression::ALL
+            .iter()
+            .copied()
+            .min_by_key(|c| inputs.iter().map(|i| c.compress(i).len()).sum::<usize>())
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::None => write!(f, "none"),
+            Compression::Smaz => write!(f, "smaz"),
+            Compression::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+impl Serialize for Compression {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Compression::None => serializer.serialize_bool(false),
+            Compression::Smaz => serializer.serialize_bool(true),
+            _ => serializer.serialize_u8(*self as u8),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Compression {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct CompressionVisitor;
+
+        impl<'de> Visitor<'de> for CompressionVisitor {
+            type Value = Compression;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a compression flag or algorithm identifier")
+            }
+
+            fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(if v {
+                    Compression::Smaz
+                } else {
+                    Compression::None
+                })
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                match v {
+                    0 => Ok(Compression::None),
+                    1 => Ok(Compression::Smaz),
+                    2 => Ok(Compression::Zstd),
+                    _ => Err(de::Error::invalid_value(de::Unexpected::Unsigned(v), &self)),
+                }
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                let v = u64::try_from(v)
+                    .map_err(|_| de::Error::invalid_value(de::Unexpected::Signed(v), &self))?;
+                self.visit_u64(v)
+            }
+        }
+
+        deserializer.deserialize_any(CompressionVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compression;
+
+    #[test]
+    fn test_compression_roundtrip() {
+        let text = "Bitte Empfang bestätigen, die Brücke über den Fluss ist gesperrt.";
+        for c in Compression::ALL {
+            let compressed = c.compress(text.as_bytes());
+            assert_eq!(c.decompress(&compressed).unwrap(), text.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_compression_smallest() {
+        let german =
+            "Lagebericht: Straße gesperrt, Stromausfall im Gebiet. Bitte Empfang bestätigen.";
+        let algo = Compression::smallest(&[german.as_bytes()]);
+        assert_eq!(algo, Compression::Zstd);
+        assert!(algo.compress(german.as_bytes()).len() < smaz::compress(german.as_bytes()).len());
+
+        assert_eq!(Compression::smallest(&[b"x"]), Compression::None);
+    }
+
+    #[test]
+    fn test_compression_legacy_flag() {
+        let smaz: Compression =
+            serde_cbor::from_slice(&serde_cbor::to_vec(&true).unwrap()).unwrap();
+        assert_eq!(smaz, Compression::Smaz);
+        let none: Compression =
+            serde_cbor::from_slice(&serde_cbor::to_vec(&false).unwrap()).unwrap();
+        assert_eq!(none, Compression::None);
+
+        assert_eq!(
+            serde_cbor::to_vec(&Compression::Smaz).unwrap(),
+            serde_cbor::to_vec(&true).unwrap()
+        );
+        let buf = serde_cbor::to_vec(&Compression::Zstd).unwrap();
+        assert_eq!(
+            serde_cbor::from_slice::<Compression>(&buf).unwrap(),
+            Compression::Zstd
+        );
+        assert!(serde_cbor::from_slice::<Compression>(&serde_cbor::to_vec(&7u8).unwrap()).is_err());
+    }
+}
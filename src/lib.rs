@@ -9,6 +9,12 @@ pub mod sms;
 #[cfg(feature = "news")]
 pub mod news;
 
+#[cfg(any(feature = "sms", feature = "news"))]
+pub mod compression;
+
+#[cfg(any(feature = "sms", feature = "news"))]
+pub mod rfc5322;
+
 #[cfg(feature = "location")]
 pub mod location;
 
@@ -1,6 +1,6 @@
-use super::{Location, NodeTypeFlags};
+use super::{Location, NodeTypeFlags, geo};
 use bp7::canonical::{CanonicalBlockType, CanonicalData, new_canonical_block};
-use bp7::{CanonicalBlock, EndpointID};
+use bp7::{CanonicalBlock, DtnTime, EndpointID};
 use derive_try_from_primitive::TryFromPrimitive;
 use serde::de::{SeqAccess, Visitor};
 use serde::ser::{SerializeSeq, Serializer};
@@ -21,6 +21,8 @@ pub enum LocationError {
     PayloadMissing,
     #[error("invalid location block")]
     InvalidLocationBlock,
+    #[error("location block is not a fence")]
+    NotAFence,
 }
 
 #[derive(Debug, Clone, PartialEq, TryFromPrimitive, Serialize, Deserialize)]
@@ -39,9 +41,82 @@ pub const LOCATION_BLOCK: CanonicalBlockType = 223;
 #[derive(Debug, Clone, PartialEq)]
 pub enum LocationBlockData {
     Position(NodeTypeFlags, Location),
+    /// Axis-aligned ellipse given by its center and the east-west and north-south semi-axes
+    ///
+    /// Radii are in metres, for `XY` locations in units of the coordinate system.
     FenceEllipse(Location, u64, u64),
+    /// Rectangle given by its top left (north-west) and bottom right (south-east) corners
+    ///
+    /// `LatLon` rectangles are bounded by parallels and meridians and may cross the antimeridian.
     FenceRect(Location, Location),
-    Trace(NodeTypeFlags, EndpointID, Location),
+    /// Location of a node the bundle passed, optionally with the time it was there
+    Trace(NodeTypeFlags, EndpointID, Location, Option<DtnTime>),
+}
+
+/// Offset of `pos` from `origin` towards east and north along the geodesic, or along the axes for `XY`
+fn offset(origin: &Location, pos: &Location) -> Option<(f64, f64)> {
+    let distance = geo::distance(origin, pos)?;
+    let (sin, cos) = geo::bearing(origin, pos)?.to_radians().sin_cos();
+    Some((distance * sin, distance * cos))
+}
+
+/// Tolerance in fence units for offsets on a degenerate ellipse, covering rounding of the offset
+const DEGENERATE_TOLERANCE: f64 = 1e-6;
+
+/// Check whether an offset lies within an axis-aligned ellipse
+///
+/// A zero radius degenerates the ellipse to a segment on the other axis, two zero radii to its center.
+fn in_ellipse((dx, dy): (f64, f64), r1: u64, r2: u64) -> bool {
+    let (r1, r2) = (r1 as f64, r2 as f64);
+    let on_axis = |offset: f64, radius: f64| offset.abs() <= radius + DEGENERATE_TOLERANCE;
+    match (r1 == 0.0, r2 == 0.0) {
+        (true, true) => on_axis(dx, 0.0) && on_axis(dy, 0.0),
+        (true, false) => on_axis(dx, 0.0) && on_axis(dy, r2),
+        (false, true) => on_axis(dx, r1) && on_axis(dy, 0.0),
+        (false, false) => (dx / r1).powi(2) + (dy / r2).powi(2) <= 1.0,
+    }
+}
+
+fn in_range(value: f32, a: f32, b: f32) -> bool {
+    value >= a.min(b) && value <= a.max(b)
+}
+
+impl LocationBlockData {
+    pub fn is_fence(&self) -> bool {
+        matches!(
+            self,
+            LocationBlockData::FenceEllipse(..) | LocationBlockData::FenceRect(..)
+        )
+    }
+    /// Check whether a position is inside a fence, borders included
+    ///
+    /// Returns `None` if this is not a fence or if the fence and the position cannot be compared,
+    /// e.g., `Human` or `WFW` locations or `LatLon` fences with `XY` positions.
+    pub fn contains(&self, pos: &Location) -> Option<bool> {
+        match (self, pos) {
+            (LocationBlockData::FenceEllipse(center, r1, r2), pos) => {
+                offset(center, pos).map(|offset| in_ellipse(offset, *r1, *r2))
+            }
+            (
+                LocationBlockData::FenceRect(Location::LatLon(nw), Location::LatLon(se)),
+                Location::LatLon((lat, lon)),
+            ) => {
+                let (west, east) = (nw.1, se.1);
+                let within_lon = if west <= east {
+                    *lon >= west && *lon <= east
+                } else {
+                    // crossing the antimeridian
+                    *lon >= west || *lon <= east
+                };
+                Some(in_range(*lat, nw.0, se.0) && within_lon)
+            }
+            (
+                LocationBlockData::FenceRect(Location::XY(corner1), Location::XY(corner2)),
+                Location::XY((x, y)),
+            ) => Some(in_range(*x, corner1.0, corner2.0) && in_range(*y, corner1.1, corner2.1)),
+            _ => None,
+        }
+    }
 }
 
 impl Serialize for LocationBlockData {
@@ -72,12 +147,16 @@ impl Serialize for LocationBlockData {
                 seq.serialize_element(&bottomright)?;
                 seq.end()
             }
-            LocationBlockData::Trace(info, node, coords) => {
-                let mut seq = serializer.serialize_seq(Some(4))?;
+            LocationBlockData::Trace(info, node, coords, timestamp) => {
+                let len = if timestamp.is_some() { 5 } else { 4 };
+                let mut seq = serializer.serialize_seq(Some(len))?;
                 seq.serialize_element(&(LocationBlockType::Trace as u8))?;
                 seq.serialize_element(&info.bits())?;
                 seq.serialize_element(&node)?;
                 seq.serialize_element(&coords)?;
+                if let Some(timestamp) = timestamp {
+                    seq.serialize_element(timestamp)?;
+                }
                 seq.end()
             }
         }
@@ -156,7 +235,9 @@ impl<'de> Deserialize<'de> for LocationBlockData {
                         let coords: Location = seq
                             .next_element()?
                             .ok_or_else(|| de::Error::invalid_length(3, &self))?;
-                        Ok(LocationBlockData::Trace(info, node, coords))
+                        // timestamp is optional
+                        let timestamp: Option<DtnTime> = seq.next_element()?;
+                        Ok(LocationBlockData::Trace(info, node, coords, timestamp))
                     }
                 }
             }
@@ -230,6 +311,17 @@ mod tests {
             NodeTypeFlags::MOBILE,
             EndpointID::try_from("dtn://node1").unwrap(),
             loc,
+            None,
+        );
+        let buf = serde_cbor::to_vec(&data).unwrap();
+        let data2 = serde_cbor::from_slice(&buf).unwrap();
+        assert_eq!(data, data2);
+
+        let data = LocationBlockData::Trace(
+            NodeTypeFlags::empty(),
+            EndpointID::try_from("ipn:23.0").unwrap(),
+            Location::XY((1.0, 2.0)),
+            Some(704_000_000_000),
         );
         let buf = serde_cbor::to_vec(&data).unwrap();
         let data2 = serde_cbor::from_slice(&buf).unwrap();
@@ -248,4 +340,102 @@ mod tests {
         let data2 = get_location_data(&cblock2).unwrap();
         assert_eq!(data, data2);
     }
+
+    #[test]
+    fn test_fence_ellipse_contains() {
+        // 1 km east-west, 500 m north-south around Darmstadt
+        let fence = LocationBlockData::FenceEllipse(Location::LatLon((49.8728, 8.6512)), 1000, 500);
+        assert_eq!(
+            fence.contains(&Location::LatLon((49.8728, 8.6512))),
+            Some(true)
+        );
+        // ~0.0125 degrees of longitude are about 900 m at this latitude
+        assert_eq!(
+            fence.contains(&Location::LatLon((49.8728, 8.6637))),
+            Some(true)
+        );
+        assert_eq!(
+            fence.contains(&Location::LatLon((49.8728, 8.6662))),
+            Some(false)
+        );
+        // ~0.0036 degrees of latitude are about 400 m
+        assert_eq!(
+            fence.contains(&Location::LatLon((49.8692, 8.6512))),
+            Some(true)
+        );
+        assert_eq!(
+            fence.contains(&Location::LatLon((49.8683, 8.6512))),
+            Some(false)
+        );
+        assert_eq!(fence.contains(&Location::XY((0.0, 0.0))), None);
+
+        let fence = LocationBlockData::FenceEllipse(Location::XY((10.0, 10.0)), 4, 2);
+        assert_eq!(fence.contains(&Location::XY((14.0, 10.0))), Some(true));
+        assert_eq!(fence.contains(&Location::XY((10.0, 12.5))), Some(false));
+        assert_eq!(fence.contains(&Location::XY((12.0, 11.0))), Some(true));
+        assert_eq!(fence.contains(&Location::XY((13.0, 11.5))), Some(false));
+    }
+
+    #[test]
+    fn test_fence_ellipse_degenerate() {
+        let point = LocationBlockData::FenceEllipse(Location::XY((0.0, 0.0)), 0, 0);
+        assert_eq!(point.contains(&Location::XY((0.0, 0.0))), Some(true));
+        assert_eq!(point.contains(&Location::XY((0.0, 1.0))), Some(false));
+        let point = LocationBlockData::FenceEllipse(Location::LatLon((49.8728, 8.6512)), 0, 0);
+        assert_eq!(
+            point.contains(&Location::LatLon((49.8728, 8.6512))),
+            Some(true)
+        );
+        assert_eq!(
+            point.contains(&Location::LatLon((-33.8688, 151.2093))),
+            Some(false)
+        );
+
+        // north-south segment
+        let segment = LocationBlockData::FenceEllipse(Location::XY((0.0, 0.0)), 0, 5);
+        assert_eq!(segment.contains(&Location::XY((0.0, -5.0))), Some(true));
+        assert_eq!(segment.contains(&Location::XY((0.0, 1e6))), Some(false));
+        assert_eq!(segment.contains(&Location::XY((0.5, 0.0))), Some(false));
+        // east-west segment
+        let segment = LocationBlockData::FenceEllipse(Location::XY((0.0, 0.0)), 5, 0);
+        assert_eq!(segment.contains(&Location::XY((3.0, 0.0))), Some(true));
+        assert_eq!(segment.contains(&Location::XY((6.0, 0.0))), Some(false));
+        assert_eq!(segment.contains(&Location::XY((0.0, 1.0))), Some(false));
+    }
+
+    #[test]
+    fn test_fence_rect_contains() {
+        let fence = LocationBlockData::FenceRect(
+            Location::LatLon((50.0, 8.0)),
+            Location::LatLon((49.0, 9.0)),
+        );
+        assert_eq!(fence.contains(&Location::LatLon((49.5, 8.5))), Some(true));
+        assert_eq!(fence.contains(&Location::LatLon((50.5, 8.5))), Some(false));
+        assert_eq!(fence.contains(&Location::LatLon((49.5, 9.5))), Some(false));
+
+        let pacific = LocationBlockData::FenceRect(
+            Location::LatLon((10.0, 170.0)),
+            Location::LatLon((-10.0, -170.0)),
+        );
+        assert_eq!(
+            pacific.contains(&Location::LatLon((0.0, 179.0))),
+            Some(true)
+        );
+        assert_eq!(
+            pacific.contains(&Location::LatLon((0.0, -175.0))),
+            Some(true)
+        );
+        assert_eq!(pacific.contains(&Location::LatLon((0.0, 0.0))), Some(false));
+
+        let fence =
+            LocationBlockData::FenceRect(Location::XY((0.0, 10.0)), Location::XY((10.0, 0.0)));
+        assert_eq!(fence.contains(&Location::XY((10.0, 5.0))), Some(true));
+        assert_eq!(fence.contains(&Location::XY((-1.0, 5.0))), Some(false));
+        assert_eq!(fence.contains(&Location::Human("Darmstadt".into())), None);
+
+        let position = LocationBlockData::Position(NodeTypeFlags::MOBILE, Location::XY((0.0, 0.0)));
+        assert_eq!(position.contains(&Location::XY((0.0, 0.0))), None);
+        let words = LocationBlockData::FenceEllipse(Location::WFW("A-B-C".into()), 1, 1);
+        assert_eq!(words.contains(&Location::WFW("A-B-C".into())), None);
+    }
 }
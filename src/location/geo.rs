@@ -0,0 +1,464 @@
+//! Geodesic calculations on locations
+//!
+//! `LatLon` locations are WGS84 coordinates in degrees, distances are in metres and bearings in
+//! degrees clockwise from north. `XY` locations use planar geometry instead, with distances in
+//! units of the coordinate system and bearings clockwise from the y axis.
+//!
+//! Calculations between other or mixed kinds of locations return `None`.
+use super::Location;
+use geographiclib_rs::{DirectGeodesic, Geodesic, InverseGeodesic};
+
+/// Mean earth radius in metres, used for spherical calculations
+pub const EARTH_RADIUS: f64 = 6_371_008.8;
+/// Semi-major axis of the WGS84 ellipsoid in metres
+const WGS84_A: f64 = 6_378_137.0;
+/// Flattening of the WGS84 ellipsoid
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+const VINCENTY_ITERATIONS: usize = 200;
+
+/// Method used for `LatLon` calculations, `XY` calculations are always planar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Accuracy {
+    /// Great circles on a sphere with the mean earth radius, fast but off by up to 0.5%
+    Spherical,
+    /// Vincenty's formulae on the WGS84 ellipsoid, accurate to less than a millimetre
+    ///
+    /// Falls back to Karney for nearly antipodal points, where the iteration does not converge.
+    Vincenty,
+    /// Karney's algorithm on the WGS84 ellipsoid, accurate to a few nanometres for all points
+    #[default]
+    Karney,
+}
+
+fn normalize_lon(lon: f64) -> f64 {
+    (lon + 540.0).rem_euclid(360.0) - 180.0
+}
+
+fn normalize_bearing(bearing: f64) -> f64 {
+    bearing.rem_euclid(360.0)
+}
+
+fn latlon((lat, lon): (f32, f32)) -> (f64, f64) {
+    (f64::from(lat), f64::from(lon))
+}
+
+/// Distance and initial bearing between two points on the sphere
+fn spherical_inverse((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> (f64, f64) {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let dphi = phi2 - phi1;
+    let dlambda = (lon2 - lon1).to_radians();
+    let a = (dphi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (dlambda / 2.0).sin().powi(2);
+    let distance = 2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin();
+    let bearing = (dlambda.sin() * phi2.cos())
+        .atan2(phi1.cos() * phi2.sin() - phi1.sin() * phi2.cos() * dlambda.cos());
+    (distance, bearing.to_degrees())
+}
+
+fn spherical_direct((lat, lon): (f64, f64), bearing: f64, distance: f64) -> (f64, f64) {
+    let phi1 = lat.to_radians();
+    let theta = bearing.to_radians();
+    let delta = distance / EARTH_RADIUS;
+    let phi2 = (phi1.sin() * delta.cos() + phi1.cos() * delta.sin() * theta.cos()).asin();
+    let dlambda =
+        (theta.sin() * delta.sin() * phi1.cos()).atan2(delta.cos() - phi1.sin() * phi2.sin());
+    (phi2.to_degrees(), lon + dlambda.to_degrees())
+}
+
+/// Coefficients A and B of Vincenty's formulae for the squared second eccentricity term
+fn vincenty_coefficients(cos2_alpha: f64) -> (f64, f64) {
+    let b = (1.0 - WGS84_F) * WGS84_A;
+    let u2 = cos2_alpha * (WGS84_A * WGS84_A - b * b) / (b * b);
+    let a_coef = 1.0 + u2 / 16384.0 * (4096.0 + u2 * (-768.0 + u2 * (320.0 - 175.0 * u2)));
+    let b_coef = u2 / 1024.0 * (256.0 + u2 * (-128.0 + u2 * (74.0 - 47.0 * u2)));
+    (a_coef, b_coef)
+}
+
+fn vincenty_delta_sigma(b_coef: f64, sin_sigma: f64, cos_sigma: f64, cos_2sigma_m: f64) -> f64 {
+    let c2 = cos_2sigma_m * cos_2sigma_m;
+    b_coef
+        * sin_sigma
+        * (cos_2sigma_m
+            + b_coef / 4.0
+                * (cos_sigma * (-1.0 + 2.0 * c2)
+                    - b_coef / 6.0
+                        * cos_2sigma_m
+                        * (-3.0 + 4.0 * sin_sigma * sin_sigma)
+                        * (-3.0 + 4.0 * c2)))
+}
+
+/// Distance and initial bearing on the ellipsoid, `None` if the iteration does not converge
+fn vincenty_inverse((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> Option<(f64, f64)> {
+    let f = WGS84_F;
+    let l = (lon2 - lon1).to_radians();
+    let u1 = ((1.0 - f) * lat1.to_radians().tan()).atan();
+    let u2 = ((1.0 - f) * lat2.to_radians().tan()).atan();
+    let (sin_u1, cos_u1) = u1.sin_cos();
+    let (sin_u2, cos_u2) = u2.sin_cos();
+
+    let mut lambda = l;
+    for _ in 0..VINCENTY_ITERATIONS {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        let sin_sigma = ((cos_u2 * sin_lambda).powi(2)
+            + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2))
+        .sqrt();
+        if sin_sigma == 0.0 {
+            // coincident points
+            return Some((0.0, 0.0));
+        }
+        let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        let sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        let cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+        // equatorial lines have cos2_alpha = 0
+        let cos_2sigma_m = if cos2_alpha != 0.0 {
+            cos_sigma - 2.0 * sin_u1 * sin_u2 / cos2_alpha
+        } else {
+            0.0
+        };
+        let c = f / 16.0 * cos2_alpha * (4.0 + f * (4.0 - 3.0 * cos2_alpha));
+        let previous = lambda;
+        lambda = l
+            + (1.0 - c)
+                * f
+                * sin_alpha
+                * (sigma
+                    + c * sin_sigma
+                        * (cos_2sigma_m + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))));
+        if lambda.abs() > std::f64::consts::PI {
+            return None;
+        }
+        if (lambda - previous).abs() < 1e-12 {
+            let (a_coef, b_coef) = vincenty_coefficients(cos2_alpha);
+            let delta_sigma = vincenty_delta_sigma(b_coef, sin_sigma, cos_sigma, cos_2sigma_m);
+            let distance = (1.0 - f) * WGS84_A * a_coef * (sigma - delta_sigma);
+            let (sin_lambda, cos_lambda) = lambda.sin_cos();
+            let bearing = (cos_u2 * sin_lambda)
+                .atan2(cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda)
+                .to_degrees();
+            return Some((distance, bearing));
+        }
+    }
+    None
+}
+
+fn vincenty_direct((lat, lon): (f64, f64), bearing: f64, distance: f64) -> (f64, f64) {
+    let f = WGS84_F;
+    let b = (1.0 - f) * WGS84_A;
+    let (sin_alpha1, cos_alpha1) = bearing.to_radians().sin_cos();
+    let tan_u1 = (1.0 - f) * lat.to_radians().tan();
+    let cos_u1 = 1.0 / (1.0 + tan_u1 * tan_u1).sqrt();
+    let sin_u1 = tan_u1 * cos_u1;
+    let sigma1 = tan_u1.atan2(cos_alpha1);
+    let sin_alpha = cos_u1 * sin_alpha1;
+    let cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+    let (a_coef, b_coef) = vincenty_coefficients(cos2_alpha);
+
+    let mut sigma = distance / (b * a_coef);
+    let mut cos_2sigma_m = (2.0 * sigma1 + sigma).cos();
+    for _ in 0..VINCENTY_ITERATIONS {
+        cos_2sigma_m = (2.0 * sigma1 + sigma).cos();
+        let (sin_sigma, cos_sigma) = sigma.sin_cos();
+        let delta_sigma = vincenty_delta_sigma(b_coef, sin_sigma, cos_sigma, cos_2sigma_m);
+        let previous = sigma;
+        sigma = distance / (b * a_coef) + delta_sigma;
+        if (sigma - previous).abs() < 1e-12 {
+            break;
+        }
+    }
+    let (sin_sigma, cos_sigma) = sigma.sin_cos();
+    let tmp = sin_u1 * sin_sigma - cos_u1 * cos_sigma * cos_alpha1;
+    let lat2 = (sin_u1 * cos_sigma + cos_u1 * sin_sigma * cos_alpha1)
+        .atan2((1.0 - f) * (sin_alpha * sin_alpha + tmp * tmp).sqrt());
+    let lambda =
+        (sin_sigma * sin_alpha1).atan2(cos_u1 * cos_sigma - sin_u1 * sin_sigma * cos_alpha1);
+    let c = f / 16.0 * cos2_alpha * (4.0 + f * (4.0 - 3.0 * cos2_alpha));
+    let l = lambda
+        - (1.0 - c)
+            * f
+            * sin_alpha
+            * (sigma
+                + c * sin_sigma
+                    * (cos_2sigma_m + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))));
+    (lat2.to_degrees(), lon + l.to_degrees())
+}
+
+impl Accuracy {
+    /// Distance and initial bearing from `from` to `to`
+    fn inverse(self, from: (f64, f64), to: (f64, f64)) -> (f64, f64) {
+        match self {
+            Accuracy::Spherical => spherical_inverse(from, to),
+            Accuracy::Vincenty => {
+                vincenty_inverse(from, to).unwrap_or_else(|| Accuracy::Karney.inverse(from, to))
+            }
+            Accuracy::Karney => {
+                let (distance, bearing, _, _): (f64, f64, f64, f64) =
+                    Geodesic::wgs84().inverse(from.0, from.1, to.0, to.1);
+                (distance, bearing)
+            }
+        }
+    }
+    fn direct(self, from: (f64, f64), bearing: f64, distance: f64) -> (f64, f64) {
+        let (lat, lon) = match self {
+            Accuracy::Spherical => spherical_direct(from, bearing, distance),
+            Accuracy::Vincenty => vincenty_direct(from, bearing, distance),
+            Accuracy::Karney => Geodesic::wgs84().direct(from.0, from.1, bearing, distance),
+        };
+        (lat, normalize_lon(lon))
+    }
+    /// Distance between two locations
+    pub fn distance(self, from: &Location, to: &Location) -> Option<f64> {
+        match (from, to) {
+            (Location::LatLon(from), Location::LatLon(to)) => {
+                Some(self.inverse(latlon(*from), latlon(*to)).0)
+            }
+            (Location::XY(from), Location::XY(to)) => {
+                let dx = f64::from(to.0) - f64::from(from.0);
+                let dy = f64::from(to.1) - f64::from(from.1);
+                Some(dx.hypot(dy))
+            }
+            _ => None,
+        }
+    }
+    /// Initial bearing from one location towards another in degrees from 0 to 360
+    pub fn bearing(self, from: &Location, to: &Location) -> Option<f64> {
+        match (from, to) {
+            (Location::LatLon(from), Location::LatLon(to)) => Some(normalize_bearing(
+                self.inverse(latlon(*from), latlon(*to)).1,
+            )),
+            (Location::XY(from), Location::XY(to)) => {
+                let dx = f64::from(to.0) - f64::from(from.0);
+                let dy = f64::from(to.1) - f64::from(from.1);
+                Some(normalize_bearing(dx.atan2(dy).to_degrees()))
+            }
+            _ => None,
+        }
+    }
+    /// Location reached when travelling the distance along the initial bearing
+    pub fn destination(self, from: &Location, bearing: f64, distance: f64) -> Option<Location> {
+        match from {
+            Location::LatLon(from) => {
+                let (lat, lon) = self.direct(latlon(*from), bearing, distance);
+                Some(Location::LatLon((lat as f32, lon as f32)))
+            }
+            Location::XY((x, y)) => {
+                let (sin, cos) = bearing.to_radians().sin_cos();
+                Some(Location::XY((
+                    (f64::from(*x) + distance * sin) as f32,
+                    (f64::from(*y) + distance * cos) as f32,
+                )))
+            }
+            _ => None,
+        }
+    }
+    /// Location halfway along the shortest path between two locations
+    pub fn midpoint(self, from: &Location, to: &Location) -> Option<Location> {
+        match (from, to) {
+            (Location::LatLon(a), Location::LatLon(b)) => {
+                let (distance, bearing) = self.inverse(latlon(*a), latlon(*b));
+                let (lat, lon) = self.direct(latlon(*a), bearing, distance / 2.0);
+                Some(Location::LatLon((lat as f32, lon as f32)))
+            }
+            (Location::XY(a), Location::XY(b)) => {
+                Some(Location::XY(((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Distance between two locations using Karney's algorithm
+pub fn distance(from: &Location, to: &Location) -> Option<f64> {
+    Accuracy::default().distance(from, to)
+}
+
+/// Initial bearing from one location towards another using Karney's algorithm
+pub fn bearing(from: &Location, to: &Location) -> Option<f64> {
+    Accuracy::default().bearing(from, to)
+}
+
+/// Location reached when travelling the distance along the bearing using Karney's algorithm
+pub fn destination(from: &Location, bearing: f64, distance: f64) -> Option<Location> {
+    Accuracy::default().destination(from, bearing, distance)
+}
+
+/// Location halfway between two locations using Karney's algorithm
+pub fn midpoint(from: &Location, to: &Location) -> Option<Location> {
+    Accuracy::default().midpoint(from, to)
+}
+
+/// Smallest rectangle containing all locations as top left (north-west) and bottom right
+/// (south-east) corner, as used by [`LocationBlockData::FenceRect`]
+///
+/// `LatLon` boxes are bounded by parallels and meridians and cross the antimeridian if that
+/// makes them narrower. Returns `None` for no locations or mixed kinds of locations.
+///
+/// [`LocationBlockData::FenceRect`]: super::LocationBlockData::FenceRect
+pub fn bounding_box(locations: &[Location]) -> Option<(Location, Location)> {
+    match locations.first()? {
+        Location::LatLon(_) => {
+            let coords = locations
+                .iter()
+                .map(|loc| match loc {
+                    Location::LatLon(coords) => Some(*coords),
+                    _ => None,
+                })
+                .collect::<Option<Vec<(f32, f32)>>>()?;
+            let north = coords.iter().map(|c| c.0).fold(f32::MIN, f32::max);
+            let south = coords.iter().map(|c| c.0).fold(f32::MAX, f32::min);
+            let mut lons: Vec<f32> = coords.iter().map(|c| c.1).collect();
+            lons.sort_by(f32::total_cmp);
+            // the box spans everything except the largest gap between neighbouring longitudes
+            let (mut west, mut east) = (lons[0], lons[lons.len() - 1]);
+            let mut largest_gap = 360.0 - (east - west);
+            for pair in lons.windows(2) {
+                if pair[1] - pair[0] > largest_gap {
+                    largest_gap = pair[1] - pair[0];
+                    west = pair[1];
+                    east = pair[0];
+                }
+            }
+            Some((
+                Location::LatLon((north, west)),
+                Location::LatLon((south, east)),
+            ))
+        }
+        Location::XY(_) => {
+            let coords = locations
+                .iter()
+                .map(|loc| match loc {
+                    Location::XY(coords) => Some(*coords),
+                    _ => None,
+                })
+                .collect::<Option<Vec<(f32, f32)>>>()?;
+            let min_x = coords.iter().map(|c| c.0).fold(f32::MAX, f32::min);
+            let max_x = coords.iter().map(|c| c.0).fold(f32::MIN, f32::max);
+            let min_y = coords.iter().map(|c| c.1).fold(f32::MAX, f32::min);
+            let max_y = coords.iter().map(|c| c.1).fold(f32::MIN, f32::max);
+            Some((Location::XY((min_x, max_y)), Location::XY((max_x, min_y))))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Accuracy, bearing, bounding_box, destination, distance, midpoint};
+    use crate::location::{Location, LocationBlockData};
+
+    // Vincenty's test line from Flinders Peak to Buninyong
+    const FLINDERS_PEAK: Location = Location::LatLon((-37.951_033, 144.424_87));
+    const BUNINYONG: Location = Location::LatLon((-37.652_82, 143.926_5));
+
+    #[test]
+    fn test_geodesic_inverse() {
+        let karney = distance(&FLINDERS_PEAK, &BUNINYONG).unwrap();
+        // f32 coordinates are precise to about a metre
+        assert!((karney - 54_972.271).abs() < 2.0, "{}", karney);
+        let vincenty = Accuracy::Vincenty
+            .distance(&FLINDERS_PEAK, &BUNINYONG)
+            .unwrap();
+        assert!((vincenty - karney).abs() < 1e-3);
+        let spherical = Accuracy::Spherical
+            .distance(&FLINDERS_PEAK, &BUNINYONG)
+            .unwrap();
+        assert!((spherical - karney).abs() / karney < 0.005);
+
+        let azimuth = bearing(&FLINDERS_PEAK, &BUNINYONG).unwrap();
+        assert!((azimuth - 306.868_16).abs() < 1e-3, "{}", azimuth);
+        let vincenty = Accuracy::Vincenty
+            .bearing(&FLINDERS_PEAK, &BUNINYONG)
+            .unwrap();
+        assert!((vincenty - azimuth).abs() < 1e-6);
+
+        assert_eq!(distance(&FLINDERS_PEAK, &FLINDERS_PEAK), Some(0.0));
+        assert_eq!(
+            Accuracy::Vincenty.distance(&FLINDERS_PEAK, &FLINDERS_PEAK),
+            Some(0.0)
+        );
+        // nearly antipodal points fall back to Karney
+        let a = Location::LatLon((0.0, 0.0));
+        let b = Location::LatLon((0.5, 179.7));
+        let vincenty = Accuracy::Vincenty.distance(&a, &b).unwrap();
+        assert!((vincenty - distance(&a, &b).unwrap()).abs() < 1e-3);
+
+        assert_eq!(distance(&FLINDERS_PEAK, &Location::XY((0.0, 0.0))), None);
+        assert_eq!(
+            bearing(&Location::Human("Darmstadt".into()), &BUNINYONG),
+            None
+        );
+    }
+
+    #[test]
+    fn test_geodesic_direct() {
+        for accuracy in [Accuracy::Spherical, Accuracy::Vincenty, Accuracy::Karney] {
+            let azimuth = accuracy.bearing(&FLINDERS_PEAK, &BUNINYONG).unwrap();
+            let length = accuracy.distance(&FLINDERS_PEAK, &BUNINYONG).unwrap();
+            let end = accuracy
+                .destination(&FLINDERS_PEAK, azimuth, length)
+                .unwrap();
+            assert!(distance(&end, &BUNINYONG).unwrap() < 2.0, "{:?}", accuracy);
+
+            let mid = accuracy.midpoint(&FLINDERS_PEAK, &BUNINYONG).unwrap();
+            let first = accuracy.distance(&FLINDERS_PEAK, &mid).unwrap();
+            let second = accuracy.distance(&mid, &BUNINYONG).unwrap();
+            assert!((first - second).abs() < 2.0, "{:?}", accuracy);
+        }
+        // crossing the antimeridian
+        let Some(Location::LatLon((_, lon))) =
+            destination(&Location::LatLon((0.0, 179.9)), 90.0, 50_000.0)
+        else {
+            panic!("no destination");
+        };
+        assert!(lon < -179.0 && lon > -180.0, "{}", lon);
+        assert_eq!(destination(&Location::WFW("A-B-C".into()), 0.0, 1.0), None);
+    }
+
+    #[test]
+    fn test_planar() {
+        let origin = Location::XY((1.0, 1.0));
+        let target = Location::XY((4.0, 5.0));
+        assert_eq!(distance(&origin, &target), Some(5.0));
+        assert_eq!(bearing(&origin, &Location::XY((2.0, 1.0))), Some(90.0));
+        assert_eq!(bearing(&origin, &Location::XY((1.0, 0.0))), Some(180.0));
+        let Some(Location::XY((x, y))) = destination(&origin, 90.0, 3.0) else {
+            panic!("no destination");
+        };
+        assert!((x - 4.0).abs() < 1e-6 && (y - 1.0).abs() < 1e-6);
+        assert_eq!(midpoint(&origin, &target), Some(Location::XY((2.5, 3.0))));
+        assert_eq!(midpoint(&origin, &BUNINYONG), None);
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let (nw, se) = bounding_box(&[
+            Location::LatLon((49.87, 8.65)),
+            Location::LatLon((50.11, 8.68)),
+            Location::LatLon((49.99, 8.27)),
+        ])
+        .unwrap();
+        assert_eq!(nw, Location::LatLon((50.11, 8.27)));
+        assert_eq!(se, Location::LatLon((49.87, 8.68)));
+
+        let pacific = [
+            Location::LatLon((-17.7, 178.0)),
+            Location::LatLon((-13.8, -171.8)),
+            Location::LatLon((-21.1, -175.2)),
+        ];
+        let (nw, se) = bounding_box(&pacific).unwrap();
+        assert_eq!(nw, Location::LatLon((-13.8, 178.0)));
+        assert_eq!(se, Location::LatLon((-21.1, -171.8)));
+        let fence = LocationBlockData::FenceRect(nw, se);
+        assert!(pacific.iter().all(|loc| fence.contains(loc) == Some(true)));
+
+        let (nw, se) =
+            bounding_box(&[Location::XY((1.0, 5.0)), Location::XY((3.0, -2.0))]).unwrap();
+        assert_eq!(nw, Location::XY((1.0, 5.0)));
+        assert_eq!(se, Location::XY((3.0, -2.0)));
+
+        assert_eq!(bounding_box(&[]), None);
+        assert_eq!(
+            bounding_box(&[Location::XY((1.0, 5.0)), Location::LatLon((1.0, 5.0))]),
+            None
+        );
+    }
+}
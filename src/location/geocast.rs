@@ -0,0 +1,157 @@
+//! Geocast: bundles addressed to all nodes inside an area
+//!
+//! The sender attaches a fence block to an otherwise regular bundle. Receivers compare the fence
+//! with their own position and only deliver the bundle if they are inside the area.
+use super::{
+    LOCATION_BLOCK, Location, LocationBlockData, LocationError, get_location_data,
+    new_location_block,
+};
+use bp7::Bundle;
+
+/// Attach a fence to an outgoing bundle, the block number is assigned automatically
+pub fn add_fence(bundle: &mut Bundle, fence: LocationBlockData) -> Result<(), LocationError> {
+    if !fence.is_fence() {
+        return Err(LocationError::NotAFence);
+    }
+    bundle.add_canonical_block(new_location_block(0, fence));
+    Ok(())
+}
+
+/// First fence attached to a bundle, other location blocks such as positions are skipped
+pub fn get_fence(bundle: &Bundle) -> Option<LocationBlockData> {
+    bundle
+        .canonicals
+        .iter()
+        .filter(|cblock| cblock.block_type == LOCATION_BLOCK)
+        .filter_map(|cblock| get_location_data(cblock).ok())
+        .find(LocationBlockData::is_fence)
+}
+
+/// What a receiver should do with a bundle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeocastDecision {
+    /// Hand the bundle to the local application
+    Deliver,
+    /// Discard the bundle
+    Drop,
+    /// Do not deliver locally, the bundle node keeps forwarding its copy towards the area
+    Forward,
+}
+
+/// Geocast filter of a receiving node
+///
+/// Nodes outside the fence drop geocast bundles unless they relay them, e.g., mobile nodes
+/// that might carry them into the area. Nodes with an unknown position or one that cannot be
+/// compared with the fence are treated as outside.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeocastFilter {
+    position: Option<Location>,
+    relay: bool,
+}
+
+impl GeocastFilter {
+    pub fn new(position: Location) -> Self {
+        GeocastFilter {
+            position: Some(position),
+            relay: false,
+        }
+    }
+    /// Filter of a node that does not know its position yet
+    pub fn unknown() -> Self {
+        GeocastFilter {
+            position: None,
+            relay: false,
+        }
+    }
+    /// Forward bundles for other areas instead of dropping them
+    pub fn relay(mut self, relay: bool) -> Self {
+        self.relay = relay;
+        self
+    }
+    pub fn set_position(&mut self, position: Location) {
+        self.position = Some(position);
+    }
+    pub fn position(&self) -> Option<&Location> {
+        self.position.as_ref()
+    }
+    /// Decide on an incoming bundle, bundles without a fence are always delivered
+    pub fn decide(&self, bundle: &Bundle) -> GeocastDecision {
+        let Some(fence) = get_fence(bundle) else {
+            return GeocastDecision::Deliver;
+        };
+        let inside = self
+            .position
+            .as_ref()
+            .and_then(|position| fence.contains(position))
+            .unwrap_or(false);
+        if inside {
+            GeocastDecision::Deliver
+        } else if self.relay {
+            GeocastDecision::Forward
+        } else {
+            GeocastDecision::Drop
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GeocastDecision, GeocastFilter, add_fence, get_fence};
+    use crate::location::{
+        Location, LocationBlockData, NodeTypeFlags, get_location_data, new_location_block,
+    };
+    use bp7::bundle::new_std_payload_bundle;
+    use bp7::{Bundle, EndpointID};
+    use std::convert::TryFrom;
+
+    fn alert() -> Bundle {
+        new_std_payload_bundle(
+            EndpointID::try_from("dtn://node1/alert").unwrap(),
+            EndpointID::try_from("dtn://helpers/alert").unwrap(),
+            b"Hochwasser".to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_geocast_decision() {
+        let fence =
+            LocationBlockData::FenceEllipse(Location::LatLon((49.8728, 8.6512)), 2000, 2000);
+        let mut bndl = alert();
+        bndl.add_canonical_block(new_location_block(
+            0,
+            LocationBlockData::Position(NodeTypeFlags::MOBILE, Location::LatLon((0.0, 0.0))),
+        ));
+        add_fence(&mut bndl, fence.clone()).unwrap();
+        let mut bndl = Bundle::try_from(bndl.to_cbor()).unwrap();
+        assert_eq!(get_fence(&bndl), Some(fence));
+        assert_eq!(bndl.payload().unwrap(), b"Hochwasser");
+        let blocks: Vec<LocationBlockData> = bndl
+            .canonicals
+            .iter()
+            .filter_map(|cblock| get_location_data(cblock).ok())
+            .collect();
+        assert_eq!(blocks.len(), 2);
+
+        let mut filter = GeocastFilter::new(Location::LatLon((49.8750, 8.6600)));
+        assert_eq!(filter.decide(&bndl), GeocastDecision::Deliver);
+        filter.set_position(Location::LatLon((50.1109, 8.6821)));
+        assert_eq!(filter.decide(&bndl), GeocastDecision::Drop);
+        assert_eq!(
+            filter.clone().relay(true).decide(&bndl),
+            GeocastDecision::Forward
+        );
+        filter.set_position(Location::XY((0.0, 0.0)));
+        assert_eq!(filter.decide(&bndl), GeocastDecision::Drop);
+        assert_eq!(
+            GeocastFilter::unknown().decide(&bndl),
+            GeocastDecision::Drop
+        );
+        assert_eq!(
+            GeocastFilter::unknown().decide(&alert()),
+            GeocastDecision::Deliver
+        );
+
+        let position = LocationBlockData::Position(NodeTypeFlags::MOBILE, Location::XY((0.0, 0.0)));
+        assert!(add_fence(&mut bndl, position).is_err());
+    }
+}
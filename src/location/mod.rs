@@ -1,7 +1,12 @@
 mod block;
+pub mod geo;
+pub mod geocast;
 mod loc;
+pub mod trace;
 
-pub use block::{LOCATION_BLOCK, LocationBlockData, get_location_data, new_location_block};
+pub use block::{
+    LOCATION_BLOCK, LocationBlockData, LocationError, get_location_data, new_location_block,
+};
 pub use loc::Location;
 
 use bitflags::bitflags;
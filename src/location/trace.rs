@@ -0,0 +1,120 @@
+//! Hop-by-hop path recording with trace blocks
+//!
+//! Every node a bundle passes may append a trace block with its own location. Trace blocks get
+//! increasing block numbers, so the path can be restored in the order the nodes were passed.
+use super::{
+    LOCATION_BLOCK, Location, LocationBlockData, NodeTypeFlags, get_location_data,
+    new_location_block,
+};
+use bp7::{Bundle, DtnTime, EndpointID};
+
+/// One node on the path of a bundle
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    pub block_number: u64,
+    pub flags: NodeTypeFlags,
+    pub node: EndpointID,
+    pub location: Location,
+    pub timestamp: Option<DtnTime>,
+}
+
+/// Append a trace block for the local node, returning its block number
+///
+/// The block gets the next free block number, i.e., one above the highest one in the bundle.
+pub fn add_trace(
+    bundle: &mut Bundle,
+    flags: NodeTypeFlags,
+    node: EndpointID,
+    location: Location,
+    timestamp: Option<DtnTime>,
+) -> u64 {
+    let block_number = bundle
+        .canonicals
+        .iter()
+        .map(|cblock| cblock.block_number)
+        .max()
+        .unwrap_or(1)
+        + 1;
+    let data = LocationBlockData::Trace(flags, node, location, timestamp);
+    bundle.add_canonical_block(new_location_block(block_number, data));
+    block_number
+}
+
+/// All trace entries of a bundle in the order they were added
+pub fn trace_path(bundle: &Bundle) -> Vec<TraceEntry> {
+    let mut path: Vec<TraceEntry> = bundle
+        .canonicals
+        .iter()
+        .filter(|cblock| cblock.block_type == LOCATION_BLOCK)
+        .filter_map(|cblock| match get_location_data(cblock) {
+            Ok(LocationBlockData::Trace(flags, node, location, timestamp)) => Some(TraceEntry {
+                block_number: cblock.block_number,
+                flags,
+                node,
+                location,
+                timestamp,
+            }),
+            _ => None,
+        })
+        .collect();
+    path.sort_by_key(|entry| entry.block_number);
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_trace, trace_path};
+    use crate::location::{Location, LocationBlockData, NodeTypeFlags, geocast::add_fence};
+    use bp7::bundle::new_std_payload_bundle;
+    use bp7::{Bundle, EndpointID};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_trace_path() {
+        let mut bndl = new_std_payload_bundle(
+            EndpointID::try_from("dtn://node1/incoming").unwrap(),
+            EndpointID::try_from("dtn://node4/incoming").unwrap(),
+            b"hello".to_vec(),
+        );
+        assert!(trace_path(&bndl).is_empty());
+        let hops = [
+            ("dtn://node1/", Location::LatLon((49.87, 8.65)), Some(1000)),
+            ("dtn://node2/", Location::LatLon((49.88, 8.66)), None),
+            ("dtn://node3/", Location::XY((3.0, 4.0)), Some(3000)),
+        ];
+        let mut numbers = Vec::new();
+        for (i, (node, location, timestamp)) in hops.iter().enumerate() {
+            numbers.push(add_trace(
+                &mut bndl,
+                NodeTypeFlags::MOBILE,
+                EndpointID::try_from(*node).unwrap(),
+                location.clone(),
+                *timestamp,
+            ));
+            if i == 0 {
+                // other blocks in between do not disturb the order
+                let fence = LocationBlockData::FenceRect(
+                    Location::LatLon((50.0, 8.0)),
+                    Location::LatLon((49.0, 9.0)),
+                );
+                add_fence(&mut bndl, fence).unwrap();
+            }
+        }
+        // the fence took the block number after the first trace entry
+        assert_eq!(numbers[1], numbers[0] + 2);
+        assert_eq!(numbers[2], numbers[1] + 1);
+
+        let bndl = Bundle::try_from(bndl.to_cbor()).unwrap();
+        let path = trace_path(&bndl);
+        assert_eq!(path.len(), 3);
+        for (entry, (node, location, timestamp)) in path.iter().zip(hops.iter()) {
+            assert_eq!(entry.node.to_string(), *node);
+            assert_eq!(&entry.location, location);
+            assert_eq!(&entry.timestamp, timestamp);
+        }
+        assert_eq!(
+            path.iter().map(|e| e.block_number).collect::<Vec<_>>(),
+            numbers
+        );
+    }
+}
@@ -0,0 +1,369 @@
+//! Export and import of articles as mbox ([RFC](https://datatracker.ietf.org/doc/html/rfc4155)) or JSON lines
+//!
+//! Imported articles keep their message ID, thread ID, references, tags, expiry and bundle lifetime,
+//! but get a fresh creation timestamp, so archives can be replayed into another network.
+//!
+//! Encrypted articles are exported decrypted and marked with their key ID, i.e., an `X-DTN-Key-Id`
+//! header or `kid` field. On import they are encrypted again with the current key of their group,
+//! importing fails if the keyring has none, so private groups never end up in clear text.
+use super::keyring::Keyring;
+use super::nntp::{from_message_id, parse_expires, to_article};
+use super::{
+    Control, DEFAULT_LIFETIME, NewsBuilder, NewsBundle, NewsBundleBuilder, NewsError,
+    newsgroup_eid, sender_eid,
+};
+use crate::rfc5322::{Message, extract_address, format_asctime};
+use bp7::dtntime::DtnTimeHelpers;
+use std::io::{BufRead, Write};
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("news error: {0}")]
+    News(#[from] NewsError),
+    #[error("invalid article: {0}")]
+    InvalidArticle(String),
+}
+
+/// Write articles as mboxrd, lines starting with `From ` are quoted with `>`
+///
+/// Encrypted articles are written decrypted and skipped if the keyring lacks their group key.
+pub fn export_mbox<'a, W, I>(
+    writer: &mut W,
+    articles: I,
+    keyring: Option<&Keyring>,
+) -> Result<(), ArchiveError>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a NewsBundle>,
+{
+    for news in articles {
+        let Some(news) = news.readable(keyring) else {
+            continue;
+        };
+        let article = to_article(&news);
+        let sender = extract_address(article.header("From").unwrap_or_default());
+        writeln!(
+            writer,
+            "From {} {}",
+            sender,
+            format_asctime(news.creation_timestamp().dtntime().unix())
+        )?;
+        for (name, value) in article.headers() {
+            writeln!(writer, "{}: {}", name, value)?;
+        }
+        writeln!(writer)?;
+        for line in article.body().lines() {
+            if line.trim_start_matches('>').starts_with("From ") {
+                write!(writer, ">")?;
+            }
+            writeln!(writer, "{}", line)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Write articles as one JSON object per line
+///
+/// Encrypted articles are written decrypted and skipped if the keyring lacks their group key.
+pub fn export_json_lines<'a, W, I>(
+    writer: &mut W,
+    articles: I,
+    keyring: Option<&Keyring>,
+) -> Result<(), ArchiveError>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a NewsBundle>,
+{
+    for news in articles {
+        if let Some(news) = news.readable(keyring) {
+            writeln!(writer, "{}", news.to_json())?;
+        }
+    }
+    Ok(())
+}
+
+/// Read all articles from an mbox and rebuild them as news bundles, see [`from_article`]
+pub fn import_mbox<R: BufRead>(
+    reader: R,
+    keyring: Option<&Keyring>,
+) -> Result<Vec<NewsBundle>, ArchiveError> {
+    let mut messages: Vec<Vec<String>> = Vec::new();
+    let mut previous_empty = true;
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        if previous_empty && line.starts_with("From ") {
+            messages.push(Vec::new());
+        } else if let Some(message) = messages.last_mut() {
+            let unquoted = match line.strip_prefix('>') {
+                Some(quoted) if quoted.trim_start_matches('>').starts_with("From ") => quoted,
+                _ => line,
+            };
+            message.push(unquoted.to_string());
+        }
+        previous_empty = line.is_empty();
+    }
+    messages
+        .iter_mut()
+        .map(|lines| {
+            // drop the separating empty line
+            if lines.last().is_some_and(|l| l.is_empty()) {
+                lines.pop();
+            }
+            from_article(&Message::parse(&lines.join("\n")), keyring)
+        })
+        .collect()
+}
+
+/// Read all articles from JSON lines and rebuild them as news bundles
+///
+/// Articles with a key ID are encrypted with the current key of their group from the keyring.
+pub fn import_json_lines<R: BufRead>(
+    reader: R,
+    keyring: Option<&Keyring>,
+) -> Result<Vec<NewsBundle>, ArchiveError> {
+    let mut bundles = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            bundles.push(NewsBundle::from_json_with(&line, keyring)?);
+        }
+    }
+    Ok(bundles)
+}
+
+/// Rebuild a news bundle from a netnews article as created by [`to_article`]
+///
+/// Articles with an `X-DTN-Key-Id` header are encrypted with the current key of their group
+/// from the keyring, they fail with [`NewsError::NoKey`] without one.
+pub fn from_article(
+    article: &Message,
+    keyring: Option<&Keyring>,
+) -> Result<NewsBundle, ArchiveError> {
+    let header = |name: &str| {
+        article
+            .header(name)
+            .ok_or_else(|| ArchiveError::InvalidArticle(format!("missing {} header", name)))
+    };
+    let message_id = |mid: &str| {
+        from_message_id(mid)
+            .ok_or_else(|| ArchiveError::InvalidArticle(format!("invalid message ID {}", mid)))
+    };
+
+    let sender = extract_address(header("From")?);
+    let node = sender.split('@').next().unwrap_or_default();
+    let group = header("Newsgroups")?
+        .split(',')
+        .map(str::trim)
+        .next()
+        .unwrap_or_default();
+
+    let mut payload = NewsBuilder::new()
+        .topic(header("Subject")?)
+        .message(article.body().trim_end_matches('\n'))
+        .message_id(&message_id(header("Message-ID")?)?);
+    if let Some(references) = article.header("References") {
+        payload = payload.references(
+            references
+                .split_whitespace()
+                .map(message_id)
+                .collect::<Result<_, _>>()?,
+        );
+    }
+    if let Some(keywords) = article.header("Keywords") {
+        payload = payload.tags(
+            keywords
+                .split(',')
+                .map(|k| k.trim().to_string())
+                .filter(|k| !k.is_empty())
+                .collect(),
+        );
+    }
+    if let Some(expires) = article.header("Expires") {
+        let expires = parse_expires(expires).ok_or_else(|| {
+            ArchiveError::InvalidArticle(format!("invalid Expires header {}", expires))
+        })?;
+        payload = payload.expires(expires);
+    }
+    if let Some(tid) = article.header("X-DTN-Thread-ID") {
+        let tid = Uuid::parse_str(tid)
+            .map_err(|_| ArchiveError::InvalidArticle(format!("invalid thread ID {}", tid)))?;
+        payload = payload.thread_id(tid);
+    }
+    if let Some(control) = article.header("Control") {
+        let mid = control.strip_prefix("cancel ").ok_or_else(|| {
+            ArchiveError::InvalidArticle(format!("unsupported control {}", control))
+        })?;
+        payload = payload.control(Control::Cancel(message_id(mid)?));
+    } else if let Some(mid) = article.header("Supersedes") {
+        payload = payload.control(Control::Supersede(message_id(mid)?));
+    }
+    let lifetime = match article.header("X-DTN-Lifetime") {
+        Some(secs) => Duration::from_secs(
+            secs.trim()
+                .parse()
+                .map_err(|_| ArchiveError::InvalidArticle(format!("invalid lifetime {}", secs)))?,
+        ),
+        None => DEFAULT_LIFETIME,
+    };
+    if article.header("X-DTN-Key-Id").is_some() {
+        let key = keyring
+            .and_then(|keyring| keyring.current(group))
+            .ok_or(NewsError::NoKey)?;
+        payload = payload.encrypt(key);
+    }
+
+    Ok(NewsBundleBuilder::new()
+        .source(sender_eid(node)?)
+        .destination(newsgroup_eid(group)?)
+        .lifetime(lifetime)
+        .payload(payload.build()?)
+        .build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArchiveError, export_json_lines, export_mbox, import_json_lines, import_mbox};
+    use crate::news::keyring::Keyring;
+    use crate::news::spool::Spool;
+    use crate::news::{
+        DEFAULT_LIFETIME, NewsBuilder, NewsBundle, NewsBundleBuilder, NewsError, new_news,
+        sender_eid,
+    };
+    use std::time::Duration;
+
+    fn check_roundtrip(original: &[&NewsBundle], imported: &[NewsBundle]) {
+        assert_eq!(original.len(), imported.len());
+        for (a, b) in original.iter().zip(imported) {
+            assert_eq!(a.message_id(), b.message_id());
+            assert_eq!(a.tid(), b.tid());
+            assert_eq!(a.references(), b.references());
+            assert_eq!(a.tags(), b.tags());
+            assert_eq!(a.topic(), b.topic());
+            assert_eq!(a.msg(), b.msg());
+            assert_eq!(a.expires(), b.expires());
+            assert_eq!(a.bundle().primary.lifetime, b.bundle().primary.lifetime);
+            assert_eq!(a.bundle().primary.source, b.bundle().primary.source);
+            assert_eq!(
+                a.bundle().primary.destination,
+                b.bundle().primary.destination
+            );
+        }
+    }
+
+    #[test]
+    fn test_archive_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(dir.path()).unwrap();
+        let post = new_news(
+            "node1",
+            "de.test",
+            "Übung",
+            None,
+            vec![],
+            "Lage ruhig.\nFrom here on\n>From there\n\nEnde",
+            vec!["übung".into(), "lage".into()],
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        let reply = NewsBundleBuilder::new()
+            .source(sender_eid("node2").unwrap())
+            .destination(post.bundle().primary.destination.clone())
+            .lifetime(Duration::from_secs(7 * 24 * 60 * 60))
+            .payload(
+                NewsBuilder::new()
+                    .reply_to(&post)
+                    .message("Verstanden")
+                    .expires(
+                        (post.creation_timestamp().dtntime() / 1000 + 30 * 24 * 60 * 60) * 1000,
+                    )
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+        spool.store(post).unwrap();
+        spool.store(reply).unwrap();
+
+        let threads = spool.threads("de.test");
+        let articles = threads[0].articles();
+
+        let mut mbox = Vec::new();
+        export_mbox(&mut mbox, articles.iter().copied(), None).unwrap();
+        let text = String::from_utf8(mbox.clone()).unwrap();
+        assert!(text.starts_with("From node1@dtn "));
+        assert!(text.contains("\n>From here on\n>>From there\n"));
+        check_roundtrip(&articles, &import_mbox(mbox.as_slice(), None).unwrap());
+
+        let mut lines = Vec::new();
+        export_json_lines(&mut lines, spool.articles("de.test"), None).unwrap();
+        assert_eq!(String::from_utf8_lossy(&lines).lines().count(), 2);
+        check_roundtrip(
+            &spool.articles("de.test"),
+            &import_json_lines(lines.as_slice(), None).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_archive_encrypted() {
+        let mut keyring = Keyring::new();
+        let key = keyring.rotate("de.private").clone();
+        let secret = NewsBundleBuilder::new()
+            .source(sender_eid("node1").unwrap())
+            .newsgroup("de.private")
+            .unwrap()
+            .payload(
+                NewsBuilder::new()
+                    .topic("Geheim")
+                    .message("Treffen um acht")
+                    .encrypt(&key)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        let mut mbox = Vec::new();
+        export_mbox(&mut mbox, [&secret], None).unwrap();
+        assert!(mbox.is_empty());
+        export_mbox(&mut mbox, [&secret], Some(&keyring)).unwrap();
+        let text = String::from_utf8(mbox.clone()).unwrap();
+        assert!(text.contains(&format!("X-DTN-Key-Id: {}\n", key.id())));
+        assert!(text.contains("Treffen um acht"));
+
+        // imported articles are encrypted again or refused, never replayed in clear text
+        assert!(matches!(
+            import_mbox(mbox.as_slice(), None),
+            Err(ArchiveError::News(NewsError::NoKey))
+        ));
+        let new_key = keyring.rotate("de.private").clone();
+        let imported = import_mbox(mbox.as_slice(), Some(&keyring)).unwrap();
+        assert!(imported[0].encryption());
+        assert_eq!(imported[0].key_id().as_deref(), Some(new_key.id()));
+        assert_eq!(imported[0].message_id(), secret.message_id());
+        assert_eq!(imported[0].topic_with(Some(&keyring)).unwrap(), "Geheim");
+        assert_eq!(
+            imported[0].msg_with(Some(&keyring)).unwrap(),
+            "Treffen um acht"
+        );
+
+        let mut lines = Vec::new();
+        export_json_lines(&mut lines, [&secret], None).unwrap();
+        assert!(lines.is_empty());
+        export_json_lines(&mut lines, [&secret], Some(&keyring)).unwrap();
+        assert!(import_json_lines(lines.as_slice(), None).is_err());
+        let imported = import_json_lines(lines.as_slice(), Some(&keyring)).unwrap();
+        assert!(imported[0].encryption());
+        assert_eq!(
+            imported[0].msg_with(Some(&keyring)).unwrap(),
+            "Treffen um acht"
+        );
+    }
+}
@@ -0,0 +1,412 @@
+//! Digest bundles carrying several articles of one newsgroup
+//!
+//! Articles are stored uncompressed together with their original source and creation timestamp,
+//! and the whole list is compressed at once. Unpacked articles are regular [`NewsBundle`]s with
+//! their original bundle ID, so they deduplicate with copies received individually.
+use super::spool::Spool;
+use super::{DEFAULT_LIFETIME, News, NewsBundle, NewsError, NewsField, newsgroup_eid};
+use crate::compression::Compression;
+use bp7::dtntime::dtn_time_now;
+use bp7::flags::BlockControlFlags;
+use bp7::*;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// Payload of a digest bundle
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+struct Digest {
+    comp: Compression,
+    /// Compressed CBOR list of [`DigestEntry`]
+    #[serde(with = "serde_bytes")]
+    articles: Vec<u8>,
+}
+
+/// Article inside a digest
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+struct DigestEntry {
+    src: EndpointID,
+    created: CreationTimestamp,
+    /// Lifetime of the original bundle in milliseconds
+    lifetime: u64,
+    news: News,
+}
+
+impl DigestEntry {
+    fn new(news: &NewsBundle) -> Result<Self, NewsError> {
+        let mut payload = news.news();
+        // encrypted content is kept as is
+        if !payload.enc {
+            let comp = payload.comp;
+            payload.topic = comp.decompress(&payload.topic)?;
+            payload.msg = comp.decompress(&payload.msg)?;
+            payload.comp = Compression::None;
+        }
+        Ok(DigestEntry {
+            src: news.bundle().primary.source.clone(),
+            created: news.creation_timestamp().clone(),
+            lifetime: news.bundle().primary.lifetime.as_millis() as u64,
+            news: payload,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DigestBundle {
+    bundle: Bundle,
+    entries: Vec<DigestEntry>,
+}
+
+impl TryFrom<Bundle> for DigestBundle {
+    type Error = NewsError;
+
+    fn try_from(value: Bundle) -> Result<Self, Self::Error> {
+        let payload = value
+            .payload()
+            .ok_or_else(|| NewsError::PayloadMissing.in_field(NewsField::Payload))?;
+        let digest: Digest = serde_cbor::from_slice(payload)
+            .map_err(|err| NewsError::from(err).in_field(NewsField::Payload))?;
+        let articles = digest
+            .comp
+            .decompress(&digest.articles)
+            .map_err(|err| NewsError::from(err).in_field(NewsField::Compression))?;
+        let entries = serde_cbor::from_slice(&articles)
+            .map_err(|err| NewsError::from(err).in_field(NewsField::Payload))?;
+        let digest_bundle = DigestBundle {
+            bundle: value,
+            entries,
+        };
+        // every article has to be a valid news bundle on its own
+        for news in digest_bundle.iter() {
+            news?;
+        }
+        Ok(digest_bundle)
+    }
+}
+
+impl TryFrom<Vec<u8>> for DigestBundle {
+    type Error = NewsError;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        let bundle = Bundle::try_from(value)?;
+        DigestBundle::try_from(bundle)
+    }
+}
+
+impl DigestBundle {
+    pub fn id(&self) -> String {
+        self.bundle.id()
+    }
+    pub fn dst(&self) -> Option<String> {
+        self.bundle.primary.destination.node()
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /// Contained articles as individual news bundles, in packing order
+    pub fn iter(&self) -> impl Iterator<Item = Result<NewsBundle, NewsError>> + '_ {
+        self.entries.iter().map(|entry| {
+            let pblock = primary::PrimaryBlockBuilder::default()
+                .destination(self.bundle.primary.destination.clone())
+                .source(entry.src.clone())
+                .report_to(EndpointID::none())
+                .creation_timestamp(entry.created.clone())
+                .lifetime(Duration::from_millis(entry.lifetime))
+                .build()
+                .unwrap();
+            let cblocks = vec![canonical::new_payload_block(
+                BlockControlFlags::empty(),
+                serde_cbor::to_vec(&entry.news)
+                    .expect("Fatal failure, could not convert news payload to CBOR"),
+            )];
+            NewsBundle::try_from(bundle::Bundle::new(pblock, cblocks))
+        })
+    }
+    /// All contained articles
+    pub fn articles(&self) -> Vec<NewsBundle> {
+        // validated on creation
+        self.iter().filter_map(Result::ok).collect()
+    }
+    pub fn bundle(&self) -> &Bundle {
+        &self.bundle
+    }
+    pub fn to_cbor(&mut self) -> Vec<u8> {
+        self.bundle.to_cbor()
+    }
+}
+
+/// Packs articles of one newsgroup into digests not exceeding a size budget
+///
+/// Articles larger than the budget on their own are put into a digest of their own.
+pub struct DigestPacker {
+    src: EndpointID,
+    group: String,
+    budget: usize,
+    lifetime: Duration,
+}
+
+impl DigestPacker {
+    /// Digests sent from `src` to `group` with a budget of 64 KiB per encoded bundle
+    pub fn new(src: EndpointID, group: &str) -> Self {
+        DigestPacker {
+            src,
+            group: group.into(),
+            budget: 64 * 1024,
+            lifetime: DEFAULT_LIFETIME,
+        }
+    }
+    /// Maximum size of an encoded digest bundle in bytes
+    pub fn budget(mut self, bytes: usize) -> Self {
+        self.budget = bytes;
+        self
+    }
+    pub fn lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+    /// Pack articles in the given order, all of them have to be addressed to the newsgroup
+    ///
+    /// Digest sizes are estimated from the encoded size of each article and the compression ratio
+    /// seen so far, so usually every emitted digest is compressed only once.
+    pub fn pack<'a, I>(&self, articles: I) -> Result<Vec<DigestBundle>, NewsError>
+    where
+        I: IntoIterator<Item = &'a NewsBundle>,
+    {
+        let dst = newsgroup_eid(&self.group)?;
+        let entries = articles
+            .into_iter()
+            .map(|news| {
+                if news.dst().as_deref() != Some(self.group.as_str()) {
+                    return Err(NewsError::InvalidEndpoint.in_field(NewsField::Destination));
+                }
+                DigestEntry::new(news)
+            })
+            .collect::<Result<Vec<DigestEntry>, NewsError>>()?;
+        if entries.is_empty() {
+            return Ok(Vec::new());
+        }
+        let sizes = entries
+            .iter()
+            .map(|entry| Ok(serde_cbor::to_vec(entry)?.len()))
+            .collect::<Result<Vec<usize>, NewsError>>()?;
+        let overhead = self.build(dst.clone(), &[])?.to_cbor().len();
+        let estimate = |raw: usize, ratio: f64| overhead + (raw as f64 * ratio) as usize;
+
+        // the ratio of all articles together is the first estimate
+        let mut all = self.build(dst.clone(), &entries)?;
+        let size = all.to_cbor().len();
+        if size <= self.budget {
+            return Ok(vec![all]);
+        }
+        let mut ratio = size.saturating_sub(overhead) as f64 / sizes.iter().sum::<usize>() as f64;
+
+        let mut digests = Vec::new();
+        let mut start = 0;
+        while start < entries.len() {
+            let mut end = start + 1;
+            let mut raw = sizes[start];
+            while end < entries.len() && estimate(raw + sizes[end], ratio) <= self.budget {
+                raw += sizes[end];
+                end += 1;
+            }
+            let mut digest = self.build(dst.clone(), &entries[start..end])?;
+            loop {
+                let size = digest.to_cbor().len();
+                ratio = size.saturating_sub(overhead) as f64 / raw.max(1) as f64;
+                if size <= self.budget || end - start == 1 {
+                    break;
+                }
+                // the estimate was too low, drop articles until the corrected one fits
+                while end - start > 1 && estimate(raw, ratio) > self.budget {
+                    end -= 1;
+                    raw -= sizes[end];
+                }
+                digest = self.build(dst.clone(), &entries[start..end])?;
+            }
+            digests.push(digest);
+            start = end;
+        }
+        Ok(digests)
+    }
+    /// Pack all articles and control messages of the newsgroup stored in a spool that are still
+    /// within their bundle lifetime, oldest first
+    pub fn pack_spool(&self, spool: &Spool) -> Result<Vec<DigestBundle>, NewsError> {
+        self.pack(spool.in_network(&self.group, dtn_time_now()))
+    }
+    fn build(&self, dst: EndpointID, entries: &[DigestEntry]) -> Result<DigestBundle, NewsError> {
+        let articles = serde_cbor::to_vec(&entries)?;
+        let comp = Compression::smallest(&[&articles]);
+        let digest = Digest {
+            comp,
+            articles: comp.compress(&articles),
+        };
+        let pblock = primary::PrimaryBlockBuilder::default()
+            .destination(dst)
+            .source(self.src.clone())
+            .report_to(EndpointID::none())
+            .creation_timestamp(CreationTimestamp::now())
+            .lifetime(self.lifetime)
+            .build()
+            .unwrap();
+        let cblocks = vec![canonical::new_payload_block(
+            BlockControlFlags::empty(),
+            serde_cbor::to_vec(&digest)?,
+        )];
+        Ok(DigestBundle {
+            bundle: bundle::Bundle::new(pblock, cblocks),
+            entries: entries.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DigestBundle, DigestPacker};
+    use crate::news::spool::Spool;
+    use crate::news::{
+        DEFAULT_LIFETIME, NewsBundle, cancel_news, new_news, reply_news, sender_eid,
+    };
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_digest_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(dir.path()).unwrap();
+        let post = new_news(
+            "node1",
+            "de.test",
+            "Lagebericht",
+            None,
+            vec![],
+            &"Keine besonderen Vorkommnisse. ".repeat(20),
+            vec!["lage".into()],
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        let reply = reply_news(
+            &post,
+            "node2",
+            "Danke, keine Vorkommnisse.",
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        let other = new_news(
+            "node3",
+            "de.test",
+            "t",
+            None,
+            vec![],
+            "m",
+            vec![],
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        let cancel = cancel_news(&other, "node3").unwrap();
+        for news in [&post, &reply, &other, &cancel] {
+            spool.store(news.clone()).unwrap();
+        }
+
+        let packer = DigestPacker::new(sender_eid("gw").unwrap(), "de.test");
+        let mut digests = packer.pack_spool(&spool).unwrap();
+        assert_eq!(digests.len(), 1);
+        assert_eq!(digests[0].len(), 3);
+
+        let digest = DigestBundle::try_from(digests[0].to_cbor()).unwrap();
+        assert_eq!(digest.dst().as_deref(), Some("de.test"));
+        let articles = digest.articles();
+        assert_eq!(articles[0].id(), post.id());
+        assert_eq!(articles[0].msg(), post.msg());
+        assert_eq!(articles[0].tags(), post.tags());
+        assert_eq!(articles[1].references(), vec![post.message_id()]);
+        assert!(articles[2].control().is_some());
+
+        // unpacked articles are known to a spool that already has them
+        assert!(!spool.store(articles[0].clone()).unwrap());
+        assert!(NewsBundle::try_from(digest.bundle().clone()).is_err());
+    }
+
+    #[test]
+    fn test_digest_budget() {
+        // text that does not compress well
+        let mut seed: u32 = 42;
+        let mut noise = |len: usize| -> String {
+            (0..len)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    (b'a' + ((seed >> 16) % 26) as u8) as char
+                })
+                .collect()
+        };
+        let posts: Vec<NewsBundle> = (0..10)
+            .map(|i| {
+                let msg = format!("{} {}", i, noise(100 + i * 7));
+                new_news(
+                    "node1",
+                    "42",
+                    "t",
+                    None,
+                    vec![],
+                    &msg,
+                    vec![],
+                    false,
+                    DEFAULT_LIFETIME,
+                )
+                .unwrap()
+            })
+            .collect();
+        let packer = DigestPacker::new(sender_eid("23").unwrap(), "42").budget(600);
+        let mut digests = packer.pack(&posts).unwrap();
+        assert!(digests.len() > 1);
+        assert!(digests.iter_mut().all(|d| d.to_cbor().len() <= 600));
+        let unpacked: Vec<String> = digests
+            .iter()
+            .flat_map(|d| d.articles())
+            .map(|news| news.msg())
+            .collect();
+        let original: Vec<String> = posts.iter().map(|news| news.msg()).collect();
+        assert_eq!(unpacked, original);
+
+        let oversized = DigestPacker::new(sender_eid("23").unwrap(), "42").budget(10);
+        assert_eq!(oversized.pack(&posts).unwrap().len(), 10);
+        let wrong_group = DigestPacker::new(sender_eid("23").unwrap(), "43");
+        assert!(wrong_group.pack(&posts).is_err());
+        assert!(packer.pack(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_digest_budget_compressible() {
+        let posts: Vec<NewsBundle> = (0..300)
+            .map(|i| {
+                let msg = format!("{} {}", i, "Keine besonderen Vorkommnisse. ".repeat(10));
+                new_news(
+                    "node1",
+                    "42",
+                    "Lagebericht",
+                    None,
+                    vec![],
+                    &msg,
+                    vec![],
+                    false,
+                    DEFAULT_LIFETIME,
+                )
+                .unwrap()
+            })
+            .collect();
+        let packer = DigestPacker::new(sender_eid("23").unwrap(), "42").budget(4096);
+        let mut digests = packer.pack(&posts).unwrap();
+        let sizes: Vec<usize> = digests.iter_mut().map(|d| d.to_cbor().len()).collect();
+        assert!(sizes.len() > 1);
+        assert!(sizes.iter().all(|size| *size <= 4096));
+        // compression is taken into account, full digests carry more than the budget uncompressed
+        assert!(sizes[..sizes.len() - 1].iter().all(|size| *size > 2048));
+        assert!(digests[0].len() * 300 > 4096);
+        let unpacked: usize = digests.iter().map(|d| d.len()).sum();
+        assert_eq!(unpacked, posts.len());
+    }
+}
@@ -0,0 +1,523 @@
+//! Gateway between newsgroups and web feeds
+//!
+//! Newsgroups are published as Atom feeds ([RFC](https://datatracker.ietf.org/doc/html/rfc4287)) with replies
+//! linked via the threading extension ([RFC](https://datatracker.ietf.org/doc/html/rfc4685)).
+//! RSS 1.0, RSS 2.0 and Atom feeds can be turned into posts, each entry gets a message ID derived
+//! from its dedupe key, so the same entry always results in the same article.
+use super::keyring::Keyring;
+use super::nntp::to_message_id;
+use super::spool::Spool;
+use super::{NewsBuilder, NewsBundle, NewsError, newsgroup_eid};
+use crate::rfc5322::format_rfc3339;
+use bp7::dtntime::DtnTimeHelpers;
+use std::collections::BTreeSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum GatewayError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("news error: {0}")]
+    News(#[from] NewsError),
+    #[error("xml error: {0}")]
+    Xml(#[from] roxmltree::Error),
+}
+
+/// Escape text for XML content and attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Atom ID of an article, a `news:` URI of its netnews message ID
+fn atom_id(mid: &str) -> String {
+    let message_id = to_message_id(mid);
+    format!("news:{}", &message_id[1..message_id.len() - 1])
+}
+
+/// Render all articles of a spool newsgroup as Atom document, newest first
+///
+/// Encrypted articles are rendered decrypted and left out if the keyring lacks their group key.
+pub fn render_atom(
+    spool: &Spool,
+    group: &str,
+    keyring: Option<&Keyring>,
+) -> Result<String, GatewayError> {
+    let articles: Vec<NewsBundle> = spool
+        .articles(group)
+        .into_iter()
+        .filter_map(|news| news.readable(keyring))
+        .collect();
+    let updated = articles
+        .last()
+        .map(|news| news.creation_timestamp().dtntime().unix())
+        .unwrap_or_default();
+
+    let mut feed = String::new();
+    feed.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    feed.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\" xmlns:thr=\"http://purl.org/syndication/thread/1.0\">\n");
+    feed.push_str(&format!(
+        "  <id>{}</id>\n",
+        escape(&newsgroup_eid(group)?.to_string())
+    ));
+    feed.push_str(&format!("  <title>{}</title>\n", escape(group)));
+    feed.push_str(&format!(
+        "  <updated>{}</updated>\n",
+        format_rfc3339(updated)
+    ));
+    for news in articles.iter().rev() {
+        feed.push_str(&render_entry(news));
+    }
+    feed.push_str("</feed>\n");
+    Ok(feed)
+}
+
+fn render_entry(news: &NewsBundle) -> String {
+    let node = news.bundle().primary.source.node().unwrap_or_default();
+    let updated = format_rfc3339(news.creation_timestamp().dtntime().unix());
+    let mut entry = String::from("  <entry>\n");
+    entry.push_str(&format!(
+        "    <id>{}</id>\n",
+        escape(&atom_id(&news.message_id()))
+    ));
+    entry.push_str(&format!("    <title>{}</title>\n", escape(&news.topic())));
+    entry.push_str(&format!("    <updated>{}</updated>\n", updated));
+    entry.push_str(&format!(
+        "    <author><name>{}</name></author>\n",
+        escape(&node)
+    ));
+    if let Some(parent) = news.references().last() {
+        entry.push_str(&format!(
+            "    <thr:in-reply-to ref=\"{}\"/>\n",
+            escape(&atom_id(parent))
+        ));
+    }
+    for tag in news.tags() {
+        entry.push_str(&format!("    <category term=\"{}\"/>\n", escape(&tag)));
+    }
+    entry.push_str(&format!(
+        "    <content type=\"text\">{}</content>\n",
+        escape(&news.msg())
+    ));
+    entry.push_str("  </entry>\n");
+    entry
+}
+
+/// Entry of an RSS or Atom feed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedEntry {
+    /// Feed the entry belongs to: its URL or id, used to tell apart entries with the same key in different feeds
+    pub feed: String,
+    /// Stable identifier of the entry: guid, id, link or title, whichever is present first, without line breaks
+    pub key: String,
+    pub title: String,
+    pub link: Option<String>,
+    /// Summary or content, markup is removed unless the type is `text`
+    pub content: String,
+    pub tags: Vec<String>,
+}
+
+impl FeedEntry {
+    /// Key of the entry within all feeds, the entry key prefixed with the feed
+    pub fn dedupe_key(&self) -> String {
+        if self.feed.is_empty() {
+            self.key.clone()
+        } else {
+            format!("{} {}", self.feed, self.key)
+        }
+    }
+    /// Message ID derived from the dedupe key
+    pub fn message_id(&self) -> String {
+        Uuid::new_v5(&Uuid::NAMESPACE_URL, self.dedupe_key().as_bytes()).to_string()
+    }
+    /// Post with topic, tags and a message ID derived from the dedupe key, the link is appended to the message
+    pub fn to_news(&self) -> NewsBuilder {
+        let msg = match &self.link {
+            Some(link) if self.content.is_empty() => link.clone(),
+            Some(link) => format!("{}\n\n{}", self.content, link),
+            None => self.content.clone(),
+        };
+        NewsBuilder::new()
+            .topic(&self.title)
+            .message(&msg)
+            .tags(self.tags.clone())
+            .message_id(&self.message_id())
+    }
+}
+
+/// Concatenated text of an element and its children
+fn text_of(node: roxmltree::Node) -> String {
+    node.descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Remove HTML tags and collapse whitespace
+fn strip_markup(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+                text.push(' ');
+            }
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Parse all entries of an RSS 1.0, RSS 2.0 or Atom document
+///
+/// Entries without any of guid, id, link or title are skipped. The feed of the entries is the
+/// id or link of the feed, or its title if it has neither.
+pub fn parse_feed(xml: &str) -> Result<Vec<FeedEntry>, GatewayError> {
+    let doc = roxmltree::Document::parse(xml)?;
+    let feed = feed_id(doc.root_element());
+    let entries = doc
+        .descendants()
+        .filter(|n| matches!(n.tag_name().name(), "item" | "entry"))
+        .filter_map(|item| parse_entry(item, &feed))
+        .collect();
+    Ok(entries)
+}
+
+/// Atom feed id, RSS channel link or RSS 1.0 channel URI, falling back to the title
+fn feed_id(root: roxmltree::Node) -> String {
+    let channel = if root.tag_name().name() == "feed" {
+        Some(root)
+    } else {
+        root.children()
+            .find(|n| n.is_element() && n.tag_name().name() == "channel")
+    };
+    let Some(channel) = channel else {
+        return String::new();
+    };
+    let child = |name: &str| {
+        channel
+            .children()
+            .find(|n| n.is_element() && n.tag_name().name() == name && n.attribute("rel").is_none())
+            .map(|n| {
+                n.attribute("href")
+                    .map(String::from)
+                    .unwrap_or_else(|| text_of(n))
+            })
+            .filter(|text| !text.is_empty())
+    };
+    child("id")
+        .or_else(|| child("link"))
+        .or_else(|| {
+            channel
+                .attributes()
+                .find(|a| a.name() == "about")
+                .map(|a| a.value().to_string())
+        })
+        .or_else(|| child("title"))
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn parse_entry(item: roxmltree::Node, feed: &str) -> Option<FeedEntry> {
+    let child = |name: &str| {
+        item.children()
+            .find(|n| n.is_element() && n.tag_name().name() == name)
+            .map(text_of)
+            .filter(|text| !text.is_empty())
+    };
+    let link = item
+        .children()
+        .filter(|n| n.is_element() && n.tag_name().name() == "link")
+        .find_map(|n| match n.attribute("href") {
+            // atom links, only the alternate representation
+            Some(href) if n.attribute("rel").is_none_or(|rel| rel == "alternate") => {
+                Some(href.to_string())
+            }
+            Some(_) => None,
+            None => Some(text_of(n)).filter(|text| !text.is_empty()),
+        });
+    let content = ["summary", "description", "content", "encoded"]
+        .iter()
+        .find_map(|name| {
+            let node = item
+                .children()
+                .find(|n| n.is_element() && n.tag_name().name() == *name)?;
+            let text = text_of(node);
+            match node.attribute("type") {
+                Some("text") => Some(text),
+                _ => Some(strip_markup(&text)),
+            }
+            .filter(|text| !text.is_empty())
+        })
+        .unwrap_or_default();
+    let title = child("title");
+    let key = child("guid")
+        .or_else(|| child("id"))
+        .or_else(|| link.clone())
+        .or_else(|| title.clone())?
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let tags = item
+        .children()
+        .filter(|n| n.is_element() && matches!(n.tag_name().name(), "category" | "subject"))
+        .filter_map(|n| {
+            n.attribute("term")
+                .map(String::from)
+                .or_else(|| Some(text_of(n)))
+        })
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    Some(FeedEntry {
+        feed: feed.into(),
+        title: title.unwrap_or_else(|| key.clone()),
+        key,
+        link,
+        content,
+        tags,
+    })
+}
+
+/// Dedupe keys of already posted feed entries, optionally kept in a file with one key per line
+///
+/// Keys are namespaced with the feed, see [`FeedEntry::dedupe_key`].
+#[derive(Debug, Default)]
+pub struct FeedState {
+    path: Option<PathBuf>,
+    seen: BTreeSet<String>,
+}
+
+impl FeedState {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Load the keys from a file, which is created on the first [`FeedState::mark`]
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, GatewayError> {
+        let path = path.as_ref().to_path_buf();
+        let mut seen = BTreeSet::new();
+        if path.exists() {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                let line = line?;
+                if !line.is_empty() {
+                    seen.insert(line);
+                }
+            }
+        }
+        Ok(FeedState {
+            path: Some(path),
+            seen,
+        })
+    }
+    pub fn is_seen(&self, entry: &FeedEntry) -> bool {
+        self.seen.contains(&entry.dedupe_key())
+    }
+    /// Entries not posted before
+    pub fn fresh(&self, entries: Vec<FeedEntry>) -> Vec<FeedEntry> {
+        entries.into_iter().filter(|e| !self.is_seen(e)).collect()
+    }
+    /// Remember an entry as posted, returns `false` if it already was
+    pub fn mark(&mut self, entry: &FeedEntry) -> Result<bool, GatewayError> {
+        let key = entry.dedupe_key();
+        if !self.seen.insert(key.clone()) {
+            return Ok(false);
+        }
+        if let Some(path) = &self.path {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", key)?;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FeedState, parse_feed, render_atom};
+    use crate::news::keyring::Keyring;
+    use crate::news::spool::Spool;
+    use crate::news::{
+        DEFAULT_LIFETIME, NewsBuilder, NewsBundleBuilder, new_news, reply_news, sender_eid,
+    };
+
+    const RSS: &str = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel><title>Stadt</title>
+  <item>
+    <title>Straßensperrung &amp; Umleitung</title>
+    <link>https://example.org/1</link>
+    <guid>urn:example:1</guid>
+    <description>&lt;p&gt;Die Brücke ist &lt;b&gt;gesperrt&lt;/b&gt;.&lt;/p&gt;</description>
+    <category>traffic</category>
+  </item>
+  <item><link>https://example.org/2</link><title>Ohne GUID</title></item>
+  <item><description>Nichts zum Identifizieren</description></item>
+</channel></rss>"#;
+
+    const ATOM: &str = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Wetter</title>
+  <entry>
+    <id>tag:example.org,2026:3</id>
+    <title>Sturmwarnung</title>
+    <link rel="self" href="https://example.org/3.atom"/>
+    <link href="https://example.org/3"/>
+    <summary>Böen bis 100 km/h</summary>
+    <category term="weather"/>
+  </entry>
+</feed>"#;
+
+    #[test]
+    fn test_parse_feed() {
+        let entries = parse_feed(RSS).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].key, "urn:example:1");
+        assert_eq!(entries[0].title, "Straßensperrung & Umleitung");
+        assert_eq!(entries[0].content, "Die Brücke ist gesperrt .");
+        assert_eq!(entries[0].tags, vec!["traffic"]);
+        assert_eq!(entries[1].key, "https://example.org/2");
+
+        let entries = parse_feed(ATOM).unwrap();
+        assert_eq!(entries[0].key, "tag:example.org,2026:3");
+        assert_eq!(entries[0].link.as_deref(), Some("https://example.org/3"));
+        assert_eq!(entries[0].tags, vec!["weather"]);
+
+        let news = entries[0].to_news().build().unwrap();
+        assert_eq!(news.topic(), "Sturmwarnung");
+        assert_eq!(news.msg(), "Böen bis 100 km/h\n\nhttps://example.org/3");
+        assert_eq!(news.message_id(), Some(entries[0].message_id().as_str()));
+        assert_eq!(
+            entries[0].message_id(),
+            parse_feed(ATOM).unwrap()[0].message_id()
+        );
+
+        assert!(parse_feed("<rss>").is_err());
+    }
+
+    #[test]
+    fn test_feed_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("seen");
+        let entries = parse_feed(RSS).unwrap();
+
+        let mut state = FeedState::open(&path).unwrap();
+        assert_eq!(state.fresh(entries.clone()).len(), 2);
+        assert!(state.mark(&entries[0]).unwrap());
+        assert!(!state.mark(&entries[0]).unwrap());
+
+        let state = FeedState::open(&path).unwrap();
+        assert!(state.is_seen(&entries[0]));
+        assert_eq!(state.fresh(entries.clone()), vec![entries[1].clone()]);
+        assert_eq!(FeedState::new().fresh(entries).len(), 2);
+    }
+
+    #[test]
+    fn test_feed_namespaces() {
+        let feed = |channel: &str| {
+            format!(
+                "<rss version=\"2.0\"><channel>{}<title>News</title><item><guid>1</guid><title>t</title></item></channel></rss>",
+                channel
+            )
+        };
+        let a = parse_feed(&feed("<link>https://a.example.org/</link>")).unwrap();
+        let b = parse_feed(&feed("<link>https://b.example.org/</link>")).unwrap();
+        assert_eq!(a[0].feed, "https://a.example.org/");
+        assert_eq!(a[0].key, b[0].key);
+        assert_ne!(a[0].message_id(), b[0].message_id());
+
+        let mut state = FeedState::new();
+        assert!(state.mark(&a[0]).unwrap());
+        assert!(!state.is_seen(&b[0]));
+        assert!(state.mark(&b[0]).unwrap());
+
+        let rdf = parse_feed(
+            r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#" xmlns="http://purl.org/rss/1.0/"><channel rdf:about="https://c.example.org/rss"><title>C</title></channel><item><title>t</title><link>https://c.example.org/1</link></item></rdf:RDF>"#,
+        )
+        .unwrap();
+        assert_eq!(rdf[0].feed, "https://c.example.org/rss");
+        assert_eq!(parse_feed(ATOM).unwrap()[0].feed, "Wetter");
+        assert_eq!(parse_feed(RSS).unwrap()[0].feed, "Stadt");
+    }
+
+    #[test]
+    fn test_render_atom() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(dir.path()).unwrap();
+        let post = new_news(
+            "node1",
+            "de.test",
+            "Q&A <1>",
+            None,
+            vec![],
+            "a < b",
+            vec!["misc".into()],
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        let reply = reply_news(&post, "node2", "ok", true, DEFAULT_LIFETIME).unwrap();
+        let parent = post.message_id();
+        spool.store(post).unwrap();
+        spool.store(reply).unwrap();
+        let entry = parse_feed(ATOM).unwrap().remove(0);
+        let ingested = NewsBundleBuilder::new()
+            .source(sender_eid("gw").unwrap())
+            .newsgroup("de.test")
+            .unwrap()
+            .payload(entry.to_news().build().unwrap())
+            .build()
+            .unwrap();
+        spool.store(ingested).unwrap();
+
+        let atom = render_atom(&spool, "de.test", None).unwrap();
+        assert!(atom.contains("<id>dtn://de.test/~news</id>"));
+        assert!(atom.contains("<title>Q&amp;A &lt;1&gt;</title>"));
+        assert!(atom.contains("<content type=\"text\">a &lt; b</content>"));
+        assert!(atom.contains(&format!("<thr:in-reply-to ref=\"news:{}@dtn7\"/>", parent)));
+
+        // the rendered feed can be ingested again
+        let entries = parse_feed(&atom).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(entries.iter().any(|e| e.title == "Q&A <1>"));
+        assert!(entries.iter().any(|e| e.tags == vec!["misc"]));
+
+        // encrypted articles need their group key
+        let mut keyring = Keyring::new();
+        let key = keyring.rotate("de.test").clone();
+        let secret = NewsBundleBuilder::new()
+            .source(sender_eid("node3").unwrap())
+            .newsgroup("de.test")
+            .unwrap()
+            .payload(
+                NewsBuilder::new()
+                    .topic("Geheim")
+                    .message("m")
+                    .encrypt(&key)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+        spool.store(secret).unwrap();
+        let atom = render_atom(&spool, "de.test", None).unwrap();
+        assert_eq!(parse_feed(&atom).unwrap().len(), 3);
+        let atom = render_atom(&spool, "de.test", Some(&keyring)).unwrap();
+        assert!(atom.contains("<title>Geheim</title>"));
+    }
+}
@@ -0,0 +1,196 @@
+//! Human-readable JSON representation of news bundles for web frontends
+//!
+//! ```json
+//! {
+//!   "src": "dtn://node1/sms",
+//!   "dst": "dtn://de.hessen.darmstadt/~news",
+//!   "topic": "Lorem ipsum",
+//!   "msg": "dolor sit amet",
+//!   "tags": ["lorem"]
+//! }
+//! ```
+use super::keyring::Keyring;
+use super::{DEFAULT_LIFETIME, NewsBuilder, NewsBundle, NewsError, news_bundle};
+use bp7::EndpointID;
+use bp7::dtntime::{DtnTimeHelpers, SECONDS1970_TO2K};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::time::Duration;
+use uuid::Uuid;
+
+fn default_compression() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewsJson {
+    /// Bundle ID, ignored when constructing a bundle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(with = "crate::serde::eid_string")]
+    pub src: EndpointID,
+    #[serde(with = "crate::serde::eid_string")]
+    pub dst: EndpointID,
+    /// Creation time as RFC 3339 string, ignored when constructing a bundle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    /// Lifetime in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifetime: Option<u64>,
+    /// Time until which readers keep the article as unix timestamp in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<u64>,
+    /// Thread ID, a new thread is started if missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tid: Option<Uuid>,
+    /// Message ID, a random one is generated if missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mid: Option<String>,
+    /// Message IDs of all ancestors, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub topic: String,
+    pub msg: String,
+    /// Key ID of a decrypted article of a private group, such articles are encrypted again when
+    /// constructing a bundle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    /// Compress topic and message when constructing a bundle
+    #[serde(default = "default_compression", skip_serializing)]
+    pub compression: bool,
+}
+
+impl From<&NewsBundle> for NewsJson {
+    fn from(news: &NewsBundle) -> Self {
+        let primary = &news.bundle().primary;
+        NewsJson {
+            id: Some(news.id()),
+            src: primary.source.clone(),
+            dst: primary.destination.clone(),
+            created: Some(primary.creation_timestamp.dtntime().string()),
+            lifetime: Some(primary.lifetime.as_secs()),
+            expires: news.expires().map(|exp| exp.unix()),
+            tid: Some(news.tid()),
+            mid: Some(news.message_id()),
+            references: news.references(),
+            tags: news.tags(),
+            topic: news.topic(),
+            msg: news.msg(),
+            kid: news.key_id(),
+            compression: news.compression(),
+        }
+    }
+}
+
+impl NewsJson {
+    /// Construct a bundle, articles of private groups are encrypted with the current group key
+    ///
+    /// Fails with [`NewsError::NoKey`] if an article has a key ID but the keyring has no key for its group.
+    pub fn build(self, keyring: Option<&Keyring>) -> Result<NewsBundle, NewsError> {
+        let mut payload = NewsBuilder::new()
+            .compression(self.compression)
+            .topic(&self.topic)
+            .message(&self.msg)
+            .thread_id(self.tid.unwrap_or_else(Uuid::new_v4))
+            .references(self.references)
+            .tags(self.tags);
+        if let Some(expires) = self.expires {
+            payload = payload.expires(expires.saturating_sub(SECONDS1970_TO2K) * 1000);
+        }
+        if self.kid.is_some() {
+            let group = self.dst.node().unwrap_or_default();
+            let key = keyring
+                .and_then(|keyring| keyring.current(&group))
+                .ok_or(NewsError::NoKey)?;
+            payload = payload.encrypt(key);
+        }
+        let payload = match self.mid {
+            Some(mid) => payload.message_id(&mid).build()?,
+            None => payload.build()?,
+        };
+        let lifetime = self
+            .lifetime
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_LIFETIME);
+        news_bundle(self.src, self.dst, lifetime, &payload)
+    }
+}
+
+impl TryFrom<NewsJson> for NewsBundle {
+    type Error = NewsError;
+
+    fn try_from(value: NewsJson) -> Result<Self, Self::Error> {
+        value.build(None)
+    }
+}
+
+impl NewsBundle {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&NewsJson::from(self))
+            .expect("Fatal failure, could not convert news to JSON")
+    }
+    /// Construct a new bundle from its JSON representation
+    pub fn from_json(json: &str) -> Result<Self, NewsError> {
+        NewsBundle::try_from(serde_json::from_str::<NewsJson>(json)?)
+    }
+    /// Construct a new bundle from its JSON representation, encrypting articles of private groups
+    pub fn from_json_with(json: &str, keyring: Option<&Keyring>) -> Result<Self, NewsError> {
+        serde_json::from_str::<NewsJson>(json)?.build(keyring)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::news::{DEFAULT_LIFETIME, NewsBundle, new_news};
+
+    #[test]
+    fn test_news_json_roundtrip() {
+        let news = new_news(
+            "node1",
+            "de.hessen.darmstadt",
+            "Straßensperrung",
+            None,
+            vec!["b0c4c4a6-6d0c-4c39-9a6f-5c9e2a1f3d7e".into()],
+            "Die Brücke ist gesperrt.",
+            vec!["verkehr".into()],
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        let json = news.to_json();
+        assert!(json.contains("\"topic\":\"Straßensperrung\""));
+        assert!(json.contains("\"dst\":\"dtn://de.hessen.darmstadt/~news\""));
+        assert!(json.contains(&format!("\"tid\":\"{}\"", news.tid())));
+
+        let news2 = NewsBundle::from_json(&json).unwrap();
+        assert_eq!(news2.topic(), news.topic());
+        assert_eq!(news2.msg(), news.msg());
+        assert_eq!(news2.tid(), news.tid());
+        assert_eq!(news2.references(), news.references());
+        assert_eq!(news2.message_id(), news.message_id());
+        assert_eq!(news2.tags(), news.tags());
+    }
+
+    #[test]
+    fn test_news_json_minimal() {
+        let news = NewsBundle::from_json(
+            r#"{"src": "ipn:23.767", "dst": "ipn:42.119", "topic": "hello", "msg": "world"}"#,
+        )
+        .unwrap();
+        assert_eq!(news.topic(), "hello");
+        assert_eq!(news.dst_ipn(), 42);
+        assert!(news.tags().is_empty());
+        assert_eq!(news.expires(), None);
+
+        let news = NewsBundle::from_json(
+            r#"{"src": "ipn:23.767", "dst": "ipn:42.119", "lifetime": 86400, "expires": 1792331054, "topic": "hello", "msg": "world"}"#,
+        )
+        .unwrap();
+        assert_eq!(news.bundle().primary.lifetime.as_secs(), 86400);
+        assert!(news.to_json().contains("\"expires\":1792331054"));
+
+        assert!(NewsBundle::from_json(r#"{"src": "ipn:23.767", "dst": "ipn:42.119"}"#).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+//! Shared keys of private newsgroups
+//!
+//! Topic, tags and message of encrypted articles are sealed with ChaCha20-Poly1305 under a group key,
+//! while routing data such as the destination, message ID and references stay in clear.
+//! Each group can have several keys: new articles use the newest one, older keys are kept
+//! to read articles written before a rotation.
+//!
+//! Keyrings are stored as text with one `group key-id base64-key` per line.
+use super::NewsError;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chacha20poly1305::aead::{Aead, AeadCore, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use uuid::Uuid;
+
+const NONCE_LEN: usize = 12;
+
+/// Symmetric key of a newsgroup, identified by a key ID carried in encrypted articles
+#[derive(Clone, PartialEq, Eq)]
+pub struct GroupKey {
+    id: String,
+    key: [u8; 32],
+}
+
+impl GroupKey {
+    pub fn new(id: &str, key: [u8; 32]) -> Self {
+        GroupKey { id: id.into(), key }
+    }
+    /// Random key with a random key ID
+    pub fn generate() -> Self {
+        GroupKey {
+            id: Uuid::new_v4().simple().to_string(),
+            key: ChaCha20Poly1305::generate_key(&mut OsRng).into(),
+        }
+    }
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    /// Encrypt and authenticate `plaintext` together with `aad`, the random nonce is prepended
+    pub(super) fn seal(&self, aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&self.key));
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .expect("Fatal failure, could not encrypt news content");
+        [nonce.as_slice(), &ciphertext].concat()
+    }
+    pub(super) fn open(&self, aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, NewsError> {
+        if sealed.len() < NONCE_LEN {
+            return Err(NewsError::Decryption);
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        ChaCha20Poly1305::new(Key::from_slice(&self.key))
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| NewsError::Decryption)
+    }
+}
+
+impl fmt::Debug for GroupKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GroupKey").field("id", &self.id).finish()
+    }
+}
+
+/// Keys of private newsgroups, oldest first per group
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Keyring {
+    groups: BTreeMap<String, Vec<GroupKey>>,
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Parse lines of `group key-id base64-key`, empty lines and lines starting with `#` are skipped
+    pub fn parse(text: &str) -> Result<Self, NewsError> {
+        let mut keyring = Keyring::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || {
+                NewsError::InvalidKey(
+                    line.split_whitespace()
+                        .take(2)
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                )
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [group, id, key] = fields[..] else {
+                return Err(invalid());
+            };
+            let key: [u8; 32] = STANDARD
+                .decode(key)
+                .ok()
+                .and_then(|key| key.try_into().ok())
+                .ok_or_else(invalid)?;
+            keyring.add(group, GroupKey::new(id, key));
+        }
+        Ok(keyring)
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        let text = std::fs::read_to_string(path)?;
+        Keyring::parse(&text)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string()))
+    }
+    /// Save the keyring to a file only readable by the owner
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), std::io::Error> {
+        super::write_private(path, &self.to_string())
+    }
+    /// Add a key to a group, it becomes the current key of the group
+    pub fn add(&mut self, group: &str, key: GroupKey) {
+        self.groups.entry(group.into()).or_default().push(key);
+    }
+    /// Generate a new current key for a group, older keys remain available for decryption
+    pub fn rotate(&mut self, group: &str) -> &GroupKey {
+        self.add(group, GroupKey::generate());
+        self.current(group).unwrap()
+    }
+    /// Key used to encrypt new articles of a group
+    pub fn current(&self, group: &str) -> Option<&GroupKey> {
+        self.groups.get(group).and_then(|keys| keys.last())
+    }
+    /// Key of a group with the given key ID, key IDs are only unique within a group
+    pub fn get(&self, group: &str, id: &str) -> Option<&GroupKey> {
+        self.groups
+            .get(group)?
+            .iter()
+            .rev()
+            .find(|key| key.id == id)
+    }
+    /// Groups with at least one key
+    pub fn groups(&self) -> Vec<String> {
+        self.groups.keys().cloned().collect()
+    }
+}
+
+impl fmt::Display for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (group, keys) in &self.groups {
+            for key in keys {
+                writeln!(f, "{} {} {}", group, key.id, STANDARD.encode(key.key))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GroupKey, Keyring};
+
+    #[test]
+    fn test_keyring_parse() {
+        let mut keyring = Keyring::new();
+        keyring.add("de.private", GroupKey::new("k1", [1; 32]));
+        let current = keyring.rotate("de.private").clone();
+        keyring.add("42", GroupKey::new("k3", [3; 32]));
+        let text = format!("# private groups\n\n{}", keyring);
+        let parsed = Keyring::parse(&text).unwrap();
+        assert_eq!(parsed, keyring);
+        assert_eq!(parsed.current("de.private"), Some(&current));
+        assert_eq!(
+            parsed.get("de.private", "k1"),
+            Some(&GroupKey::new("k1", [1; 32]))
+        );
+        assert_eq!(parsed.get("42", "k1"), None);
+        assert_eq!(parsed.groups(), vec!["42", "de.private"]);
+        assert!(!format!("{:?}", current).contains("key"));
+
+        assert!(Keyring::parse("de.private k1").is_err());
+        assert!(Keyring::parse("de.private k1 c2hvcnQ=").is_err());
+    }
+
+    #[test]
+    fn test_keyring_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keyring");
+        // existing files readable by others are restricted as well
+        std::fs::write(&path, "").unwrap();
+        let mut keyring = Keyring::new();
+        keyring.rotate("de.private");
+        keyring.save(&path).unwrap();
+        assert_eq!(Keyring::load(&path).unwrap(), keyring);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
@@ -1,12 +1,34 @@
 /// This protocol is inspired by the net news format ([RFC](https://datatracker.ietf.org/doc/html/rfc5536))
-use bp7::flags::BlockControlFlags;
+use crate::compression::{Compression, CompressionError};
+use bp7::flags::{BlockControlFlags, BundleControlFlags};
 use bp7::*;
 use core::fmt;
+use keyring::{GroupKey, Keyring};
+use moderation::Moderators;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 use std::time::Duration;
 use thiserror::Error;
 use uuid::Uuid;
+
+pub mod archive;
+pub mod digest;
+#[cfg(feature = "feed")]
+pub mod gateway;
+pub mod json;
+pub mod keyring;
+pub mod moderation;
+pub mod nntp;
+pub mod spool;
+pub mod subscription;
+
+/// IPN service number of news senders
+pub const SENDER_IPN_SERVICE: u64 = 767;
+/// IPN service number of newsgroups
+pub const NEWS_IPN_SERVICE: u64 = 119;
+/// Lifetime of newly created news bundles
+pub const DEFAULT_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
 #[derive(Error, Debug)]
 pub enum NewsError {
     #[error("bundle decoding error: {0}")]
@@ -15,28 +37,89 @@ pub enum NewsError {
     NonUtf8(#[from] std::string::FromUtf8Error),
     #[error("serde cbor error: {0}")]
     Cbor(#[from] serde_cbor::Error),
+    #[error("invalid json: {0}")]
+    Json(#[from] serde_json::Error),
     #[error("failed to decompress message: {0}")]
-    SmazDecompress(#[from] smaz::DecompressError),
+    Decompression(#[from] CompressionError),
     #[error("failed to create endpoint: {0}")]
     EndpointIdInvalid(#[from] bp7::eid::EndpointIdError),
     #[error("News is missing message text")]
     NoMessage,
     #[error("News is missing a topic")]
     NoTopic,
+    #[error("invalid message id: {0:?}")]
+    InvalidMessageId(String),
     #[error("invalid endpoint supplied")]
     InvalidEndpoint,
+    #[error("invalid newsgroup name: {0:?}")]
+    InvalidGroup(String),
+    #[error("unknown endpoint service: {0}")]
+    UnknownService(String),
     #[error("payload missing")]
     PayloadMissing,
-    #[error("invalid news bundle")]
-    InvalidNewsBundle,
+    #[error("not a submission to a moderated newsgroup")]
+    NotSubmission,
+    #[error("content is encrypted, a keyring is required")]
+    Encrypted,
+    #[error("no group key for encryption")]
+    NoKey,
+    #[error("unknown group key: {0}")]
+    UnknownKey(String),
+    #[error("invalid group key: {0}")]
+    InvalidKey(String),
+    #[error("failed to decrypt content")]
+    Decryption,
+    #[error("invalid moderator key: {0}")]
+    InvalidModeratorKey(String),
+    #[error("invalid news bundle, {field}")]
+    InvalidNewsBundle {
+        field: NewsField,
+        #[source]
+        source: Box<NewsError>,
+    },
 }
 
-fn smaz_compress(indata: &[u8]) -> Vec<u8> {
-    smaz::compress(indata)
+/// Part of a news bundle that failed validation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NewsField {
+    Source,
+    Destination,
+    Payload,
+    Compression,
+    Topic,
+    Message,
+    MessageId,
 }
 
-fn smaz_decompress(indata: &[u8]) -> Result<Vec<u8>, NewsError> {
-    Ok(smaz::decompress(indata)?)
+impl fmt::Display for NewsField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            NewsField::Source => "source",
+            NewsField::Destination => "destination",
+            NewsField::Payload => "payload",
+            NewsField::Compression => "compression",
+            NewsField::Topic => "topic",
+            NewsField::Message => "message",
+            NewsField::MessageId => "message id",
+        };
+        f.write_str(name)
+    }
+}
+
+impl NewsError {
+    fn in_field(self, field: NewsField) -> NewsError {
+        NewsError::InvalidNewsBundle {
+            field,
+            source: Box::new(self),
+        }
+    }
+    /// Field of the bundle that caused a validation error
+    pub fn field(&self) -> Option<NewsField> {
+        match self {
+            NewsError::InvalidNewsBundle { field, .. } => Some(*field),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -47,11 +130,8 @@ impl TryFrom<Bundle> for NewsBundle {
 
     fn try_from(value: Bundle) -> Result<Self, Self::Error> {
         let news_bundle = NewsBundle(value);
-        if news_bundle.is_valid().is_err() {
-            Err(NewsError::InvalidNewsBundle)
-        } else {
-            Ok(news_bundle)
-        }
+        news_bundle.validate(false)?;
+        Ok(news_bundle)
     }
 }
 impl TryFrom<Vec<u8>> for NewsBundle {
@@ -59,12 +139,7 @@ impl TryFrom<Vec<u8>> for NewsBundle {
 
     fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
         let bundle = Bundle::try_from(value.to_vec())?;
-        let news_bundle = NewsBundle(bundle);
-        if news_bundle.is_valid().is_err() {
-            Err(NewsError::InvalidNewsBundle)
-        } else {
-            Ok(news_bundle)
-        }
+        NewsBundle::try_from(bundle)
     }
 }
 
@@ -74,8 +149,15 @@ impl fmt::Display for NewsBundle {
         writeln!(f, "From: {}", self.src().unwrap_or_default())?;
         writeln!(f, "To: {}", self.dst().unwrap_or_default())?;
         writeln!(f, "Creation TS: {}", self.creation_timestamp())?;
+        writeln!(f, "Message ID: {}", self.message_id())?;
         writeln!(f, "Thread ID: {}", self.tid())?;
         writeln!(f, "References: {:?}", self.references())?;
+        if let Some(kid) = self.key_id() {
+            writeln!(f, "Key ID: {}", kid)?;
+        }
+        if let Some(control) = self.control() {
+            writeln!(f, "Control: {}", control)?;
+        }
         writeln!(f, "Tags: {:?}", self.tags())?;
         writeln!(f, "Topic: {}", self.topic())?;
         writeln!(f, "\n{}", self.msg())
@@ -84,23 +166,40 @@ impl fmt::Display for NewsBundle {
 enum EIDType {
     Src,
     Dst,
+    /// Moderator receiving a submission
+    Moderator,
 }
 impl NewsBundle {
+    /// Accept bundles with unknown endpoint services, returning them as warnings
+    ///
+    /// All other validation errors are still fatal.
+    pub fn try_from_lenient(value: Bundle) -> Result<(Self, Vec<NewsError>), NewsError> {
+        let news_bundle = NewsBundle(value);
+        let warnings = news_bundle.validate(true)?;
+        Ok((news_bundle, warnings))
+    }
     fn is_eid_valid(&self, eid: &EndpointID, service: EIDType) -> Result<(), NewsError> {
         match eid {
             EndpointID::Ipn(_, ipn) => match service {
                 EIDType::Src => {
-                    if ipn.service_number() == 767 {
+                    if ipn.service_number() == SENDER_IPN_SERVICE {
                         Ok(())
                     } else {
-                        Err(NewsError::InvalidEndpoint)
+                        Err(NewsError::UnknownService(eid.to_string()))
                     }
                 }
                 EIDType::Dst => {
-                    if ipn.service_number() == 119 {
+                    if ipn.service_number() == NEWS_IPN_SERVICE {
                         Ok(())
                     } else {
+                        Err(NewsError::UnknownService(eid.to_string()))
+                    }
+                }
+                EIDType::Moderator => {
+                    if ipn.service_number() == NEWS_IPN_SERVICE {
                         Err(NewsError::InvalidEndpoint)
+                    } else {
+                        Ok(())
                     }
                 }
             },
@@ -109,12 +208,19 @@ impl NewsBundle {
                     if ssp.service_name() == Some("sms") {
                         Ok(())
                     } else {
-                        Err(NewsError::InvalidEndpoint)
+                        Err(NewsError::UnknownService(eid.to_string()))
                     }
                 }
                 EIDType::Dst => {
                     if ssp.service_name() == Some("~news") {
                         Ok(())
+                    } else {
+                        Err(NewsError::UnknownService(eid.to_string()))
+                    }
+                }
+                EIDType::Moderator => {
+                    if ssp.is_singleton() {
+                        Ok(())
                     } else {
                         Err(NewsError::InvalidEndpoint)
                     }
@@ -123,21 +229,67 @@ impl NewsBundle {
             _ => Err(NewsError::InvalidEndpoint),
         }
     }
-    fn is_valid(&self) -> Result<(), NewsError> {
-        self.is_eid_valid(&self.0.primary.source, EIDType::Src)?;
-        self.is_eid_valid(&self.0.primary.destination, EIDType::Dst)?;
+    /// Validate the bundle, returning the cause and offending field on error
+    ///
+    /// In lenient mode unknown endpoint services are collected as warnings instead.
+    fn validate(&self, lenient: bool) -> Result<Vec<NewsError>, NewsError> {
+        let mut warnings = Vec::new();
+        // submissions are addressed to the moderator instead of the newsgroup
+        let submission = self
+            .0
+            .payload()
+            .and_then(|payload| serde_cbor::from_slice::<News>(payload).ok())
+            .is_some_and(|news| news.grp.is_some());
+        let dst = if submission {
+            EIDType::Moderator
+        } else {
+            EIDType::Dst
+        };
+        for (eid, service, field) in [
+            (&self.0.primary.source, EIDType::Src, NewsField::Source),
+            (&self.0.primary.destination, dst, NewsField::Destination),
+        ] {
+            match self.is_eid_valid(eid, service) {
+                Ok(()) => {}
+                Err(err @ NewsError::UnknownService(_)) if lenient => {
+                    warnings.push(err.in_field(field))
+                }
+                Err(err) => return Err(err.in_field(field)),
+            }
+        }
 
         // Validate general payload
-        let payload = self.0.payload().ok_or(NewsError::PayloadMissing)?;
-        let news: News = serde_cbor::from_slice(payload)?;
+        let payload = self
+            .0
+            .payload()
+            .ok_or_else(|| NewsError::PayloadMissing.in_field(NewsField::Payload))?;
+        let news: News = serde_cbor::from_slice(payload)
+            .map_err(|err| NewsError::from(err).in_field(NewsField::Payload))?;
+        news.check_message_ids()
+            .map_err(|err| err.in_field(NewsField::MessageId))?;
 
-        // Validate payload message and compression
-        if news.comp {
-            String::from_utf8(smaz_decompress(&news.msg)?)?;
-        } else {
-            String::from_utf8(news.msg)?;
+        // Validate payload message and compression, encrypted content is checked on decryption
+        if news.enc {
+            return Ok(warnings);
         }
-        Ok(())
+        for (data, field) in [
+            (&news.msg, NewsField::Message),
+            (&news.topic, NewsField::Topic),
+        ] {
+            let text = news
+                .comp
+                .decompress(data)
+                .map_err(|err| NewsError::from(err).in_field(NewsField::Compression))?;
+            String::from_utf8(text).map_err(|err| NewsError::from(err).in_field(field))?;
+        }
+        Ok(warnings)
+    }
+    /// Decode all news bundles from a buffer of concatenated CBOR bundles, e.g., an appended log file
+    pub fn decode_all(buf: &[u8]) -> Result<Vec<NewsBundle>, NewsError> {
+        serde_cbor::Deserializer::from_slice(buf)
+            .into_iter::<Bundle>()
+            .map(|bundle| NewsBundle::try_from(bundle?))
+            .collect()
     }
     pub fn id(&self) -> String {
         self.0.id()
@@ -163,6 +315,20 @@ impl NewsBundle {
     pub fn dst(&self) -> Option<String> {
         self.0.primary.destination.node()
     }
+    /// Target newsgroup, which differs from the destination for submissions to a moderator
+    pub fn newsgroup(&self) -> Option<String> {
+        self.news()
+            .submission_group()
+            .map(String::from)
+            .or_else(|| self.dst())
+    }
+    /// Check whether this is a post waiting for approval by a moderator
+    pub fn is_submission(&self) -> bool {
+        self.news().submission_group().is_some()
+    }
+    pub fn approval(&self) -> Option<Approval> {
+        self.news().approval().cloned()
+    }
     pub fn creation_timestamp(&self) -> &CreationTimestamp {
         &self.0.primary.creation_timestamp
     }
@@ -174,27 +340,122 @@ impl NewsBundle {
     pub fn compression(&self) -> bool {
         self.news().compression()
     }
+    pub fn compression_algorithm(&self) -> Compression {
+        self.news().compression_algorithm()
+    }
     pub fn encryption(&self) -> bool {
         self.news().encryption()
     }
     pub fn signature(&self) -> Option<Vec<u8>> {
         self.news().signature()
     }
+    /// Message, empty for encrypted articles, see [`NewsBundle::msg_with`]
     pub fn msg(&self) -> String {
         self.news().msg()
     }
+    /// Topic, empty for encrypted articles, see [`NewsBundle::topic_with`]
     pub fn topic(&self) -> String {
         self.news().topic()
     }
     pub fn tid(&self) -> Uuid {
         self.news().thread_id()
     }
-    pub fn references(&self) -> Option<String> {
-        self.news().references()
+    /// Message ID of the article, falls back to the bundle ID for articles without one
+    pub fn message_id(&self) -> String {
+        self.news()
+            .message_id()
+            .map(String::from)
+            .unwrap_or_else(|| self.id())
+    }
+    pub fn references(&self) -> Vec<String> {
+        self.news().references().to_vec()
     }
+    pub fn control(&self) -> Option<Control> {
+        self.news().control().cloned()
+    }
+    /// Check whether this is a control message that may act on `original`
+    ///
+    /// Signed control messages are checked with the verifier if one is given,
+    /// otherwise the sender must be the original author.
+    pub fn controls(
+        &self,
+        original: &NewsBundle,
+        verifier: Option<&dyn SignatureVerifier>,
+    ) -> bool {
+        let Some(control) = self.control() else {
+            return false;
+        };
+        if control.target() != original.message_id() {
+            return false;
+        }
+        match verifier {
+            Some(verifier) if self.signature().is_some() => verifier.verify(self, original),
+            _ => self.0.primary.source.node_id() == original.bundle().primary.source.node_id(),
+        }
+    }
+    /// Tags, empty for encrypted articles, see [`NewsBundle::tags_with`]
     pub fn tags(&self) -> Vec<String> {
         self.news().tags().to_vec()
     }
+    /// Check whether the article belongs to a private group, i.e., it is encrypted or a decrypted copy
+    pub fn is_private(&self) -> bool {
+        self.news().is_private()
+    }
+    /// ID of the group key the content is or was encrypted with
+    pub fn key_id(&self) -> Option<String> {
+        self.news().key_id().map(String::from)
+    }
+    /// Topic, encrypted articles require a keyring with their group key
+    pub fn topic_with(&self, keyring: Option<&Keyring>) -> Result<String, NewsError> {
+        Ok(self
+            .news()
+            .decrypt(&self.newsgroup().unwrap_or_default(), keyring)?
+            .topic())
+    }
+    /// Message, encrypted articles require a keyring with their group key
+    pub fn msg_with(&self, keyring: Option<&Keyring>) -> Result<String, NewsError> {
+        Ok(self
+            .news()
+            .decrypt(&self.newsgroup().unwrap_or_default(), keyring)?
+            .msg())
+    }
+    /// Tags, encrypted articles require a keyring with their group key
+    pub fn tags_with(&self, keyring: Option<&Keyring>) -> Result<Vec<String>, NewsError> {
+        Ok(self
+            .news()
+            .decrypt(&self.newsgroup().unwrap_or_default(), keyring)?
+            .tags)
+    }
+    /// Plain copy for display or export, `None` for encrypted articles without their group key
+    pub fn readable(&self, keyring: Option<&Keyring>) -> Option<NewsBundle> {
+        match (self.encryption(), keyring) {
+            (false, _) => Some(self.clone()),
+            (true, Some(keyring)) => self.decrypted(keyring).ok(),
+            (true, None) => None,
+        }
+    }
+    /// Same bundle with a decrypted payload, e.g., for display or export
+    ///
+    /// The copy keeps the key ID, so follow-ups to it have to be encrypted again.
+    pub fn decrypted(&self, keyring: &Keyring) -> Result<NewsBundle, NewsError> {
+        let payload = self
+            .news()
+            .decrypt(&self.newsgroup().unwrap_or_default(), Some(keyring))?;
+        let cblocks = vec![canonical::new_payload_block(
+            BlockControlFlags::empty(),
+            serde_cbor::to_vec(&payload)?,
+        )];
+        NewsBundle::try_from(bundle::Bundle::new(self.0.primary.clone(), cblocks))
+    }
+    /// Time until which readers should keep the article, if the author set one
+    pub fn expires(&self) -> Option<DtnTime> {
+        self.news().expires()
+    }
+    /// Time at which the bundle stops being forwarded in the network
+    pub fn lifetime_end(&self) -> DtnTime {
+        let lifetime = self.0.primary.lifetime.as_millis() as u64;
+        self.creation_timestamp().dtntime().saturating_add(lifetime)
+    }
     pub fn bundle(&self) -> &Bundle {
         &self.0
     }
@@ -206,48 +467,210 @@ impl NewsBundle {
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct News {
-    comp: bool,
+    comp: Compression,
     enc: bool,
     #[serde(with = "serde_bytes")]
     topic: Vec<u8>,
     tid: Uuid,
-    references: Option<String>,
+    /// Message IDs of all ancestors, oldest first
+    #[serde(default, with = "crate::serde::one_or_many")]
+    references: Vec<String>,
     tags: Vec<String>,
     #[serde(with = "serde_bytes")]
     msg: Vec<u8>,
     sig: Option<Vec<u8>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ctl: Option<Control>,
+    /// Expiry for readers, independent of the bundle lifetime
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exp: Option<DtnTime>,
+    /// Moderated newsgroup of a submission sent to its moderator
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    grp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    apv: Option<Approval>,
+    /// Group key of encrypted content
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kid: Option<String>,
+}
+
+/// Encrypted content of a news payload, sealed into the message field
+#[derive(Serialize, Deserialize)]
+struct Sealed {
+    #[serde(with = "serde_bytes")]
+    topic: Vec<u8>,
+    tags: Vec<String>,
+    #[serde(with = "serde_bytes")]
+    msg: Vec<u8>,
+}
+
+/// Approval of a post to a moderated newsgroup, like the netnews `Approved` header
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Approval {
+    /// Endpoint of the approving moderator
+    pub moderator: EndpointID,
+    /// Signature of the moderator over [`News::approval_data`]
+    #[serde(with = "serde_bytes")]
+    pub sig: Vec<u8>,
+}
+
+/// Control messages acting on an earlier article, referenced by its message ID
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Control {
+    /// Withdraw the article
+    Cancel(String),
+    /// Replace the article with this one
+    Supersede(String),
+}
+
+impl Control {
+    /// Message ID of the affected article
+    pub fn target(&self) -> &str {
+        match self {
+            Control::Cancel(mid) | Control::Supersede(mid) => mid,
+        }
+    }
+}
+
+impl fmt::Display for Control {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Control::Cancel(mid) => write!(f, "cancel {}", mid),
+            Control::Supersede(mid) => write!(f, "supersede {}", mid),
+        }
+    }
+}
+
+/// Checks the signature of a control message against the original article
+///
+/// How signatures are created and which keys are trusted is up to the implementation.
+pub trait SignatureVerifier: Send + Sync {
+    fn verify(&self, control: &NewsBundle, original: &NewsBundle) -> bool;
+}
+
+/// Check whether a message ID can be used in article headers, i.e., it is not empty and has
+/// no whitespace, control characters or angle brackets
+pub fn is_valid_message_id(mid: &str) -> bool {
+    !mid.is_empty()
+        && !mid
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == '<' || c == '>')
 }
 
 impl News {
+    /// Check the message ID, references and control target
+    fn check_message_ids(&self) -> Result<(), NewsError> {
+        let mids = self
+            .mid
+            .iter()
+            .chain(&self.references)
+            .map(String::as_str)
+            .chain(self.ctl.as_ref().map(Control::target));
+        for mid in mids {
+            if !is_valid_message_id(mid) {
+                return Err(NewsError::InvalidMessageId(mid.into()));
+            }
+        }
+        Ok(())
+    }
     pub fn compression(&self) -> bool {
+        self.comp != Compression::None
+    }
+    pub fn compression_algorithm(&self) -> Compression {
         self.comp
     }
     pub fn encryption(&self) -> bool {
         self.enc
     }
-    pub fn references(&self) -> Option<String> {
-        self.references.clone()
+    pub fn key_id(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+    /// Encrypted or decrypted from encrypted content, see [`News::decrypt`]
+    pub fn is_private(&self) -> bool {
+        self.enc || self.kid.is_some()
+    }
+    /// Clear fields bound to the encrypted content, so it cannot be moved to another article
+    fn associated_data(&self) -> Vec<u8> {
+        serde_cbor::to_vec(&(&self.mid, self.tid, &self.kid))
+            .expect("Fatal failure, could not convert associated data to CBOR")
+    }
+    /// Encrypt topic, tags and message with a group key
+    fn seal(&mut self, key: &GroupKey) {
+        self.enc = true;
+        self.kid = Some(key.id().into());
+        let sealed = Sealed {
+            topic: std::mem::take(&mut self.topic),
+            tags: std::mem::take(&mut self.tags),
+            msg: std::mem::take(&mut self.msg),
+        };
+        let plaintext = serde_cbor::to_vec(&sealed)
+            .expect("Fatal failure, could not convert news content to CBOR");
+        self.msg = key.seal(&self.associated_data(), &plaintext);
+    }
+    /// Plain copy of the payload, encrypted content requires a keyring with the key of its newsgroup
+    ///
+    /// The key ID is kept to mark the copy as private.
+    pub fn decrypt(&self, group: &str, keyring: Option<&Keyring>) -> Result<News, NewsError> {
+        if !self.enc {
+            return Ok(self.clone());
+        }
+        let keyring = keyring.ok_or(NewsError::Encrypted)?;
+        let kid = self.kid.clone().unwrap_or_default();
+        let key = keyring.get(group, &kid).ok_or(NewsError::UnknownKey(kid))?;
+        let plaintext = key.open(&self.associated_data(), &self.msg)?;
+        let sealed: Sealed = serde_cbor::from_slice(&plaintext)?;
+        for data in [&sealed.topic, &sealed.msg] {
+            String::from_utf8(self.comp.decompress(data)?)?;
+        }
+        Ok(News {
+            enc: false,
+            topic: sealed.topic,
+            tags: sealed.tags,
+            msg: sealed.msg,
+            ..self.clone()
+        })
+    }
+    pub fn references(&self) -> &[String] {
+        self.references.as_slice()
+    }
+    /// Explicit message ID, not present in articles created by older versions
+    pub fn message_id(&self) -> Option<&str> {
+        self.mid.as_deref()
+    }
+    pub fn control(&self) -> Option<&Control> {
+        self.ctl.as_ref()
     }
     pub fn signature(&self) -> Option<Vec<u8>> {
         self.sig.clone()
     }
+    /// Message text, empty for encrypted articles, see [`News::decrypt`]
     pub fn msg(&self) -> String {
-        if self.compression() {
-            String::from_utf8_lossy(&smaz_decompress(&self.msg).expect("decompressing msg failed"))
-                .to_string()
-        } else {
-            String::from_utf8_lossy(&self.msg).to_string()
+        if self.enc {
+            return String::new();
         }
+        String::from_utf8_lossy(
+            &self
+                .comp
+                .decompress(&self.msg)
+                .expect("decompressing msg failed"),
+        )
+        .to_string()
     }
+    /// Topic, empty for encrypted articles, see [`News::decrypt`]
     pub fn topic(&self) -> String {
-        if self.compression() {
-            String::from_utf8_lossy(
-                &smaz_decompress(&self.topic).expect("decompressing topic failed"),
-            )
-            .to_string()
-        } else {
-            String::from_utf8_lossy(&self.topic).to_string()
+        if self.enc {
+            return String::new();
         }
+        String::from_utf8_lossy(
+            &self
+                .comp
+                .decompress(&self.topic)
+                .expect("decompressing topic failed"),
+        )
+        .to_string()
     }
     pub fn thread_id(&self) -> Uuid {
         self.tid
@@ -255,47 +678,119 @@ impl News {
     pub fn tags(&self) -> &[String] {
         self.tags.as_slice()
     }
+    pub fn expires(&self) -> Option<DtnTime> {
+        self.exp
+    }
+    /// Newsgroup the post was submitted to if it awaits approval
+    pub fn submission_group(&self) -> Option<&str> {
+        self.grp.as_deref()
+    }
+    pub fn approval(&self) -> Option<&Approval> {
+        self.apv.as_ref()
+    }
+    /// Data covered by the moderator signature, independent of the compression used
+    ///
+    /// The author is covered so an approval cannot be replayed under another source endpoint.
+    /// Encrypted content is covered as is.
+    pub fn approval_data(&self, group: &str, author: &EndpointID) -> Vec<u8> {
+        let (topic, msg) = if self.enc {
+            (self.topic.clone(), self.msg.clone())
+        } else {
+            (
+                self.comp.decompress(&self.topic).unwrap_or_default(),
+                self.comp.decompress(&self.msg).unwrap_or_default(),
+            )
+        };
+        serde_cbor::to_vec(&(
+            group,
+            author.to_string(),
+            &self.mid,
+            self.tid,
+            &self.references,
+            &self.tags,
+            serde_bytes::Bytes::new(&topic),
+            serde_bytes::Bytes::new(&msg),
+            &self.ctl,
+            self.exp,
+        ))
+        .expect("Fatal failure, could not convert approval data to CBOR")
+    }
 }
 
 pub struct NewsBuilder {
     comp: bool,
+    algo: Option<Compression>,
     enc: bool,
     topic: Option<String>,
     thread_id: Option<Uuid>,
-    references: Option<String>,
+    message_id: Option<String>,
+    references: Vec<String>,
     tags: Vec<String>,
     msg: Option<String>,
     sig: Option<Vec<u8>>,
+    control: Option<Control>,
+    expires: Option<DtnTime>,
+    key: Option<GroupKey>,
+    encrypted_parent: bool,
+    private_parent: bool,
 }
 
 impl NewsBuilder {
     pub fn new() -> Self {
         NewsBuilder {
             comp: true,
+            algo: None,
             enc: false,
             topic: None,
             thread_id: None,
-            references: None,
+            message_id: None,
+            references: Vec::new(),
             tags: vec![],
             msg: None,
             sig: None,
+            control: None,
+            expires: None,
+            key: None,
+            encrypted_parent: false,
+            private_parent: false,
         }
     }
+    /// Reply in the thread of an article, taking over its topic and tags
+    ///
+    /// Encrypted articles have to be [decrypted](NewsBundle::decrypted) first,
+    /// building fails with [`NewsError::Encrypted`] otherwise. Replies to private articles
+    /// have to be [encrypted](NewsBuilder::encrypt), building fails with [`NewsError::NoKey`] otherwise.
     pub fn reply_to(mut self, news: &NewsBundle) -> Self {
-        self.references = Some(news.id());
+        self.encrypted_parent = news.encryption();
+        self.private_parent = news.is_private();
+        self.references = news.references();
+        self.references.push(news.message_id());
         self.thread_id = Some(news.tid());
         self.tags = news.tags();
         self.topic = Some(news.topic());
         self
     }
+    /// Compress with the algorithm producing the smallest output
     pub fn compression(mut self, comp: bool) -> Self {
         self.comp = comp;
         self
     }
+    /// Force a specific compression algorithm
+    pub fn compression_algorithm(mut self, algo: Compression) -> Self {
+        self.algo = Some(algo);
+        self
+    }
+    /// Encrypt topic, tags and message, requires a group key, see [`NewsBuilder::encrypt`]
     pub fn encryption(mut self, enc: bool) -> Self {
         self.enc = enc;
         self
     }
+    /// Encrypt topic, tags and message with a group key, e.g., the current key of a [`Keyring`]
+    pub fn encrypt(mut self, key: &GroupKey) -> Self {
+        self.enc = true;
+        self.key = Some(key.clone());
+        self
+    }
     pub fn message(mut self, msg: &str) -> Self {
         self.msg = Some(msg.into());
         self
@@ -308,8 +803,18 @@ impl NewsBuilder {
         self.thread_id = Some(tid);
         self
     }
-    pub fn references(mut self, bid: &str) -> Self {
-        self.references = Some(bid.into());
+    /// Use a specific message ID instead of a random one, it has to pass [`is_valid_message_id`]
+    pub fn message_id(mut self, mid: &str) -> Self {
+        self.message_id = Some(mid.into());
+        self
+    }
+    /// Append a message ID to the references
+    pub fn reference(mut self, mid: &str) -> Self {
+        self.references.push(mid.into());
+        self
+    }
+    pub fn references(mut self, mids: Vec<String>) -> Self {
+        self.references = mids;
         self
     }
     pub fn tag(mut self, tag: &str) -> Self {
@@ -324,33 +829,61 @@ impl NewsBuilder {
         self.sig = Some(sig);
         self
     }
+    pub fn control(mut self, control: Control) -> Self {
+        self.control = Some(control);
+        self
+    }
+    /// Time until which readers should keep the article, like the netnews `Expires` header
+    pub fn expires(mut self, time: DtnTime) -> Self {
+        self.expires = Some(time);
+        self
+    }
+    /// Keep the article for the given duration from now on
+    pub fn expires_in(self, duration: Duration) -> Self {
+        self.expires(dtn_time_now() + duration.as_millis() as u64)
+    }
     pub fn build(self) -> Result<News, NewsError> {
-        if let Some(msg) = self.msg {
-            Ok(News {
-                comp: self.comp,
-                enc: self.enc,
-                topic: if self.comp {
-                    smaz_compress(self.topic.ok_or(NewsError::NoTopic)?.as_bytes())
-                } else {
-                    self.topic.ok_or(NewsError::NoTopic)?.as_bytes().to_vec()
-                },
-                tid: if let Some(tid) = self.thread_id {
-                    tid
-                } else {
-                    Uuid::new_v4()
-                },
-                references: self.references,
-                tags: self.tags,
-                msg: if self.comp {
-                    smaz_compress(msg.as_bytes())
-                } else {
-                    msg.as_bytes().to_vec()
-                },
-                sig: self.sig,
-            })
-        } else {
-            Err(NewsError::NoMessage)
+        if self.encrypted_parent {
+            return Err(NewsError::Encrypted);
+        }
+        if self.private_parent && !self.enc {
+            return Err(NewsError::NoKey);
         }
+        let msg = self.msg.ok_or(NewsError::NoMessage)?;
+        let topic = self.topic.ok_or(NewsError::NoTopic)?;
+        let comp = match self.algo {
+            Some(algo) => algo,
+            None if self.comp => Compression::smallest(&[topic.as_bytes(), msg.as_bytes()]),
+            None => Compression::None,
+        };
+        let mut news = News {
+            comp,
+            enc: false,
+            topic: comp.compress(topic.as_bytes()),
+            tid: if let Some(tid) = self.thread_id {
+                tid
+            } else {
+                Uuid::new_v4()
+            },
+            references: self.references,
+            tags: self.tags,
+            msg: comp.compress(msg.as_bytes()),
+            sig: self.sig,
+            mid: Some(
+                self.message_id
+                    .unwrap_or_else(|| Uuid::new_v4().to_string()),
+            ),
+            ctl: self.control,
+            exp: self.expires,
+            grp: None,
+            apv: None,
+            kid: None,
+        };
+        news.check_message_ids()?;
+        if self.enc {
+            news.seal(self.key.as_ref().ok_or(NewsError::NoKey)?);
+        }
+        Ok(news)
     }
 }
 
@@ -366,82 +899,352 @@ pub fn new_news(
     dst_newsgroup: &str,
     topic: &str,
     thread_id: Option<Uuid>,
-    references: Option<String>,
+    references: Vec<String>,
+    msg: &str,
+    tags: Vec<String>,
+    compression: bool,
+    lifetime: Duration,
+) -> Result<NewsBundle, NewsError> {
+    new_news_moderated(
+        src_node_name,
+        dst_newsgroup,
+        topic,
+        thread_id,
+        references,
+        msg,
+        tags,
+        compression,
+        lifetime,
+        &Moderators::new(),
+    )
+}
+
+/// Create a new news bundle, posts to moderated newsgroups are submitted to their moderator
+#[allow(clippy::too_many_arguments)]
+pub fn new_news_moderated(
+    src_node_name: &str,
+    dst_newsgroup: &str,
+    topic: &str,
+    thread_id: Option<Uuid>,
+    references: Vec<String>,
     msg: &str,
     tags: Vec<String>,
     compression: bool,
+    lifetime: Duration,
+    moderators: &Moderators,
 ) -> Result<NewsBundle, NewsError> {
     let src_eid = EndpointID::with_dtn(&format!("//{}/sms", src_node_name))?;
     let dst_eid = EndpointID::with_dtn(&format!("//{}/~news", dst_newsgroup))?;
 
-    let pblock = primary::PrimaryBlockBuilder::default()
-        .destination(dst_eid)
-        .source(src_eid)
-        .report_to(EndpointID::none())
-        .creation_timestamp(CreationTimestamp::now())
-        .lifetime(Duration::from_secs(60 * 60))
-        .build()
-        .unwrap();
-
     let payload = NewsBuilder::new()
         .compression(compression)
         .message(msg)
         .topic(topic)
         .thread_id(thread_id.unwrap_or_else(Uuid::new_v4))
-        .tags(tags);
-    let payload = if let Some(referece) = references {
-        payload.references(&referece).build()?
-    } else {
-        payload.build()?
-    };
-    let cblocks = vec![canonical::new_payload_block(
-        BlockControlFlags::empty(),
-        serde_cbor::to_vec(&payload)
-            .expect("Fatal failure, could not convert news payload to CBOR"),
-    )];
+        .references(references)
+        .tags(tags)
+        .build()?;
 
-    Ok(NewsBundle::try_from(bundle::Bundle::new(pblock, cblocks))
-        .expect("error creating news bundle"))
+    NewsBundleBuilder::new()
+        .source(src_eid)
+        .destination(dst_eid)
+        .lifetime(lifetime)
+        .payload(payload)
+        .moderators(moderators)
+        .build()
 }
 
-/// Create a new news bundle for DTN addressing scheme
+/// Create a reply in clear text
+///
+/// Replies to encrypted articles fail with [`NewsError::Encrypted`] and to decrypted copies
+/// with [`NewsError::NoKey`], use [`NewsBuilder::encrypt`] for those.
 pub fn reply_news(
     parent_post: &NewsBundle,
     src_node_name: &str,
     msg: &str,
     compression: bool,
+    lifetime: Duration,
+) -> Result<NewsBundle, NewsError> {
+    reply_news_moderated(
+        parent_post,
+        src_node_name,
+        msg,
+        compression,
+        lifetime,
+        &Moderators::new(),
+    )
+}
+
+/// Create a reply in clear text like [`reply_news`], replies in moderated newsgroups are
+/// submitted to their moderator
+pub fn reply_news_moderated(
+    parent_post: &NewsBundle,
+    src_node_name: &str,
+    msg: &str,
+    compression: bool,
+    lifetime: Duration,
+    moderators: &Moderators,
 ) -> Result<NewsBundle, NewsError> {
     let src_eid = EndpointID::with_dtn(&format!("//{}/sms", src_node_name))?;
 
-    let pblock = primary::PrimaryBlockBuilder::default()
-        .destination(parent_post.bundle().primary.destination.clone())
+    let payload = NewsBuilder::new()
+        .compression(compression)
+        .message(msg)
+        .reply_to(parent_post)
+        .build()?;
+
+    NewsBundleBuilder::new()
         .source(src_eid)
-        .report_to(EndpointID::none())
-        .creation_timestamp(CreationTimestamp::now())
-        .lifetime(Duration::from_secs(60 * 60))
+        .destination(parent_post.bundle().primary.destination.clone())
+        .lifetime(lifetime)
+        .payload(payload)
+        .moderators(moderators)
         .build()
-        .unwrap();
+}
+
+/// Create a control message cancelling an earlier article, it travels as long as the original
+pub fn cancel_news(original: &NewsBundle, src_node_name: &str) -> Result<NewsBundle, NewsError> {
+    let src_eid = EndpointID::with_dtn(&format!("//{}/sms", src_node_name))?;
+    let mid = original.message_id();
+
+    let payload = NewsBuilder::new()
+        .compression(false)
+        .topic(&format!("cmsg cancel {}", mid))
+        .message("")
+        .thread_id(original.tid())
+        .control(Control::Cancel(mid))
+        .build()?;
+
+    news_bundle(
+        src_eid,
+        original.bundle().primary.destination.clone(),
+        original.bundle().primary.lifetime,
+        &payload,
+    )
+}
+
+/// Create a corrected version of an earlier article that replaces it, keeping the original lifetime
+///
+/// The correction is in clear text, so encrypted articles fail with [`NewsError::Encrypted`]
+/// and decrypted copies with [`NewsError::NoKey`].
+pub fn supersede_news(
+    original: &NewsBundle,
+    src_node_name: &str,
+    msg: &str,
+    compression: bool,
+) -> Result<NewsBundle, NewsError> {
+    if original.encryption() {
+        return Err(NewsError::Encrypted);
+    }
+    if original.is_private() {
+        return Err(NewsError::NoKey);
+    }
+    let src_eid = EndpointID::with_dtn(&format!("//{}/sms", src_node_name))?;
 
     let payload = NewsBuilder::new()
         .compression(compression)
+        .topic(&original.topic())
         .message(msg)
-        .reply_to(parent_post)
+        .thread_id(original.tid())
+        .references(original.references())
+        .tags(original.tags())
+        .control(Control::Supersede(original.message_id()))
         .build()?;
 
-    let cblocks = vec![canonical::new_payload_block(
-        BlockControlFlags::empty(),
-        serde_cbor::to_vec(&payload)
-            .expect("Fatal failure, could not convert news payload to CBOR"),
-    )];
+    news_bundle(
+        src_eid,
+        original.bundle().primary.destination.clone(),
+        original.bundle().primary.lifetime,
+        &payload,
+    )
+}
 
-    Ok(NewsBundle::try_from(bundle::Bundle::new(pblock, cblocks))
-        .expect("error creating news bundle"))
+/// Endpoint of a newsgroup, numeric group names become IPN endpoints, e.g., `42` is `ipn:42.119`
+pub fn newsgroup_eid(group: &str) -> Result<EndpointID, NewsError> {
+    match group.parse::<u64>() {
+        Ok(number) => Ok(EndpointID::with_ipn(number, NEWS_IPN_SERVICE)?),
+        Err(_) => Ok(EndpointID::with_dtn(&format!("//{}/~news", group))?),
+    }
+}
+
+/// Sending endpoint of a node, numeric node names become IPN endpoints, e.g., `23` is `ipn:23.767`
+pub fn sender_eid(node: &str) -> Result<EndpointID, NewsError> {
+    match node.parse::<u64>() {
+        Ok(number) => Ok(EndpointID::with_ipn(number, SENDER_IPN_SERVICE)?),
+        Err(_) => Ok(EndpointID::with_dtn(&format!("//{}/sms", node))?),
+    }
+}
+
+/// Check whether an endpoint uses the newsgroup service of its scheme
+fn is_newsgroup_eid(eid: &EndpointID) -> bool {
+    match eid {
+        EndpointID::Ipn(_, ipn) => ipn.service_number() == NEWS_IPN_SERVICE,
+        EndpointID::Dtn(_, ssp) => ssp.service_name() == Some("~news"),
+        _ => false,
+    }
+}
+
+/// Builder for news bundles with arbitrary endpoints
+///
+/// The resulting bundle passes the same validation as received bundles,
+/// so endpoints must use the news services of either scheme.
+/// Posts to newsgroups of the given [`moderators`](Self::moderators) are addressed to the
+/// moderator instead, see [`moderation`].
+pub struct NewsBundleBuilder {
+    src: Option<EndpointID>,
+    dst: Option<EndpointID>,
+    lifetime: Duration,
+    flags: BundleControlFlags,
+    payload: Option<News>,
+    moderators: Option<Moderators>,
+}
+
+impl NewsBundleBuilder {
+    pub fn new() -> Self {
+        NewsBundleBuilder {
+            src: None,
+            dst: None,
+            lifetime: DEFAULT_LIFETIME,
+            flags: BundleControlFlags::empty(),
+            payload: None,
+            moderators: None,
+        }
+    }
+    pub fn source(mut self, src: EndpointID) -> Self {
+        self.src = Some(src);
+        self
+    }
+    pub fn destination(mut self, dst: EndpointID) -> Self {
+        self.dst = Some(dst);
+        self
+    }
+    /// Address a newsgroup by name or IPN group number
+    pub fn newsgroup(mut self, group: &str) -> Result<Self, NewsError> {
+        self.dst = Some(newsgroup_eid(group)?);
+        Ok(self)
+    }
+    pub fn lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+    /// Bundle processing control flags of the primary block
+    pub fn bundle_control_flags(mut self, flags: BundleControlFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+    pub fn payload(mut self, news: News) -> Self {
+        self.payload = Some(news);
+        self
+    }
+    /// Submit posts to moderated newsgroups to their moderator
+    pub fn moderators(mut self, moderators: &Moderators) -> Self {
+        self.moderators = Some(moderators.clone());
+        self
+    }
+    pub fn build(self) -> Result<NewsBundle, NewsError> {
+        let src = self
+            .src
+            .ok_or_else(|| NewsError::InvalidEndpoint.in_field(NewsField::Source))?;
+        let dst = self
+            .dst
+            .ok_or_else(|| NewsError::InvalidEndpoint.in_field(NewsField::Destination))?;
+        let mut payload = self
+            .payload
+            .ok_or_else(|| NewsError::PayloadMissing.in_field(NewsField::Payload))?;
+
+        // posts to moderated groups go to the moderator first, authors may still cancel directly
+        let mut dst = dst;
+        let exempt = payload.apv.is_some()
+            || payload.grp.is_some()
+            || matches!(payload.ctl, Some(Control::Cancel(_)));
+        if !exempt
+            && is_newsgroup_eid(&dst)
+            && let Some(group) = dst.node()
+            && let Some(moderator) = self
+                .moderators
+                .as_ref()
+                .and_then(|moderators| moderators.moderator(&group))
+        {
+            dst = moderator.clone();
+            payload.grp = Some(group);
+        }
+
+        let pblock = primary::PrimaryBlockBuilder::default()
+            .bundle_control_flags(self.flags.bits())
+            .destination(dst)
+            .source(src)
+            .report_to(EndpointID::none())
+            .creation_timestamp(CreationTimestamp::now())
+            .lifetime(self.lifetime)
+            .build()
+            .unwrap();
+
+        let cblocks = vec![canonical::new_payload_block(
+            BlockControlFlags::empty(),
+            serde_cbor::to_vec(&payload)
+                .expect("Fatal failure, could not convert news payload to CBOR"),
+        )];
+
+        NewsBundle::try_from(bundle::Bundle::new(pblock, cblocks))
+    }
+}
+
+impl Default for NewsBundleBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Write a file only readable by its owner, e.g., one containing keys
+pub(crate) fn write_private<P: AsRef<std::path::Path>>(
+    path: P,
+    contents: &str,
+) -> Result<(), std::io::Error> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // the mode only applies to new files
+        if let Ok(meta) = std::fs::metadata(&path)
+            && meta.permissions().mode() & 0o077 != 0
+        {
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    options.open(path)?.write_all(contents.as_bytes())
+}
+
+fn news_bundle(
+    src_eid: EndpointID,
+    dst_eid: EndpointID,
+    lifetime: Duration,
+    payload: &News,
+) -> Result<NewsBundle, NewsError> {
+    NewsBundleBuilder::new()
+        .source(src_eid)
+        .destination(dst_eid)
+        .lifetime(lifetime)
+        .payload(payload.clone())
+        .build()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::news::{NewsBundle, new_news};
+    use crate::news::NewsError;
+    use crate::news::keyring::Keyring;
+    use crate::news::{
+        Control, DEFAULT_LIFETIME, News, NewsBuilder, NewsBundle, NewsBundleBuilder, NewsField,
+        SignatureVerifier, cancel_news, new_news, news_bundle, newsgroup_eid, sender_eid,
+        supersede_news,
+    };
+    use bp7::EndpointID;
+    use bp7::flags::BundleControlFlags;
     use std::convert::TryFrom;
+    use std::time::Duration;
+    use uuid::Uuid;
 
     use super::reply_news;
     #[test]
@@ -451,10 +1254,11 @@ mod tests {
             "de.hessen.darmstadt",
             "Lorem ipsum dolor sit amet",
             None,
-            None,
+            vec![],
             "The quick brown fox jumps over the lazy dog",
             Vec::new(),
             false,
+            DEFAULT_LIFETIME,
         )
         .unwrap();
         let bin_bundle = news.to_cbor();
@@ -469,10 +1273,11 @@ mod tests {
             "de.hessen.darmstadt",
             "Lorem ipsum dolor sit amet",
             None,
-            None,
+            vec![],
             "The quick brown fox jumps over the lazy dog",
             Vec::new(),
             true,
+            DEFAULT_LIFETIME,
         )
         .unwrap();
         let bin_bundle = news.to_cbor();
@@ -494,8 +1299,7 @@ mod tests {
             01239468786,
             01239468999,
             "The quick brown fox jumps over the lazy dog",
-            true,
-        )
+            true, DEFAULT_LIFETIME,)
         .unwrap();
         let bin_bundle = news.to_cbor();
         let compressed_bundle = loragent::compression::snap_compress(&bin_bundle);
@@ -513,10 +1317,11 @@ mod tests {
             "de.hessen.darmstadt",
             "Lorem ipsum dolor sit amet",
             None,
-            None,
+            vec![],
             "The quick brown fox jumps over the lazy dog",
             Vec::new(),
             false,
+            DEFAULT_LIFETIME,
         )
         .unwrap();
         let mut raw_bundle = news.bundle().clone();
@@ -549,18 +1354,398 @@ mod tests {
             "de.hessen.darmstadt",
             "Lorem ipsum dolor sit amet",
             None,
-            None,
+            vec![],
             "The quick brown fox jumps over the lazy dog",
             Vec::new(),
             false,
+            DEFAULT_LIFETIME,
         )
         .unwrap();
 
-        let news2 = reply_news(&news1, "node2", "just a reply", true).unwrap();
+        let news2 = reply_news(&news1, "node2", "just a reply", true, DEFAULT_LIFETIME).unwrap();
         assert_eq!(news1.topic(), news2.topic());
         assert_eq!(news1.tid(), news2.tid());
         assert_eq!(news1.tags(), news2.tags());
-        assert_eq!(Some(news1.id()), news2.references());
+        assert_eq!(vec![news1.message_id()], news2.references());
         assert_ne!(news1.msg(), news2.msg());
+        assert_ne!(news1.message_id(), news2.message_id());
+
+        let news3 = reply_news(
+            &news2,
+            "node3",
+            "reply to the reply",
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![news1.message_id(), news2.message_id()],
+            news3.references()
+        );
+    }
+
+    #[test]
+    fn test_news_control() {
+        let original = new_news(
+            "node1",
+            "de.hessen.darmstadt",
+            "Announcement",
+            None,
+            vec![],
+            "Meeting at 10:00",
+            vec!["events".into()],
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        let reply = reply_news(&original, "node2", "see you", true, DEFAULT_LIFETIME).unwrap();
+
+        let cancel = cancel_news(&original, "node1").unwrap();
+        assert_eq!(
+            cancel.control(),
+            Some(Control::Cancel(original.message_id()))
+        );
+        assert!(cancel.controls(&original, None));
+        assert!(!cancel.controls(&reply, None));
+        assert!(original.control().is_none());
+
+        let forged = cancel_news(&original, "node2").unwrap();
+        assert!(!forged.controls(&original, None));
+
+        let correction = supersede_news(&original, "node1", "Meeting at 11:00", true).unwrap();
+        assert_eq!(
+            correction.control(),
+            Some(Control::Supersede(original.message_id()))
+        );
+        assert_eq!(correction.topic(), original.topic());
+        assert_eq!(correction.tid(), original.tid());
+        assert!(correction.controls(&original, None));
+
+        struct AcceptAll;
+        impl SignatureVerifier for AcceptAll {
+            fn verify(&self, _control: &NewsBundle, _original: &NewsBundle) -> bool {
+                true
+            }
+        }
+        let payload = NewsBuilder::new()
+            .topic("cmsg")
+            .message("")
+            .signature(vec![1, 2, 3])
+            .control(Control::Cancel(original.message_id()))
+            .build()
+            .unwrap();
+        let signed = news_bundle(
+            EndpointID::with_dtn("//moderator/sms").unwrap(),
+            original.bundle().primary.destination.clone(),
+            DEFAULT_LIFETIME,
+            &payload,
+        )
+        .unwrap();
+        assert!(!signed.controls(&original, None));
+        assert!(signed.controls(&original, Some(&AcceptAll)));
+    }
+
+    #[test]
+    fn test_news_bundle_builder_ipn() {
+        let payload = NewsBuilder::new()
+            .topic("Pegelstand")
+            .message("Rhein bei 4,20 m")
+            .build()
+            .unwrap();
+        let news = NewsBundleBuilder::new()
+            .source(sender_eid("23").unwrap())
+            .newsgroup("42")
+            .unwrap()
+            .lifetime(Duration::from_secs(24 * 60 * 60))
+            .bundle_control_flags(BundleControlFlags::BUNDLE_MUST_NOT_FRAGMENTED)
+            .payload(payload.clone())
+            .build()
+            .unwrap();
+        assert!(news.is_pure("ipn"));
+        assert_eq!(news.src_ipn(), 23);
+        assert_eq!(news.dst_ipn(), 42);
+        assert_eq!(news.bundle().primary.destination.to_string(), "ipn:42.119");
+        assert_eq!(news.bundle().primary.lifetime.as_secs(), 24 * 60 * 60);
+        assert_eq!(
+            news.bundle().primary.bundle_control_flags,
+            BundleControlFlags::BUNDLE_MUST_NOT_FRAGMENTED.bits()
+        );
+        assert_eq!(news.topic(), "Pegelstand");
+
+        assert_eq!(
+            newsgroup_eid("de.test").unwrap().to_string(),
+            "dtn://de.test/~news"
+        );
+        let err = NewsBundleBuilder::new()
+            .source(EndpointID::with_ipn(23, 1).unwrap())
+            .newsgroup("42")
+            .unwrap()
+            .payload(payload.clone())
+            .build()
+            .unwrap_err();
+        assert_eq!(err.field(), Some(NewsField::Source));
+        let err = NewsBundleBuilder::new()
+            .source(sender_eid("23").unwrap())
+            .payload(payload)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.field(), Some(NewsField::Destination));
+    }
+
+    #[test]
+    fn test_news_decode_all() {
+        let mut buf = Vec::new();
+        let mut ids = Vec::new();
+        for topic in ["first", "second", "third"] {
+            let mut news = new_news(
+                "node1",
+                "de.test",
+                topic,
+                None,
+                vec![],
+                "m",
+                vec![],
+                true,
+                DEFAULT_LIFETIME,
+            )
+            .unwrap();
+            ids.push(news.id());
+            buf.extend(news.to_cbor());
+        }
+        let decoded = NewsBundle::decode_all(&buf).unwrap();
+        assert_eq!(decoded.iter().map(|n| n.id()).collect::<Vec<_>>(), ids);
+        assert_eq!(decoded[2].topic(), "third");
+
+        assert!(NewsBundle::decode_all(&buf[..buf.len() - 1]).is_err());
+        assert!(NewsBundle::decode_all(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_news_legacy_references() {
+        #[derive(serde::Serialize)]
+        struct LegacyNews {
+            comp: bool,
+            enc: bool,
+            #[serde(with = "serde_bytes")]
+            topic: Vec<u8>,
+            tid: Uuid,
+            references: Option<String>,
+            tags: Vec<String>,
+            #[serde(with = "serde_bytes")]
+            msg: Vec<u8>,
+            sig: Option<Vec<u8>>,
+        }
+        let legacy = |references: Option<String>| LegacyNews {
+            comp: false,
+            enc: false,
+            topic: b"topic".to_vec(),
+            tid: Uuid::new_v4(),
+            references,
+            tags: vec![],
+            msg: b"msg".to_vec(),
+            sig: None,
+        };
+
+        let buf = serde_cbor::to_vec(&legacy(Some("dtn://node1/sms-1-0".into()))).unwrap();
+        let news: News = serde_cbor::from_slice(&buf).unwrap();
+        assert_eq!(news.references(), ["dtn://node1/sms-1-0"]);
+        assert_eq!(news.message_id(), None);
+        // a single reference is still encoded as plain string
+        assert_eq!(serde_cbor::to_vec(&news).unwrap(), buf);
+
+        let buf = serde_cbor::to_vec(&legacy(None)).unwrap();
+        let news: News = serde_cbor::from_slice(&buf).unwrap();
+        assert!(news.references().is_empty());
+        assert_eq!(serde_cbor::to_vec(&news).unwrap(), buf);
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let news = new_news(
+            "node1",
+            "de.hessen.darmstadt",
+            "Lorem ipsum dolor sit amet",
+            None,
+            vec![],
+            "The quick brown fox jumps over the lazy dog",
+            Vec::new(),
+            false,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        let mut raw_bundle = news.bundle().clone();
+        raw_bundle.primary.source = bp7::EndpointID::try_from("dtn://node1/mail").unwrap();
+
+        let err = NewsBundle::try_from(raw_bundle.clone()).unwrap_err();
+        assert_eq!(err.field(), Some(NewsField::Source));
+        assert_eq!(err.to_string(), "invalid news bundle, source");
+        assert_eq!(
+            std::error::Error::source(&err).unwrap().to_string(),
+            "unknown endpoint service: dtn://node1/mail"
+        );
+
+        let (lenient, warnings) = NewsBundle::try_from_lenient(raw_bundle.clone()).unwrap();
+        assert_eq!(lenient.topic(), "Lorem ipsum dolor sit amet");
+        assert_eq!(warnings[0].field(), Some(NewsField::Source));
+
+        raw_bundle.primary.destination = bp7::EndpointID::none();
+        let err = NewsBundle::try_from_lenient(raw_bundle).unwrap_err();
+        assert_eq!(err.field(), Some(NewsField::Destination));
+    }
+
+    #[test]
+    fn test_submission_destination() {
+        let mut payload = NewsBuilder::new().topic("t").message("m").build().unwrap();
+        payload.grp = Some("de.test".into());
+        let submission = NewsBundleBuilder::new()
+            .source(sender_eid("node1").unwrap())
+            .destination(EndpointID::try_from("dtn://mod1/moderation").unwrap())
+            .payload(payload)
+            .build()
+            .unwrap();
+        assert!(submission.is_submission());
+
+        for dst in [
+            "dtn://de.test/~news",
+            "ipn:23.119",
+            "dtn://mod1/~moderation",
+            "dtn:none",
+        ] {
+            let mut raw_bundle = submission.bundle().clone();
+            raw_bundle.primary.destination = EndpointID::try_from(dst).unwrap();
+            let err = NewsBundle::try_from_lenient(raw_bundle).unwrap_err();
+            assert_eq!(err.field(), Some(NewsField::Destination), "{}", dst);
+        }
+        let mut raw_bundle = submission.bundle().clone();
+        raw_bundle.primary.destination = EndpointID::try_from("ipn:23.120").unwrap();
+        assert!(NewsBundle::try_from(raw_bundle).is_ok());
+    }
+
+    #[test]
+    fn test_encrypted_news_shared_key_id() {
+        use crate::news::keyring::GroupKey;
+        let mut keyring = Keyring::new();
+        keyring.add("de.private", GroupKey::new("k1", [1; 32]));
+        keyring.add("en.private", GroupKey::new("k1", [2; 32]));
+        for group in ["de.private", "en.private"] {
+            let payload = NewsBuilder::new()
+                .topic(group)
+                .message("m")
+                .encrypt(keyring.current(group).unwrap())
+                .build()
+                .unwrap();
+            let news = news_bundle(
+                sender_eid("node1").unwrap(),
+                newsgroup_eid(group).unwrap(),
+                DEFAULT_LIFETIME,
+                &payload,
+            )
+            .unwrap();
+            assert_eq!(news.topic_with(Some(&keyring)).unwrap(), group);
+        }
+    }
+
+    #[test]
+    fn test_encrypted_news() {
+        let mut keyring = Keyring::new();
+        let old_key = keyring.rotate("de.private").clone();
+        let payload = NewsBuilder::new()
+            .topic("Geheim")
+            .message("Treffen um acht")
+            .tag("intern")
+            .encrypt(&old_key)
+            .build()
+            .unwrap();
+        assert!(payload.encryption());
+        assert_eq!(payload.key_id(), Some(old_key.id()));
+        assert_eq!(payload.topic(), "");
+        assert!(payload.tags().is_empty());
+        let src = sender_eid("node1").unwrap();
+        let mut news = news_bundle(
+            src,
+            newsgroup_eid("de.private").unwrap(),
+            DEFAULT_LIFETIME,
+            &payload,
+        )
+        .unwrap();
+        let news = NewsBundle::try_from(news.to_cbor()).unwrap();
+        assert_eq!(news.dst().as_deref(), Some("de.private"));
+        assert_eq!(news.key_id().as_deref(), Some(old_key.id()));
+        assert!(matches!(news.msg_with(None), Err(NewsError::Encrypted)));
+
+        // articles written before a rotation stay readable
+        let new_key = keyring.rotate("de.private").clone();
+        assert_ne!(new_key.id(), old_key.id());
+        assert_eq!(keyring.current("de.private"), Some(&new_key));
+        assert_eq!(news.topic_with(Some(&keyring)).unwrap(), "Geheim");
+        assert_eq!(news.msg_with(Some(&keyring)).unwrap(), "Treffen um acht");
+        assert_eq!(news.tags_with(Some(&keyring)).unwrap(), vec!["intern"]);
+        let plain = news.decrypted(&keyring).unwrap();
+        assert!(!plain.encryption());
+        assert_eq!(plain.id(), news.id());
+        assert_eq!(plain.message_id(), news.message_id());
+        assert_eq!(plain.msg(), "Treffen um acht");
+
+        let mut other = Keyring::new();
+        other.rotate("de.private");
+        assert!(matches!(
+            news.msg_with(Some(&other)),
+            Err(NewsError::UnknownKey(_))
+        ));
+        let mut wrong = Keyring::new();
+        wrong.add(
+            "de.private",
+            super::keyring::GroupKey::new(old_key.id(), [7; 32]),
+        );
+        assert!(matches!(
+            news.msg_with(Some(&wrong)),
+            Err(NewsError::Decryption)
+        ));
+        assert!(matches!(
+            NewsBuilder::new()
+                .topic("t")
+                .message("m")
+                .encryption(true)
+                .build(),
+            Err(NewsError::NoKey)
+        ));
+
+        // follow-ups never leak into clear text
+        assert!(matches!(
+            reply_news(&news, "node2", "ok", true, DEFAULT_LIFETIME),
+            Err(NewsError::Encrypted)
+        ));
+        assert!(matches!(
+            supersede_news(&news, "node1", "fixed", true),
+            Err(NewsError::Encrypted)
+        ));
+        // decrypted copies stay private
+        assert!(plain.is_private());
+        assert!(matches!(
+            reply_news(&plain, "node2", "ok", true, DEFAULT_LIFETIME),
+            Err(NewsError::NoKey)
+        ));
+        assert!(matches!(
+            supersede_news(&plain, "node1", "fixed", true),
+            Err(NewsError::NoKey)
+        ));
+        let reply = NewsBuilder::new()
+            .reply_to(&plain)
+            .message("ok")
+            .encrypt(keyring.current("de.private").unwrap())
+            .build()
+            .unwrap();
+        assert!(reply.encryption());
+        assert_eq!(reply.key_id(), Some(new_key.id()));
+        assert_eq!(reply.topic(), "");
+        let reply = news_bundle(
+            sender_eid("node2").unwrap(),
+            plain.bundle().primary.destination.clone(),
+            DEFAULT_LIFETIME,
+            &reply,
+        )
+        .unwrap();
+        assert_eq!(reply.topic_with(Some(&keyring)).unwrap(), "Geheim");
+        assert_eq!(reply.references(), vec![news.message_id()]);
     }
 }
@@ -0,0 +1,429 @@
+//! Moderated newsgroups
+//!
+//! Posts to a moderated group are sent to its moderator as submissions, see [`NewsBundle::is_submission`].
+//! The moderator publishes approved posts to the group with an [`Approval`] signed over
+//! [`News::approval_data`]. Receivers configured with the same moderators drop posts
+//! without a valid approval.
+//!
+//! Senders pass the moderators to [`NewsBundleBuilder::moderators`] or use
+//! [`new_news_moderated`] and [`reply_news_moderated`], receivers pass them to
+//! [`Spool::with_moderation`]. They are usually read from a file similar to the `moderators`
+//! file of INN:
+//!
+//! ```text
+//! # newsgroups:moderator endpoint
+//! de.announce.*:dtn://mod1/moderation
+//! 42:ipn:23.120
+//! ```
+//!
+//! Approvals are Ed25519 signatures: moderators sign with a [`ModeratorKey`], receivers check
+//! them with the public keys in [`ModeratorKeys`], stored as one `moderator-endpoint base64-key`
+//! per line.
+//!
+//! [`News`]: super::News
+//! [`new_news_moderated`]: super::new_news_moderated
+//! [`reply_news_moderated`]: super::reply_news_moderated
+//! [`Spool::with_moderation`]: super::spool::Spool::with_moderation
+use super::subscription::Wildmat;
+use super::{
+    Approval, Control, NewsBundle, NewsBundleBuilder, NewsError, is_newsgroup_eid, newsgroup_eid,
+};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use bp7::EndpointID;
+use chacha20poly1305::aead::OsRng;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use std::fmt;
+use std::path::Path;
+
+/// Creates the signature of an approving moderator
+pub trait ApprovalSigner {
+    fn sign(&self, data: &[u8]) -> Vec<u8>;
+}
+
+/// Checks the signature of a moderator approval
+///
+/// How signatures are created and which keys belong to a moderator is up to the implementation.
+pub trait ApprovalVerifier: Send + Sync {
+    fn verify(&self, moderator: &EndpointID, data: &[u8], sig: &[u8]) -> bool;
+}
+
+/// Ed25519 signing key of a moderator, stored as base64 text
+#[derive(Clone)]
+pub struct ModeratorKey(SigningKey);
+
+impl ModeratorKey {
+    pub fn generate() -> Self {
+        ModeratorKey(SigningKey::generate(&mut OsRng))
+    }
+    pub fn parse(text: &str) -> Result<Self, NewsError> {
+        let key: [u8; 32] = STANDARD
+            .decode(text.trim())
+            .ok()
+            .and_then(|key| key.try_into().ok())
+            .ok_or_else(|| NewsError::InvalidModeratorKey("signing key".into()))?;
+        Ok(ModeratorKey(SigningKey::from_bytes(&key)))
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        let text = std::fs::read_to_string(path)?;
+        ModeratorKey::parse(&text)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string()))
+    }
+    /// Save the key to a file only readable by the owner
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), std::io::Error> {
+        super::write_private(path, &format!("{}\n", STANDARD.encode(self.0.to_bytes())))
+    }
+    /// Public key in the base64 form used by [`ModeratorKeys`]
+    pub fn public_key(&self) -> String {
+        STANDARD.encode(self.0.verifying_key().to_bytes())
+    }
+}
+
+impl fmt::Debug for ModeratorKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ModeratorKey")
+            .field(&self.public_key())
+            .finish()
+    }
+}
+
+impl ApprovalSigner for ModeratorKey {
+    fn sign(&self, data: &[u8]) -> Vec<u8> {
+        self.0.sign(data).to_vec()
+    }
+}
+
+/// Public keys of moderators for checking approvals, a moderator may have several keys
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModeratorKeys {
+    keys: Vec<(EndpointID, VerifyingKey)>,
+}
+
+impl ModeratorKeys {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Parse lines of `moderator-endpoint base64-key`, empty lines and lines starting with `#` are skipped
+    pub fn parse(text: &str) -> Result<Self, NewsError> {
+        let mut keys = ModeratorKeys::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [moderator, key] = fields[..] else {
+                return Err(NewsError::InvalidModeratorKey(line.into()));
+            };
+            keys = keys.with_key(EndpointID::try_from(moderator)?, key)?;
+        }
+        Ok(keys)
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        let text = std::fs::read_to_string(path)?;
+        ModeratorKeys::parse(&text)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string()))
+    }
+    /// Trust a base64 public key for approvals of `moderator`
+    pub fn with_key(mut self, moderator: EndpointID, key: &str) -> Result<Self, NewsError> {
+        let key = STANDARD
+            .decode(key)
+            .ok()
+            .and_then(|key| <[u8; 32]>::try_from(key).ok())
+            .and_then(|key| VerifyingKey::from_bytes(&key).ok())
+            .ok_or_else(|| NewsError::InvalidModeratorKey(moderator.to_string()))?;
+        self.keys.push((moderator, key));
+        Ok(self)
+    }
+}
+
+impl ApprovalVerifier for ModeratorKeys {
+    fn verify(&self, moderator: &EndpointID, data: &[u8], sig: &[u8]) -> bool {
+        let Ok(sig) = Signature::from_slice(sig) else {
+            return false;
+        };
+        self.keys
+            .iter()
+            .filter(|(eid, _)| eid == moderator)
+            .any(|(_, key)| key.verify_strict(data, &sig).is_ok())
+    }
+}
+
+/// Moderator endpoints of newsgroups, the first matching pattern decides
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Moderators {
+    entries: Vec<(Wildmat, EndpointID)>,
+}
+
+impl Moderators {
+    pub const fn new() -> Self {
+        Moderators {
+            entries: Vec::new(),
+        }
+    }
+    /// Parse lines of `newsgroups:moderator endpoint`, empty lines and lines starting with `#` are skipped
+    ///
+    /// Moderators must be singleton endpoints outside of the newsgroup services.
+    pub fn parse(text: &str) -> Result<Self, NewsError> {
+        let mut moderators = Moderators::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (groups, moderator) = line.split_once(':').ok_or(NewsError::InvalidEndpoint)?;
+            let moderator = EndpointID::try_from(moderator.trim())?;
+            if moderator == EndpointID::none()
+                || moderator.is_non_singleton()
+                || is_newsgroup_eid(&moderator)
+            {
+                return Err(NewsError::InvalidEndpoint);
+            }
+            moderators = moderators.with_moderator(groups, moderator);
+        }
+        Ok(moderators)
+    }
+    /// Moderate all groups matching the wildmat
+    pub fn with_moderator(mut self, groups: &str, moderator: EndpointID) -> Self {
+        self.entries.push((Wildmat::parse(groups), moderator));
+        self
+    }
+    pub fn moderator(&self, group: &str) -> Option<&EndpointID> {
+        self.entries
+            .iter()
+            .find(|(groups, _)| groups.matches(group))
+            .map(|(_, moderator)| moderator)
+    }
+    pub fn is_moderated(&self, group: &str) -> bool {
+        self.moderator(group).is_some()
+    }
+    /// Check whether an incoming post may be shown to readers
+    ///
+    /// Posts to moderated groups need an approval by the group moderator with a valid signature.
+    /// Cancel messages are exempt, as they are checked against the original author anyway.
+    pub fn accepts(&self, news: &NewsBundle, verifier: &dyn ApprovalVerifier) -> bool {
+        let Some(group) = news.newsgroup() else {
+            return false;
+        };
+        let Some(moderator) = self.moderator(&group) else {
+            return true;
+        };
+        if news.is_submission() {
+            return false;
+        }
+        if matches!(news.control(), Some(Control::Cancel(_))) {
+            return true;
+        }
+        let payload = news.news();
+        match payload.approval() {
+            Some(approval) => {
+                approval.moderator == *moderator
+                    && verifier.verify(
+                        moderator,
+                        &payload.approval_data(&group, &news.bundle().primary.source),
+                        &approval.sig,
+                    )
+            }
+            None => false,
+        }
+    }
+}
+
+/// Publish a submission to its newsgroup, approved and signed by `moderator`
+///
+/// The author stays the source of the approved post.
+pub fn approve_news(
+    submission: &NewsBundle,
+    moderator: &EndpointID,
+    signer: &dyn ApprovalSigner,
+) -> Result<NewsBundle, NewsError> {
+    let mut payload = submission.news();
+    let group = payload.grp.take().ok_or(NewsError::NotSubmission)?;
+    payload.apv = Some(Approval {
+        moderator: moderator.clone(),
+        sig: signer.sign(&payload.approval_data(&group, &submission.bundle().primary.source)),
+    });
+    NewsBundleBuilder::new()
+        .source(submission.bundle().primary.source.clone())
+        .destination(newsgroup_eid(&group)?)
+        .lifetime(submission.bundle().primary.lifetime)
+        .payload(payload)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ApprovalSigner, ApprovalVerifier, ModeratorKey, ModeratorKeys, Moderators, approve_news,
+    };
+    use crate::news::spool::Spool;
+    use crate::news::{
+        DEFAULT_LIFETIME, NewsBuilder, NewsBundle, NewsBundleBuilder, cancel_news,
+        new_news_moderated, reply_news_moderated, sender_eid,
+    };
+    use bp7::EndpointID;
+    use std::convert::TryFrom;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_moderators_parse() {
+        let mods = Moderators::parse(
+            "# moderated groups\nde.moderated.*:dtn://mod1/moderation\n\n4711:ipn:23.120\n",
+        )
+        .unwrap();
+        assert_eq!(
+            mods.moderator("de.moderated.test").unwrap().to_string(),
+            "dtn://mod1/moderation"
+        );
+        assert_eq!(mods.moderator("4711").unwrap().to_string(), "ipn:23.120");
+        assert!(!mods.is_moderated("de.test"));
+        assert!(Moderators::parse("de.*").is_err());
+        assert!(Moderators::parse("de.*:not an eid").is_err());
+        assert!(Moderators::parse("de.*:dtn://mod1/~moderation").is_err());
+        assert!(Moderators::parse("de.*:dtn://de.test/~news").is_err());
+        assert!(Moderators::parse("de.*:dtn:none").is_err());
+    }
+
+    #[test]
+    fn test_moderator_keys() {
+        let moderator = EndpointID::try_from("dtn://mod1/moderation").unwrap();
+        let key = ModeratorKey::generate();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("moderator.key");
+        key.save(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let loaded = ModeratorKey::load(&path).unwrap();
+        assert_eq!(loaded.public_key(), key.public_key());
+
+        let keys = ModeratorKeys::parse(&format!(
+            "# moderators\n{} {}\n",
+            moderator,
+            key.public_key()
+        ))
+        .unwrap();
+        let sig = loaded.sign(b"data");
+        assert!(keys.verify(&moderator, b"data", &sig));
+        assert!(!keys.verify(&moderator, b"other", &sig));
+        assert!(!keys.verify(&moderator, b"data", b"short"));
+        let mod2 = EndpointID::try_from("dtn://mod2/moderation").unwrap();
+        assert!(!keys.verify(&mod2, b"data", &sig));
+
+        assert!(ModeratorKeys::parse("dtn://mod1/moderation").is_err());
+        assert!(ModeratorKeys::parse("dtn://mod1/moderation bm90IGEga2V5").is_err());
+        assert!(ModeratorKey::parse("bm90IGEga2V5").is_err());
+    }
+
+    #[test]
+    fn test_moderated_post() {
+        let moderator = EndpointID::try_from("dtn://mod1/moderation").unwrap();
+        let mods = Moderators::new().with_moderator("de.moderated.*", moderator.clone());
+        assert!(mods.is_moderated("de.moderated.announce"));
+
+        let post = |group: &str, mods: &Moderators| {
+            NewsBundleBuilder::new()
+                .source(sender_eid("node1").unwrap())
+                .newsgroup(group)
+                .unwrap()
+                .payload(NewsBuilder::new().topic("t").message("m").build().unwrap())
+                .moderators(mods)
+                .build()
+                .unwrap()
+        };
+        let free = post("de.unmoderated", &mods);
+        assert!(!free.is_submission());
+        // senders unaware of the moderator post directly, receivers drop these posts
+        let unapproved = post("de.moderated.announce", &Moderators::new());
+        assert!(!unapproved.is_submission());
+        let key = ModeratorKey::generate();
+        let keys = ModeratorKeys::new()
+            .with_key(moderator.clone(), &key.public_key())
+            .unwrap();
+        assert!(!mods.accepts(&unapproved, &keys));
+
+        let submission = post("de.moderated.announce", &mods);
+        assert!(submission.is_submission());
+        assert_eq!(submission.bundle().primary.destination, moderator);
+        assert_eq!(
+            submission.newsgroup().as_deref(),
+            Some("de.moderated.announce")
+        );
+        assert_eq!(submission.dst().as_deref(), Some("mod1"));
+        assert!(NewsBundle::try_from(submission.clone().to_cbor()).is_ok());
+
+        let approved = approve_news(&submission, &moderator, &key).unwrap();
+        assert!(!approved.is_submission());
+        assert_eq!(approved.dst().as_deref(), Some("de.moderated.announce"));
+        assert_eq!(approved.message_id(), submission.message_id());
+        assert_eq!(approved.src(), submission.src());
+        assert!(approve_news(&approved, &moderator, &key).is_err());
+
+        assert!(mods.accepts(&free, &keys));
+        assert!(mods.accepts(&approved, &keys));
+        assert!(!mods.accepts(&submission, &keys));
+        let other = ModeratorKeys::new()
+            .with_key(moderator.clone(), &ModeratorKey::generate().public_key())
+            .unwrap();
+        assert!(!mods.accepts(&approved, &other));
+        // the approval is bound to the author
+        let replayed = NewsBundleBuilder::new()
+            .source(sender_eid("node3").unwrap())
+            .destination(approved.bundle().primary.destination.clone())
+            .payload(approved.news())
+            .build()
+            .unwrap();
+        assert!(!mods.accepts(&replayed, &keys));
+        let cancel = cancel_news(&approved, "node1").unwrap();
+        assert!(!cancel.is_submission());
+        assert!(mods.accepts(&cancel, &keys));
+
+        // replies are moderated as well
+        let reply = NewsBundleBuilder::new()
+            .source(sender_eid("node2").unwrap())
+            .destination(approved.bundle().primary.destination.clone())
+            .payload(
+                NewsBuilder::new()
+                    .reply_to(&approved)
+                    .message("r")
+                    .build()
+                    .unwrap(),
+            )
+            .moderators(&mods)
+            .build()
+            .unwrap();
+        assert!(reply.is_submission());
+        let reply =
+            reply_news_moderated(&approved, "node2", "r", true, DEFAULT_LIFETIME, &mods).unwrap();
+        assert!(reply.is_submission());
+        assert_eq!(reply.newsgroup(), approved.newsgroup());
+        assert_eq!(
+            reply.bundle().primary.destination,
+            *mods.moderator(&approved.newsgroup().unwrap()).unwrap()
+        );
+        let post = new_news_moderated(
+            "node2",
+            &approved.newsgroup().unwrap(),
+            "t",
+            None,
+            vec![],
+            "m",
+            vec![],
+            true,
+            DEFAULT_LIFETIME,
+            &mods,
+        )
+        .unwrap();
+        assert!(post.is_submission());
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(dir.path())
+            .unwrap()
+            .with_moderation(mods, Arc::new(keys));
+        assert!(!spool.store(submission).unwrap());
+        assert!(!spool.store(unapproved).unwrap());
+        assert!(spool.store(approved).unwrap());
+        assert!(spool.store(free).unwrap());
+    }
+}
@@ -0,0 +1,1095 @@
+//! Minimal NNTP reader server ([RFC](https://datatracker.ietf.org/doc/html/rfc3977)) on top of the news spool
+//!
+//! Newsgroups are the node names of `~news` destinations, e.g., `dtn://de.hessen.darmstadt/~news` is
+//! served as `de.hessen.darmstadt` and `ipn:42.119` as `42`. Article numbers are the persistent numbers of the spool.
+//! Posted articles are turned into news bundles, handed to a [`Submitter`] and stored in the local spool.
+use super::keyring::Keyring;
+use super::moderation::Moderators;
+use super::spool::{Spool, SpoolError};
+use super::{
+    Control, DEFAULT_LIFETIME, NewsBuilder, NewsBundle, NewsBundleBuilder, NewsError, cancel_news,
+    newsgroup_eid, sender_eid,
+};
+use crate::rfc5322::{Message, format_date, parse_date};
+use bp7::dtntime::{DtnTime, DtnTimeHelpers, SECONDS1970_TO2K};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Right hand side of generated message IDs
+const MESSAGE_ID_DOMAIN: &str = "dtn7";
+
+#[derive(Error, Debug)]
+pub enum NntpError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("news error: {0}")]
+    News(#[from] NewsError),
+    #[error("spool error: {0}")]
+    Spool(#[from] SpoolError),
+    #[error("invalid article: {0}")]
+    InvalidArticle(String),
+    #[error("failed to submit article: {0}")]
+    Submit(String),
+}
+
+/// Hands posted articles to the DTN
+pub trait Submitter: Send + Sync {
+    fn submit(&self, news: &mut NewsBundle) -> Result<(), NntpError>;
+}
+
+#[cfg(feature = "client")]
+impl Submitter for crate::client::DtnClient {
+    fn submit(&self, news: &mut NewsBundle) -> Result<(), NntpError> {
+        self.insert_bundle(news.to_cbor())
+            .map_err(|err| NntpError::Submit(err.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Netnews message ID for a news message ID
+///
+/// Legacy articles use their bundle ID as message ID, so colons are escaped,
+/// e.g., `dtn://node1/sms-123-0` becomes `<dtn%3A//node1/sms-123-0@dtn7>`.
+pub fn to_message_id(mid: &str) -> String {
+    format!(
+        "<{}@{}>",
+        mid.replace('%', "%25").replace(':', "%3A"),
+        MESSAGE_ID_DOMAIN
+    )
+}
+
+/// News message ID for a netnews message ID created by [`to_message_id`]
+pub fn from_message_id(message_id: &str) -> Option<String> {
+    let local = message_id
+        .trim()
+        .strip_prefix('<')?
+        .strip_suffix('>')?
+        .strip_suffix(MESSAGE_ID_DOMAIN)?
+        .strip_suffix('@')?;
+    Some(local.replace("%3A", ":").replace("%25", "%"))
+}
+
+/// DTN time of an `Expires` header value, `None` if it is not a valid date
+pub fn parse_expires(expires: &str) -> Option<DtnTime> {
+    let unix = parse_date(expires)?;
+    Some(unix.saturating_sub(SECONDS1970_TO2K) * 1000)
+}
+
+/// Header field value with line breaks and other control characters replaced by spaces
+fn header_value(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_control() && c != '\t' { ' ' } else { c })
+        .collect()
+}
+
+/// Render a news bundle as netnews article ([RFC](https://datatracker.ietf.org/doc/html/rfc5536))
+pub fn to_article(news: &NewsBundle) -> Message {
+    let src = &news.bundle().primary.source;
+    let node = src.node().unwrap_or_default();
+    let mut article = Message::new()
+        .with_header("Path", "dtn7!not-for-mail")
+        .with_header("From", &format!("{} <{}@{}>", node, node, src.scheme()))
+        .with_header("Newsgroups", &header_value(&news.dst().unwrap_or_default()))
+        .with_header("Subject", &header_value(&news.topic()))
+        .with_header(
+            "Date",
+            &format_date(news.creation_timestamp().dtntime().unix()),
+        )
+        .with_header(
+            "Message-ID",
+            &header_value(&to_message_id(&news.message_id())),
+        );
+    if !news.references().is_empty() {
+        let references: Vec<String> = news
+            .references()
+            .iter()
+            .map(|mid| to_message_id(mid))
+            .collect();
+        article = article.with_header("References", &header_value(&references.join(" ")));
+    }
+    if !news.tags().is_empty() {
+        article = article.with_header("Keywords", &header_value(&news.tags().join(", ")));
+    }
+    if let Some(expires) = news.expires() {
+        article = article.with_header("Expires", &format_date(expires.unix()));
+    }
+    if let Some(approval) = news.approval() {
+        article = article.with_header("Approved", &approval.moderator.to_string());
+    }
+    article = article.with_header("X-DTN-Thread-ID", &news.tid().to_string());
+    article = article.with_header(
+        "X-DTN-Lifetime",
+        &news.bundle().primary.lifetime.as_secs().to_string(),
+    );
+    if let Some(kid) = news.key_id() {
+        article = article.with_header("X-DTN-Key-Id", &header_value(&kid));
+    }
+    match news.control() {
+        Some(Control::Cancel(mid)) => {
+            article = article.with_header(
+                "Control",
+                &header_value(&format!("cancel {}", to_message_id(&mid))),
+            )
+        }
+        Some(Control::Supersede(mid)) => {
+            article = article.with_header("Supersedes", &header_value(&to_message_id(&mid)))
+        }
+        None => {}
+    }
+    article.with_body(&news.msg())
+}
+
+#[derive(Default)]
+struct Session {
+    group: Option<String>,
+    /// Article numbers and bundle IDs of the selected group in ascending order
+    articles: Vec<(u64, String)>,
+    /// Index of the current article in `articles`
+    current: Option<usize>,
+}
+
+/// Callback receiving errors that do not stop the server
+pub type ErrorHandler = Arc<dyn Fn(&str) + Send + Sync>;
+
+#[derive(Clone)]
+pub struct NntpServer {
+    spool: Arc<Mutex<Spool>>,
+    submitter: Arc<dyn Submitter>,
+    node_name: String,
+    groups: Vec<String>,
+    compression: bool,
+    lifetime: Duration,
+    moderators: Moderators,
+    keyring: Option<Arc<Keyring>>,
+    on_error: Option<ErrorHandler>,
+}
+
+impl NntpServer {
+    /// Create a server posting as `node_name`
+    pub fn new(spool: Arc<Mutex<Spool>>, submitter: Arc<dyn Submitter>, node_name: &str) -> Self {
+        NntpServer {
+            spool,
+            submitter,
+            node_name: node_name.into(),
+            groups: Vec::new(),
+            compression: true,
+            lifetime: DEFAULT_LIFETIME,
+            moderators: Moderators::new(),
+            keyring: None,
+            on_error: None,
+        }
+    }
+    /// Newsgroups to list even if no article has been received yet
+    pub fn with_groups(mut self, groups: Vec<String>) -> Self {
+        self.groups = groups;
+        self
+    }
+    /// Compress posted articles
+    pub fn compression(mut self, comp: bool) -> Self {
+        self.compression = comp;
+        self
+    }
+    /// Bundle lifetime of posted articles, how long readers keep them is set by the `Expires` header
+    pub fn lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+    /// Moderators of newsgroups, posts to their groups are submitted to them for approval
+    pub fn moderators(mut self, moderators: Moderators) -> Self {
+        self.moderators = moderators;
+        self
+    }
+    /// Keys of private newsgroups, posts to groups with a key are encrypted
+    pub fn keyring(mut self, keyring: Keyring) -> Self {
+        self.keyring = Some(Arc::new(keyring));
+        self
+    }
+
+    /// Report errors that do not stop the server, e.g., failed sessions or undecodable bundles
+    pub fn on_error<F: Fn(&str) + Send + Sync + 'static>(mut self, handler: F) -> Self {
+        self.on_error = Some(Arc::new(handler));
+        self
+    }
+    fn report(&self, msg: &str) {
+        if let Some(handler) = &self.on_error {
+            handler(msg);
+        }
+    }
+
+    /// Plain copy of an article, encrypted ones need their group key in the keyring
+    fn decrypted(&self, news: &NewsBundle) -> Result<NewsBundle, NewsError> {
+        match (news.encryption(), &self.keyring) {
+            (false, _) => Ok(news.clone()),
+            (true, Some(keyring)) => news.decrypted(keyring),
+            (true, None) => Err(NewsError::Encrypted),
+        }
+    }
+    /// Encrypt a follow-up to an encrypted article with the current key of its group
+    fn seal(&self, payload: NewsBuilder, parent: &NewsBundle) -> Result<NewsBuilder, NewsError> {
+        if !parent.is_private() {
+            return Ok(payload);
+        }
+        let group = parent.newsgroup().unwrap_or_default();
+        match self
+            .keyring
+            .as_ref()
+            .and_then(|keyring| keyring.current(&group))
+        {
+            Some(key) => Ok(payload.encrypt(key)),
+            None => Err(NewsError::NoKey),
+        }
+    }
+
+    /// Accept connections forever, each client is served in its own thread
+    pub fn serve(&self, listener: TcpListener) -> Result<(), NntpError> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+            std::thread::spawn(move || {
+                if let Err(err) = server.handle(stream) {
+                    server.report(&format!("NNTP session failed: {}", err));
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Serve a single client connection until it quits
+    pub fn handle(&self, stream: TcpStream) -> Result<(), NntpError> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        let mut session = Session::default();
+
+        reply(&mut writer, "200 dtn7 news server ready, posting allowed")?;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let mut args = line.split_whitespace();
+            let Some(cmd) = args.next() else {
+                continue;
+            };
+            let args: Vec<&str> = args.collect();
+            match cmd.to_ascii_uppercase().as_str() {
+                "CAPABILITIES" => {
+                    reply(&mut writer, "101 Capability list:")?;
+                    multiline(
+                        &mut writer,
+                        &[
+                            "VERSION 2",
+                            "READER",
+                            "POST",
+                            "LIST ACTIVE NEWSGROUPS OVERVIEW.FMT",
+                            "OVER",
+                        ],
+                    )?;
+                }
+                "MODE" => reply(&mut writer, "200 posting allowed")?,
+                "LIST" => self.cmd_list(&mut writer, args.first().copied())?,
+                "GROUP" | "LISTGROUP" => {
+                    let listgroup = cmd.eq_ignore_ascii_case("LISTGROUP");
+                    let Some(group) = args
+                        .first()
+                        .map(|g| g.to_string())
+                        .or(session.group.clone())
+                    else {
+                        reply(&mut writer, "412 no newsgroup selected")?;
+                        continue;
+                    };
+                    if !self.group_names().contains(&group) {
+                        reply(&mut writer, "411 no such newsgroup")?;
+                        continue;
+                    }
+                    let (low, high) = self.select_group(&mut session, &group);
+                    reply(
+                        &mut writer,
+                        &format!("211 {} {} {} {}", session.articles.len(), low, high, group),
+                    )?;
+                    if listgroup {
+                        let numbers: Vec<String> = session
+                            .articles
+                            .iter()
+                            .map(|(n, _)| n.to_string())
+                            .collect();
+                        multiline(&mut writer, &numbers)?;
+                    }
+                }
+                "ARTICLE" | "HEAD" | "BODY" | "STAT" => {
+                    let (number, news) = match self.select_article(&mut session, args.first()) {
+                        Ok(selected) => selected,
+                        Err(response) => {
+                            reply(&mut writer, response)?;
+                            continue;
+                        }
+                    };
+                    let article = to_article(&news);
+                    let mid = to_message_id(&news.message_id());
+                    let head: Vec<String> = article
+                        .headers()
+                        .iter()
+                        .map(|(name, value)| format!("{}: {}", name, value))
+                        .collect();
+                    let body: Vec<&str> = article.body().lines().collect();
+                    match cmd.to_ascii_uppercase().as_str() {
+                        "ARTICLE" => {
+                            reply(&mut writer, &format!("220 {} {}", number, mid))?;
+                            let mut lines = head;
+                            lines.push(String::new());
+                            lines.extend(body.iter().map(|l| l.to_string()));
+                            multiline(&mut writer, &lines)?;
+                        }
+                        "HEAD" => {
+                            reply(&mut writer, &format!("221 {} {}", number, mid))?;
+                            multiline(&mut writer, &head)?;
+                        }
+                        "BODY" => {
+                            reply(&mut writer, &format!("222 {} {}", number, mid))?;
+                            multiline(&mut writer, &body)?;
+                        }
+                        _ => reply(&mut writer, &format!("223 {} {}", number, mid))?,
+                    }
+                }
+                "NEXT" | "LAST" => {
+                    if session.group.is_none() {
+                        reply(&mut writer, "412 no newsgroup selected")?;
+                        continue;
+                    }
+                    let Some(current) = session.current else {
+                        reply(&mut writer, "420 no current article selected")?;
+                        continue;
+                    };
+                    let next = if cmd.eq_ignore_ascii_case("NEXT") {
+                        Some(current + 1).filter(|n| *n < session.articles.len())
+                    } else {
+                        current.checked_sub(1)
+                    };
+                    match next {
+                        Some(n) => {
+                            let number = session.articles[n].0.to_string();
+                            match self.select_article(&mut session, Some(&number.as_str())) {
+                                Ok((number, news)) => {
+                                    let mid = to_message_id(&news.message_id());
+                                    reply(&mut writer, &format!("223 {} {}", number, mid))?
+                                }
+                                Err(response) => reply(&mut writer, response)?,
+                            }
+                        }
+                        None if cmd.eq_ignore_ascii_case("NEXT") => {
+                            reply(&mut writer, "421 no next article in this group")?
+                        }
+                        None => reply(&mut writer, "422 no previous article in this group")?,
+                    }
+                }
+                "OVER" | "XOVER" => self.cmd_over(&mut writer, &mut session, args.first())?,
+                "POST" => {
+                    reply(&mut writer, "340 send article to be posted")?;
+                    let article = Message::parse(&read_multiline(&mut reader)?);
+                    match self.post(&article) {
+                        Ok(news) => {
+                            reply(
+                                &mut writer,
+                                &format!(
+                                    "240 article received {}",
+                                    to_message_id(&news.message_id())
+                                ),
+                            )?;
+                        }
+                        Err(err) => reply(&mut writer, &format!("441 posting failed: {}", err))?,
+                    }
+                }
+                "QUIT" => {
+                    reply(&mut writer, "205 closing connection")?;
+                    return Ok(());
+                }
+                _ => reply(&mut writer, "500 unknown command")?,
+            }
+        }
+    }
+
+    /// Turn a posted article into a news bundle, submit and store it
+    ///
+    /// Follow-ups to known articles become replies, everything else starts a new thread.
+    /// Cancel control messages and `Supersedes` headers are accepted for articles posted by this node.
+    /// Posts to groups with a key in the keyring are encrypted with its current key, follow-ups
+    /// and corrections to encrypted articles fail without one.
+    pub fn post(&self, article: &Message) -> Result<NewsBundle, NntpError> {
+        let group = article
+            .header("Newsgroups")
+            .and_then(|groups| groups.split(',').map(str::trim).find(|g| !g.is_empty()))
+            .ok_or_else(|| NntpError::InvalidArticle("missing Newsgroups header".into()))?
+            .to_string();
+        let body = article.body().trim_end();
+        let parent = article
+            .header("References")
+            .and_then(|refs| refs.split_whitespace().last())
+            .and_then(from_message_id)
+            .and_then(|mid| self.spool.lock().unwrap().find_message(&mid).cloned());
+
+        let original = |mid: &str| {
+            from_message_id(mid)
+                .and_then(|mid| self.spool.lock().unwrap().find_message(&mid).cloned())
+                .ok_or_else(|| NntpError::InvalidArticle(format!("unknown article {}", mid)))
+        };
+
+        let mut news = if let Some(control) = article.header("Control") {
+            let mid = control.strip_prefix("cancel ").ok_or_else(|| {
+                NntpError::InvalidArticle(format!("unsupported control {}", control))
+            })?;
+            let original = original(mid)?;
+            let cancel = cancel_news(&original, &self.node_name)?;
+            if !cancel.controls(&original, None) {
+                return Err(NntpError::InvalidArticle("not the original author".into()));
+            }
+            cancel
+        } else if let Some(mid) = article.header("Supersedes") {
+            let original = original(mid)?;
+            let plain = self.decrypted(&original)?;
+            let payload = NewsBuilder::new()
+                .topic(&plain.topic())
+                .thread_id(plain.tid())
+                .references(plain.references())
+                .tags(plain.tags())
+                .control(Control::Supersede(original.message_id()));
+            let correction = NewsBundleBuilder::new()
+                .source(sender_eid(&self.node_name)?)
+                .destination(original.bundle().primary.destination.clone())
+                .lifetime(original.bundle().primary.lifetime)
+                .payload(
+                    self.seal(payload, &original)?
+                        .compression(self.compression)
+                        .message(body)
+                        .build()?,
+                )
+                .moderators(&self.moderators)
+                .build()?;
+            if !correction.controls(&original, None) {
+                return Err(NntpError::InvalidArticle("not the original author".into()));
+            }
+            correction
+        } else {
+            let (mut payload, dst) = match parent {
+                Some(parent) => (
+                    self.seal(
+                        NewsBuilder::new().reply_to(&self.decrypted(&parent)?),
+                        &parent,
+                    )?,
+                    parent.bundle().primary.destination.clone(),
+                ),
+                None => {
+                    let subject = article.header("Subject").ok_or_else(|| {
+                        NntpError::InvalidArticle("missing Subject header".into())
+                    })?;
+                    let tags = article
+                        .header("Keywords")
+                        .map(|keywords| {
+                            keywords
+                                .split(',')
+                                .map(|k| k.trim().to_string())
+                                .filter(|k| !k.is_empty())
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    let mut payload = NewsBuilder::new().topic(subject).tags(tags);
+                    // groups with a key in the keyring are private
+                    if let Some(key) = self.keyring.as_ref().and_then(|k| k.current(&group)) {
+                        payload = payload.encrypt(key);
+                    }
+                    (payload, newsgroup_eid(&group)?)
+                }
+            };
+            if let Some(expires) = article.header("Expires") {
+                let expires = parse_expires(expires).ok_or_else(|| {
+                    NntpError::InvalidArticle(format!("invalid Expires header {}", expires))
+                })?;
+                payload = payload.expires(expires);
+            }
+            NewsBundleBuilder::new()
+                .source(sender_eid(&self.node_name)?)
+                .destination(dst)
+                .lifetime(self.lifetime)
+                .payload(
+                    payload
+                        .compression(self.compression)
+                        .message(body)
+                        .build()?,
+                )
+                .moderators(&self.moderators)
+                .build()?
+        };
+        self.submitter.submit(&mut news)?;
+        self.spool.lock().unwrap().store(news.clone())?;
+        Ok(news)
+    }
+
+    /// Subscribe to all configured newsgroups on the local dtnd and store incoming articles in the spool
+    #[cfg(feature = "client")]
+    pub fn receive(&self, client: &crate::client::DtnClient) -> anyhow::Result<()> {
+        use super::digest::DigestBundle;
+        use crate::client::Message as WsMessage;
+
+        let endpoints: Vec<String> = self
+            .groups
+            .iter()
+            .map(|group| newsgroup_eid(group).map(|eid| eid.to_string()))
+            .collect::<Result<_, _>>()?;
+        for endpoint in &endpoints {
+            client.register_application_endpoint(endpoint)?;
+        }
+        let mut wscon = client.ws()?;
+        wscon.write_text("/bundle")?;
+        let msg = wscon.read_text()?;
+        if !msg.starts_with("200 tx mode: bundle") {
+            anyhow::bail!("[!] Failed to set mode to `bundle`");
+        }
+        for endpoint in &endpoints {
+            wscon.write_text(&format!("/subscribe {}", endpoint))?;
+            let msg = wscon.read_text()?;
+            if !msg.starts_with("200 subscribed") {
+                anyhow::bail!("[!] Failed to subscribe to {}", endpoint);
+            }
+        }
+
+        loop {
+            match wscon.read_message()? {
+                WsMessage::Binary(bin) => {
+                    let articles = match NewsBundle::try_from(bin.to_vec()) {
+                        Ok(news) => vec![news],
+                        Err(err) => match DigestBundle::try_from(bin.to_vec()) {
+                            Ok(digest) => digest.articles(),
+                            Err(_) => {
+                                self.report(&format!(
+                                    "Not a news bundle: {:#}",
+                                    anyhow::Error::from(err)
+                                ));
+                                continue;
+                            }
+                        },
+                    };
+                    for news in articles {
+                        let id = news.id();
+                        if let Err(err) = self.spool.lock().unwrap().store(news) {
+                            self.report(&format!("Failed to store {}: {}", id, err));
+                        }
+                    }
+                }
+                WsMessage::Close(_) => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn group_names(&self) -> Vec<String> {
+        let mut groups = self.spool.lock().unwrap().groups();
+        groups.extend(self.groups.iter().cloned());
+        groups.sort();
+        groups.dedup();
+        groups
+    }
+
+    /// Select a group, returns its low and high water marks
+    ///
+    /// Encrypted articles are only listed if the keyring has their group key.
+    fn select_group(&self, session: &mut Session, group: &str) -> (u64, u64) {
+        let keyring = self.keyring.as_deref();
+        let spool = self.spool.lock().unwrap();
+        session.articles = spool
+            .numbered(group)
+            .into_iter()
+            .filter(|(_, news)| news.readable(keyring).is_some())
+            .map(|(number, news)| (number, news.id()))
+            .collect();
+        session.current = if session.articles.is_empty() {
+            None
+        } else {
+            Some(0)
+        };
+        session.group = Some(group.into());
+        water_marks(&spool, group)
+    }
+
+    /// Resolve an article argument, returns the article number (0 for message IDs) or an error response
+    ///
+    /// Encrypted articles are returned decrypted, those without a key in the keyring are not found.
+    fn select_article(
+        &self,
+        session: &mut Session,
+        arg: Option<&&str>,
+    ) -> Result<(u64, NewsBundle), &'static str> {
+        let spool = self.spool.lock().unwrap();
+        let keyring = self.keyring.as_deref();
+        if let Some(mid) = arg.filter(|a| a.starts_with('<')) {
+            return from_message_id(mid)
+                .and_then(|mid| spool.find_message(&mid))
+                .and_then(|news| news.readable(keyring))
+                .map(|news| (0, news))
+                .ok_or("430 no article with that message-id");
+        }
+        let group = session
+            .group
+            .as_deref()
+            .ok_or("412 no newsgroup selected")?;
+        let index = match arg {
+            Some(number) => {
+                let index = number
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(|n| {
+                        session
+                            .articles
+                            .binary_search_by_key(&n, |(number, _)| *number)
+                            .ok()
+                    })
+                    .ok_or("423 no article with that number")?;
+                session.current = Some(index);
+                index
+            }
+            None => session.current.ok_or("420 no current article selected")?,
+        };
+        let (number, id) = &session.articles[index];
+        spool
+            .get(group, id)
+            .and_then(|news| news.readable(keyring))
+            .map(|news| (*number, news))
+            .ok_or("423 article has expired")
+    }
+
+    fn cmd_list<W: Write>(&self, writer: &mut W, keyword: Option<&str>) -> Result<(), NntpError> {
+        let keyword = keyword.unwrap_or("ACTIVE").to_ascii_uppercase();
+        let groups = self.group_names();
+        let lines: Vec<String> = match keyword.as_str() {
+            "ACTIVE" => {
+                let spool = self.spool.lock().unwrap();
+                groups
+                    .iter()
+                    .map(|group| {
+                        let (low, high) = water_marks(&spool, group);
+                        // posts to moderated groups are accepted but forwarded to the moderator
+                        let status = if self.moderators.is_moderated(group) {
+                            "m"
+                        } else {
+                            "y"
+                        };
+                        format!("{} {} {} {}", group, high, low, status)
+                    })
+                    .collect()
+            }
+            "NEWSGROUPS" => groups
+                .iter()
+                .map(|group| format!("{}\tdtn://{}/~news", group, group))
+                .collect(),
+            "OVERVIEW.FMT" => [
+                "Subject:",
+                "From:",
+                "Date:",
+                "Message-ID:",
+                "References:",
+                ":bytes",
+                ":lines",
+            ]
+            .iter()
+            .map(|l| l.to_string())
+            .collect(),
+            _ => return reply(writer, "501 unsupported list keyword"),
+        };
+        reply(writer, "215 information follows")?;
+        multiline(writer, &lines)
+    }
+
+    fn cmd_over<W: Write>(
+        &self,
+        writer: &mut W,
+        session: &mut Session,
+        range: Option<&&str>,
+    ) -> Result<(), NntpError> {
+        if session.group.is_none() {
+            return reply(writer, "412 no newsgroup selected");
+        }
+        let (low, high) = match range {
+            None => match session.current {
+                Some(i) => (session.articles[i].0, session.articles[i].0),
+                None => return reply(writer, "420 no current article selected"),
+            },
+            Some(range) => {
+                let (low, high) = range.split_once('-').unwrap_or((range, range));
+                let low = low.parse::<u64>().unwrap_or(1);
+                let high = if high.is_empty() {
+                    u64::MAX
+                } else {
+                    high.parse::<u64>().unwrap_or(u64::MAX)
+                };
+                (low, high)
+            }
+        };
+        let numbers: Vec<u64> = session
+            .articles
+            .iter()
+            .map(|(number, _)| *number)
+            .filter(|number| (low..=high).contains(number))
+            .collect();
+        if numbers.is_empty() {
+            return reply(writer, "423 no articles in that range");
+        }
+        let mut lines = Vec::new();
+        for number in numbers {
+            let Ok((_, news)) = self.select_article(session, Some(&number.to_string().as_str()))
+            else {
+                continue;
+            };
+            let article = to_article(&news);
+            let field = |name: &str| article.header(name).unwrap_or_default().replace('\t', " ");
+            lines.push(format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                number,
+                field("Subject"),
+                field("From"),
+                field("Date"),
+                field("Message-ID"),
+                field("References"),
+                article.to_string().len(),
+                article.body().lines().count()
+            ));
+        }
+        reply(writer, "224 overview information follows")?;
+        multiline(writer, &lines)
+    }
+}
+
+/// Low and high water marks of a group, the low water mark exceeds the high one if it is empty
+fn water_marks(spool: &Spool, group: &str) -> (u64, u64) {
+    let high = spool.high_water(group);
+    let low = spool
+        .numbered(group)
+        .first()
+        .map(|(number, _)| *number)
+        .unwrap_or(high + 1);
+    (low, high)
+}
+
+fn reply<W: Write>(writer: &mut W, line: &str) -> Result<(), NntpError> {
+    write!(writer, "{}\r\n", line)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write a dot-stuffed multi-line data block including the terminating dot
+fn multiline<W: Write, S: AsRef<str>>(writer: &mut W, lines: &[S]) -> Result<(), NntpError> {
+    for line in lines {
+        let line = line.as_ref();
+        if line.starts_with('.') {
+            write!(writer, ".")?;
+        }
+        write!(writer, "{}\r\n", line)?;
+    }
+    reply(writer, ".")
+}
+
+/// Read a dot-stuffed multi-line data block up to the terminating dot
+fn read_multiline<R: BufRead>(reader: &mut R) -> Result<String, NntpError> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(NntpError::InvalidArticle("unterminated article".into()));
+        }
+        let content = line.trim_end_matches(['\r', '\n']);
+        if content == "." {
+            break;
+        }
+        lines.push(content.strip_prefix('.').unwrap_or(content).to_string());
+    }
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        NntpError, NntpServer, Session, Submitter, from_message_id, to_article, to_message_id,
+    };
+    use crate::news::keyring::Keyring;
+    use crate::news::spool::Spool;
+    use crate::news::{NewsBuilder, NewsBundle, NewsBundleBuilder, NewsError, sender_eid};
+    use crate::rfc5322::Message;
+    use bp7::dtntime::DtnTimeHelpers;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Outbox(Mutex<Vec<NewsBundle>>);
+
+    impl Submitter for Outbox {
+        fn submit(&self, news: &mut NewsBundle) -> Result<(), NntpError> {
+            self.0.lock().unwrap().push(news.clone());
+            Ok(())
+        }
+    }
+
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Client {
+        fn command(&mut self, cmd: &str) -> String {
+            write!(self.writer, "{}\r\n", cmd).unwrap();
+            self.line()
+        }
+        fn line(&mut self) -> String {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            line.trim_end().to_string()
+        }
+        fn block(&mut self) -> Vec<String> {
+            let mut lines = Vec::new();
+            loop {
+                let line = self.line();
+                if line == "." {
+                    return lines;
+                }
+                lines.push(line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_message_id_roundtrip() {
+        for id in [
+            "dtn://node1/sms-123-0",
+            "ipn:23.767-42-1",
+            "b0c4c4a6-6d0c-4c39-9a6f-5c9e2a1f3d7e",
+        ] {
+            let mid = to_message_id(id);
+            assert!(!mid.contains(':'));
+            assert_eq!(from_message_id(&mid).unwrap(), id);
+        }
+        assert_eq!(from_message_id("<foo@example.org>"), None);
+    }
+
+    #[test]
+    fn test_article_header_injection() {
+        let bndl = NewsBundleBuilder::new()
+            .source(sender_eid("node1").unwrap())
+            .newsgroup("dtn.test")
+            .unwrap()
+            .payload(
+                NewsBuilder::new()
+                    .topic("Hello\r\nControl: cancel <victim@dtn7>")
+                    .tags(vec!["a\nNewsgroups: dtn.evil".into(), "b".into()])
+                    .message("body")
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+        let article = Message::parse(&to_article(&bndl).to_string());
+        assert_eq!(
+            article.header("Subject").unwrap(),
+            "Hello  Control: cancel <victim@dtn7>"
+        );
+        assert_eq!(
+            article.header("Keywords").unwrap(),
+            "a Newsgroups: dtn.evil, b"
+        );
+        assert!(article.header("Control").is_none());
+        assert_eq!(article.header("Newsgroups").unwrap(), "dtn.test");
+    }
+
+    #[test]
+    fn test_nntp_post_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Arc::new(Mutex::new(Spool::open(dir.path()).unwrap()));
+        let mut keyring = Keyring::new();
+        let key = keyring.rotate("de.private").clone();
+        let parent = NewsBundleBuilder::new()
+            .source(sender_eid("node1").unwrap())
+            .newsgroup("de.private")
+            .unwrap()
+            .payload(
+                NewsBuilder::new()
+                    .topic("secret")
+                    .message("m")
+                    .encrypt(&key)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+        spool.lock().unwrap().store(parent.clone()).unwrap();
+        let follow_up = Message::parse(&format!(
+            "Newsgroups: de.private\r\nReferences: {}\r\n\r\nreply\r\n",
+            to_message_id(&parent.message_id())
+        ));
+        let correction = Message::parse(&format!(
+            "Newsgroups: de.private\r\nSupersedes: {}\r\n\r\ncorrected\r\n",
+            to_message_id(&parent.message_id())
+        ));
+
+        let server = NntpServer::new(spool.clone(), Arc::new(Outbox::default()), "node1");
+        for article in [&follow_up, &correction] {
+            assert!(matches!(
+                server.post(article),
+                Err(NntpError::News(NewsError::Encrypted))
+            ));
+        }
+        let without_key = server.clone().keyring(Keyring::new());
+        assert!(without_key.post(&follow_up).is_err());
+
+        let server = server.keyring(keyring.clone());
+        let reply = server.post(&follow_up).unwrap();
+        assert!(reply.encryption());
+        assert_eq!(reply.key_id().as_deref(), Some(key.id()));
+        let plain = reply.decrypted(&keyring).unwrap();
+        assert_eq!(plain.topic(), "secret");
+        assert_eq!(plain.references(), vec![parent.message_id()]);
+
+        // readers only see articles they have the key for, decrypted
+        let mid = to_message_id(&reply.message_id());
+        let mut session = Session::default();
+        without_key.select_group(&mut session, "de.private");
+        assert!(session.articles.is_empty());
+        assert!(
+            without_key
+                .select_article(&mut session, Some(&mid.as_str()))
+                .is_err()
+        );
+        server.select_group(&mut session, "de.private");
+        assert_eq!(session.articles.len(), 2);
+        let (_, article) = server
+            .select_article(&mut session, Some(&mid.as_str()))
+            .unwrap();
+        assert_eq!(article.msg(), "reply");
+
+        let corrected = server.post(&correction).unwrap();
+        assert!(corrected.encryption());
+        assert_eq!(corrected.decrypted(&keyring).unwrap().msg(), "corrected");
+
+        let post = server
+            .post(&Message::parse(
+                "Newsgroups: de.private\r\nSubject: new\r\n\r\nbody\r\n",
+            ))
+            .unwrap();
+        assert!(post.encryption());
+    }
+
+    #[test]
+    fn test_nntp_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Arc::new(Mutex::new(Spool::open(dir.path()).unwrap()));
+        let outbox = Arc::new(Outbox::default());
+        let server = NntpServer::new(spool.clone(), outbox.clone(), "node1")
+            .with_groups(vec!["de.test".into()]);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || server.serve(listener));
+
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut client = Client {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+        };
+        assert!(client.line().starts_with("200"));
+        assert!(client.command("MODE READER").starts_with("200"));
+        assert!(client.command("LIST").starts_with("215"));
+        assert_eq!(client.block(), vec!["de.test 0 1 y"]);
+
+        assert!(client.command("POST").starts_with("340"));
+        let posted = client.command(
+            "Newsgroups: de.test\r\nSubject: Hello\r\nKeywords: a, b\r\n\r\nfirst line\r\n..dotted\r\n.",
+        );
+        assert!(posted.starts_with("240"), "{}", posted);
+        let parent_mid = posted.split_whitespace().last().unwrap().to_string();
+
+        assert!(client.command("POST").starts_with("340"));
+        let reply = client.command(&format!(
+            "Newsgroups: de.test\r\nSubject: Re: Hello\r\nReferences: {}\r\nExpires: Sun, 18 Oct 2026 13:44:14 +0000\r\n\r\nreply\r\n.",
+            parent_mid
+        ));
+        assert!(reply.starts_with("240"), "{}", reply);
+
+        {
+            let outbox = outbox.0.lock().unwrap();
+            assert_eq!(outbox.len(), 2);
+            assert_eq!(outbox[0].topic(), "Hello");
+            assert_eq!(outbox[0].msg(), "first line\n.dotted");
+            assert_eq!(outbox[0].tags(), vec!["a", "b"]);
+            assert_eq!(outbox[1].references(), vec![outbox[0].message_id()]);
+            assert_eq!(outbox[1].tid(), outbox[0].tid());
+            assert_eq!(outbox[0].expires(), None);
+            assert_eq!(
+                outbox[1].expires().map(|exp| exp.unix()),
+                Some(1_792_331_054)
+            );
+        }
+
+        assert_eq!(client.command("GROUP de.test"), "211 2 1 2 de.test");
+        assert_eq!(client.command("STAT"), format!("223 1 {}", parent_mid));
+        assert!(client.command("NEXT").starts_with("223 2 "));
+        assert!(client.command("NEXT").starts_with("421"));
+        assert!(client.command("LAST").starts_with("223 1 "));
+
+        assert!(client.command("ARTICLE 1").starts_with("220 1"));
+        let article = client.block();
+        assert!(article.contains(&"Subject: Hello".to_string()));
+        assert!(article.contains(&"Newsgroups: de.test".to_string()));
+        assert!(article.contains(&"..dotted".to_string()));
+
+        assert!(client.command("HEAD 2").starts_with("221 2"));
+        let head = client.block();
+        assert!(head.contains(&format!("References: {}", parent_mid)));
+        assert!(head.contains(&"Expires: Sun, 18 Oct 2026 13:44:14 +0000".to_string()));
+        assert!(
+            client
+                .command(&format!("BODY {}", parent_mid))
+                .starts_with("222 0")
+        );
+        assert_eq!(client.block(), vec!["first line", "..dotted"]);
+
+        assert!(client.command("OVER 1-").starts_with("224"));
+        assert_eq!(client.block().len(), 2);
+
+        assert!(client.command("POST").starts_with("340"));
+        let superseded = client.command(&format!(
+            "Newsgroups: de.test\r\nSubject: Hello\r\nSupersedes: {}\r\n\r\ncorrected\r\n.",
+            parent_mid
+        ));
+        assert!(superseded.starts_with("240"), "{}", superseded);
+        assert!(
+            client
+                .command(&format!("STAT {}", parent_mid))
+                .starts_with("430")
+        );
+        let correction_mid = superseded.split_whitespace().last().unwrap().to_string();
+        assert!(client.command("POST").starts_with("340"));
+        let cancelled = client.command(&format!(
+            "Newsgroups: de.test\r\nSubject: cmsg cancel\r\nControl: cancel {}\r\n\r\n.",
+            correction_mid
+        ));
+        assert!(cancelled.starts_with("240"), "{}", cancelled);
+        // numbers of removed articles are not reused, the cancel message itself is not listed
+        assert_eq!(client.command("GROUP de.test"), "211 1 2 4 de.test");
+        assert!(client.command("LISTGROUP").starts_with("211"));
+        assert_eq!(client.block(), vec!["2"]);
+        assert!(client.command("LIST ACTIVE").starts_with("215"));
+        assert_eq!(client.block(), vec!["de.test 4 2 y"]);
+        assert!(client.command("ARTICLE 1").starts_with("423"));
+        assert!(client.command("ARTICLE 3").starts_with("423"));
+        assert!(client.command("HEAD 2").starts_with("221 2"));
+        client.block();
+        let reopened = Spool::open(dir.path()).unwrap();
+        assert_eq!(reopened.high_water("de.test"), 4);
+        assert_eq!(reopened.numbered("de.test")[0].0, 2);
+
+        assert!(client.command("GROUP de.nope").starts_with("411"));
+        assert!(client.command("FOO").starts_with("500"));
+        assert!(client.command("QUIT").starts_with("205"));
+    }
+}
@@ -0,0 +1,929 @@
+//! Persistent storage of received news bundles
+//!
+//! Articles are stored as raw bundles below one directory per newsgroup:
+//!
+//! ```text
+//! <root>/<newsgroup>/<sanitized bundle id>.bundle
+//! <root>/<newsgroup>/numbers
+//! ```
+//!
+//! The `numbers` file lists the article number of each bundle as `<number> <bundle id>` lines.
+//! Numbers are assigned in arrival order and never reused, a line holding only a number records
+//! the high-water mark of a group whose newest article has been removed.
+//!
+//! Keeping an article for readers is independent of its bundle lifetime: articles are retained
+//! until their `Expires` time, or for the spool retention if they have none. Only articles
+//! within their bundle lifetime are handed to the network again, see [`Spool::in_network`].
+use super::moderation::{ApprovalVerifier, Moderators};
+use super::{Control, NewsBundle, NewsError, SignatureVerifier};
+use bp7::dtntime::{DtnTime, dtn_time_now};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+const BUNDLE_EXTENSION: &str = "bundle";
+const NUMBERS_FILE: &str = "numbers";
+
+#[derive(Error, Debug)]
+pub enum SpoolError {
+    #[error("spool io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid article in spool: {0}")]
+    News(#[from] NewsError),
+    #[error("invalid newsgroup name: {0}")]
+    InvalidGroup(String),
+}
+
+/// A post together with all direct replies, i.e., articles whose last reference is its message ID
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreadNode<'a> {
+    pub article: &'a NewsBundle,
+    pub replies: Vec<ThreadNode<'a>>,
+}
+
+impl<'a> ThreadNode<'a> {
+    /// Newest article in this subtree
+    pub fn latest_article(&self) -> &'a NewsBundle {
+        self.replies
+            .iter()
+            .map(|r| r.latest_article())
+            .chain(std::iter::once(self.article))
+            .max_by_key(|news| sort_key(news))
+            .unwrap_or(self.article)
+    }
+    /// Creation time of the newest article in this subtree
+    pub fn latest_activity(&self) -> DtnTime {
+        self.latest_article().creation_timestamp().dtntime()
+    }
+    /// All articles of this subtree, parents before their replies
+    pub fn articles(&self) -> Vec<&'a NewsBundle> {
+        let mut articles = vec![self.article];
+        for reply in &self.replies {
+            articles.extend(reply.articles());
+        }
+        articles
+    }
+    /// Number of articles in this subtree
+    pub fn len(&self) -> usize {
+        1 + self.replies.iter().map(|r| r.len()).sum::<usize>()
+    }
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+/// All articles of a newsgroup sharing the same thread ID
+///
+/// Usually there is a single root, replies whose parent is unknown or expired become additional roots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thread<'a> {
+    pub tid: Uuid,
+    pub roots: Vec<ThreadNode<'a>>,
+}
+
+impl<'a> Thread<'a> {
+    pub fn latest_article(&self) -> Option<&'a NewsBundle> {
+        self.roots
+            .iter()
+            .map(|r| r.latest_article())
+            .max_by_key(|news| sort_key(news))
+    }
+    pub fn latest_activity(&self) -> DtnTime {
+        self.latest_article()
+            .map(|news| news.creation_timestamp().dtntime())
+            .unwrap_or(0)
+    }
+    /// All articles of the thread, parents before their replies
+    pub fn articles(&self) -> Vec<&'a NewsBundle> {
+        self.roots.iter().flat_map(|r| r.articles()).collect()
+    }
+    pub fn len(&self) -> usize {
+        self.roots.iter().map(|r| r.len()).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+}
+
+/// Article numbers of a newsgroup
+#[derive(Debug, Default)]
+struct Numbering {
+    high: u64,
+    ids: HashMap<String, u64>,
+}
+
+impl Numbering {
+    /// Load the numbers of a group directory, numbering articles that have none yet
+    ///
+    /// Lines of removed articles are dropped by rewriting the file.
+    fn load(dir: &Path, articles: &HashMap<String, NewsBundle>) -> Result<Numbering, SpoolError> {
+        let path = dir.join(NUMBERS_FILE);
+        let contents = match fs::read_to_string(&path) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            contents => contents?,
+        };
+        let mut numbering = Numbering::default();
+        let mut stale = false;
+        for line in contents.lines() {
+            let (number, id) = line.split_once(' ').unwrap_or((line, ""));
+            let Ok(number) = number.parse::<u64>() else {
+                stale = true;
+                continue;
+            };
+            numbering.high = numbering.high.max(number);
+            if articles.contains_key(id) && !numbering.ids.contains_key(id) {
+                numbering.ids.insert(id.to_string(), number);
+            } else {
+                stale = true;
+            }
+        }
+        let mut unnumbered: Vec<&NewsBundle> = articles
+            .values()
+            .filter(|news| !numbering.ids.contains_key(&news.id()))
+            .collect();
+        unnumbered.sort_by_key(|news| sort_key(news));
+        for news in &unnumbered {
+            numbering.high += 1;
+            numbering.ids.insert(news.id(), numbering.high);
+        }
+        if stale || !unnumbered.is_empty() {
+            let mut entries: Vec<(&u64, &String)> =
+                numbering.ids.iter().map(|(id, n)| (n, id)).collect();
+            entries.sort();
+            let mut contents: String = entries
+                .iter()
+                .map(|(n, id)| format!("{} {}\n", n, id))
+                .collect();
+            if entries.last().is_none_or(|(n, _)| **n < numbering.high) {
+                contents.push_str(&format!("{}\n", numbering.high));
+            }
+            let tmp = dir.join(format!("{}.tmp", NUMBERS_FILE));
+            fs::write(&tmp, contents)?;
+            fs::rename(&tmp, &path)?;
+        }
+        Ok(numbering)
+    }
+}
+
+/// On-disk article store
+///
+/// Cancel and supersede control messages are applied when the sender is the original author
+/// or their signature passes the configured [`SignatureVerifier`]. Control messages are kept,
+/// so that articles arriving after their cancellation are rejected as well.
+pub struct Spool {
+    root: PathBuf,
+    groups: BTreeMap<String, HashMap<String, NewsBundle>>,
+    numbers: HashMap<String, Numbering>,
+    verifier: Option<Arc<dyn SignatureVerifier>>,
+    retention: Option<Duration>,
+    moderation: Option<(Moderators, Arc<dyn ApprovalVerifier>)>,
+    skipped: Vec<(PathBuf, SpoolError)>,
+}
+
+impl Spool {
+    /// Open a spool directory, creating it if necessary, and load all stored articles
+    ///
+    /// Files that cannot be read or decoded, e.g., truncated by a crash, are skipped and
+    /// reported by [`Spool::skipped`].
+    pub fn open<P: AsRef<Path>>(root: P) -> Result<Spool, SpoolError> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(&root)?;
+        let mut groups = BTreeMap::new();
+        let mut numbers = HashMap::new();
+        let mut skipped = Vec::new();
+        for group_dir in fs::read_dir(&root)? {
+            let group_dir = group_dir?;
+            if !group_dir.file_type()?.is_dir() {
+                continue;
+            }
+            let group = group_dir.file_name().to_string_lossy().to_string();
+            let mut articles = HashMap::new();
+            for entry in fs::read_dir(group_dir.path())? {
+                let path = entry?.path();
+                if path.extension().is_none_or(|ext| ext != BUNDLE_EXTENSION) {
+                    continue;
+                }
+                match fs::read(&path)
+                    .map_err(SpoolError::from)
+                    .and_then(|buf| Ok(NewsBundle::try_from(buf)?))
+                {
+                    Ok(news) => {
+                        articles.insert(news.id(), news);
+                    }
+                    Err(err) => skipped.push((path, err)),
+                }
+            }
+            numbers.insert(
+                group.clone(),
+                Numbering::load(&group_dir.path(), &articles)?,
+            );
+            groups.insert(group, articles);
+        }
+        Ok(Spool {
+            root,
+            groups,
+            numbers,
+            verifier: None,
+            retention: None,
+            moderation: None,
+            skipped,
+        })
+    }
+
+    /// Files skipped by [`Spool::open`] together with the reason they could not be loaded
+    pub fn skipped(&self) -> &[(PathBuf, SpoolError)] {
+        &self.skipped
+    }
+
+    /// Verify signed control messages
+    pub fn with_verifier(mut self, verifier: Arc<dyn SignatureVerifier>) -> Self {
+        self.verifier = Some(verifier);
+        self
+    }
+
+    /// Keep articles without `Expires` time for this long after creation instead of their bundle lifetime
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Reject posts to moderated groups without a valid approval
+    pub fn with_moderation(
+        mut self,
+        moderators: Moderators,
+        verifier: Arc<dyn ApprovalVerifier>,
+    ) -> Self {
+        self.moderation = Some((moderators, verifier));
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Store an article, returns `false` if it or another article with its message ID was already
+    /// present, has been cancelled or superseded or lacks the approval for a moderated group
+    ///
+    /// Cancels and supersedes of a stored article are rejected unless their sender may control it.
+    pub fn store(&mut self, mut news: NewsBundle) -> Result<bool, SpoolError> {
+        if let Some((moderators, verifier)) = &self.moderation
+            && !moderators.accepts(&news, verifier.as_ref())
+        {
+            return Ok(false);
+        }
+        let group = news.dst().unwrap_or_default();
+        if group.is_empty() || group.starts_with('.') || group.contains(['/', '\\']) {
+            return Err(SpoolError::InvalidGroup(group));
+        }
+        let id = news.id();
+        if self.get(&group, &id).is_some() || self.find_message(&news.message_id()).is_some() {
+            return Ok(false);
+        }
+        let verifier = self.verifier.as_deref();
+        let mut articles = self.groups.get(&group).into_iter().flat_map(|a| a.values());
+        if articles.any(|control| control.controls(&news, verifier)) {
+            return Ok(false);
+        }
+        let (targets, forged): (Vec<&NewsBundle>, Vec<&NewsBundle>) = self
+            .groups
+            .get(&group)
+            .into_iter()
+            .flat_map(|articles| articles.values())
+            .filter(|original| {
+                news.control()
+                    .is_some_and(|control| control.target() == original.message_id())
+            })
+            .partition(|original| news.controls(original, verifier));
+        if !forged.is_empty() {
+            return Ok(false);
+        }
+        let targets: Vec<String> = targets.iter().map(|original| original.id()).collect();
+        for target in targets {
+            self.remove(&group, &target)?;
+        }
+
+        let dir = self.root.join(&group);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(file_name(&id)), news.to_cbor())?;
+        let numbering = self.numbers.entry(group.clone()).or_default();
+        let number = numbering.high + 1;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(NUMBERS_FILE))?;
+        writeln!(file, "{} {}", number, id)?;
+        numbering.high = number;
+        numbering.ids.insert(id.clone(), number);
+        self.groups.entry(group).or_default().insert(id, news);
+        Ok(true)
+    }
+
+    fn remove(&mut self, group: &str, id: &str) -> Result<(), SpoolError> {
+        match fs::remove_file(self.root.join(group).join(file_name(id))) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+        if let Some(articles) = self.groups.get_mut(group) {
+            articles.remove(id);
+        }
+        if let Some(numbering) = self.numbers.get_mut(group) {
+            numbering.ids.remove(id);
+        }
+        Ok(())
+    }
+
+    /// Names of all newsgroups with stored articles
+    pub fn groups(&self) -> Vec<String> {
+        self.groups
+            .iter()
+            .filter(|(_, articles)| !articles.is_empty())
+            .map(|(group, _)| group.clone())
+            .collect()
+    }
+
+    pub fn get(&self, group: &str, id: &str) -> Option<&NewsBundle> {
+        self.groups.get(group).and_then(|articles| articles.get(id))
+    }
+
+    /// Look up an article by bundle ID in all newsgroups
+    pub fn find(&self, id: &str) -> Option<&NewsBundle> {
+        self.groups.values().find_map(|articles| articles.get(id))
+    }
+
+    /// Look up an article by message ID in all newsgroups
+    pub fn find_message(&self, mid: &str) -> Option<&NewsBundle> {
+        self.groups
+            .values()
+            .flat_map(|articles| articles.values())
+            .find(|news| news.message_id() == mid)
+    }
+
+    /// All articles of a newsgroup, oldest first, cancel messages are omitted
+    pub fn articles(&self, group: &str) -> Vec<&NewsBundle> {
+        let mut articles: Vec<&NewsBundle> = self
+            .groups
+            .get(group)
+            .map(|articles| {
+                articles
+                    .values()
+                    .filter(|news| !matches!(news.control(), Some(Control::Cancel(_))))
+                    .collect()
+            })
+            .unwrap_or_default();
+        articles.sort_by_key(|news| sort_key(news));
+        articles
+    }
+
+    /// Article numbers and articles of a newsgroup in ascending order, cancel messages are omitted
+    ///
+    /// Numbers stay the same across restarts, new arrivals always get a higher number.
+    pub fn numbered(&self, group: &str) -> Vec<(u64, &NewsBundle)> {
+        let Some(numbering) = self.numbers.get(group) else {
+            return Vec::new();
+        };
+        let mut articles: Vec<(u64, &NewsBundle)> = self
+            .articles(group)
+            .into_iter()
+            .filter_map(|news| Some((*numbering.ids.get(&news.id())?, news)))
+            .collect();
+        articles.sort_by_key(|(number, _)| *number);
+        articles
+    }
+
+    /// Highest article number ever assigned in a newsgroup, 0 if it never had articles
+    pub fn high_water(&self, group: &str) -> u64 {
+        self.numbers.get(group).map(|n| n.high).unwrap_or(0)
+    }
+
+    /// All stored bundles of a newsgroup including control messages, oldest first
+    pub fn bundles(&self, group: &str) -> Vec<&NewsBundle> {
+        let mut bundles: Vec<&NewsBundle> = self
+            .groups
+            .get(group)
+            .map(|articles| articles.values().collect())
+            .unwrap_or_default();
+        bundles.sort_by_key(|news| sort_key(news));
+        bundles
+    }
+
+    /// Bundles of a newsgroup whose bundle lifetime has not passed at the given time, oldest first
+    pub fn in_network(&self, group: &str, now: DtnTime) -> Vec<&NewsBundle> {
+        self.bundles(group)
+            .into_iter()
+            .filter(|news| news.lifetime_end() > now)
+            .collect()
+    }
+
+    /// Time until which readers keep an article, `None` if its age is unknown
+    ///
+    /// This is the `Expires` time of the article, or its creation time plus the spool retention
+    /// or, if no retention is configured, plus the bundle lifetime.
+    pub fn keep_until(&self, news: &NewsBundle) -> Option<DtnTime> {
+        if let Some(expires) = news.expires() {
+            return Some(expires);
+        }
+        let created = news.creation_timestamp().dtntime();
+        if created == 0 {
+            return None;
+        }
+        Some(match self.retention {
+            Some(retention) => created.saturating_add(retention.as_millis() as u64),
+            None => news.lifetime_end(),
+        })
+    }
+
+    /// Reply trees of a newsgroup, most recently active thread first
+    pub fn threads(&self, group: &str) -> Vec<Thread<'_>> {
+        let articles = self.articles(group);
+        let mut by_tid: HashMap<Uuid, Vec<&NewsBundle>> = HashMap::new();
+        for news in &articles {
+            by_tid.entry(news.tid()).or_default().push(news);
+        }
+        let mut threads: Vec<Thread> = by_tid
+            .into_iter()
+            .map(|(tid, posts)| {
+                // replies to a superseded article belong to its replacement, unless the supersede
+                // arrived before an original it may not control
+                let verifier = self.verifier.as_deref();
+                let superseded: HashMap<String, String> = posts
+                    .iter()
+                    .filter_map(|news| match news.control() {
+                        Some(Control::Supersede(mid)) => Some((mid, news)),
+                        _ => None,
+                    })
+                    .filter(|(mid, news)| {
+                        articles
+                            .iter()
+                            .filter(|original| &original.message_id() == mid)
+                            .all(|original| news.controls(original, verifier))
+                    })
+                    .map(|(mid, news)| (mid, news.message_id()))
+                    .collect();
+                let mut visited = HashSet::new();
+                let mut roots: Vec<ThreadNode> = posts
+                    .iter()
+                    .filter(|news| {
+                        parent(news, &superseded)
+                            .is_none_or(|parent| !posts.iter().any(|p| p.message_id() == parent))
+                    })
+                    .map(|news| thread_node(news, &posts, &superseded, &mut visited))
+                    .collect();
+                // articles on a reference cycle have no root, break the cycle at the oldest one
+                while let Some(news) = posts.iter().find(|news| !visited.contains(&news.id())) {
+                    roots.push(thread_node(news, &posts, &superseded, &mut visited));
+                }
+                Thread { tid, roots }
+            })
+            .collect();
+        threads.sort_by_key(|thread| std::cmp::Reverse(thread.latest_article().map(sort_key)));
+        threads
+    }
+
+    /// Remove all articles readers no longer need to keep
+    pub fn expire(&mut self) -> Result<Vec<String>, SpoolError> {
+        self.expire_at(dtn_time_now())
+    }
+
+    /// Remove all articles kept until before the given time, returns the removed bundle IDs
+    ///
+    /// Articles without a creation time or `Expires` time are kept as their age is unknown.
+    pub fn expire_at(&mut self, now: DtnTime) -> Result<Vec<String>, SpoolError> {
+        let expired: Vec<(String, String)> = self
+            .groups
+            .iter()
+            .flat_map(|(group, articles)| {
+                articles
+                    .iter()
+                    .filter(|(_, news)| self.keep_until(news).is_some_and(|until| until <= now))
+                    .map(|(id, _)| (group.clone(), id.clone()))
+            })
+            .collect();
+        for (group, id) in &expired {
+            self.remove(group, id)?;
+        }
+        Ok(expired.into_iter().map(|(_, id)| id).collect())
+    }
+}
+
+fn parent(news: &NewsBundle, superseded: &HashMap<String, String>) -> Option<String> {
+    let parent = news.references().pop()?;
+    Some(superseded.get(&parent).cloned().unwrap_or(parent))
+}
+
+/// Reply tree below an article, every article is only placed once, even on reference cycles
+fn thread_node<'a>(
+    news: &'a NewsBundle,
+    posts: &[&'a NewsBundle],
+    superseded: &HashMap<String, String>,
+    visited: &mut HashSet<String>,
+) -> ThreadNode<'a> {
+    visited.insert(news.id());
+    let mid = news.message_id();
+    let mut replies = Vec::new();
+    for reply in posts {
+        if parent(reply, superseded).as_ref() == Some(&mid) && !visited.contains(&reply.id()) {
+            replies.push(thread_node(reply, posts, superseded, visited));
+        }
+    }
+    ThreadNode {
+        article: news,
+        replies,
+    }
+}
+
+fn sort_key(news: &NewsBundle) -> (DtnTime, u64, String) {
+    let ts = news.creation_timestamp();
+    (ts.dtntime(), ts.seqno(), news.id())
+}
+
+/// Map a bundle ID to a file name, e.g., `dtn://node1/sms-123-0` becomes `dtn___node1_sms-123-0.bundle`
+fn file_name(id: &str) -> String {
+    let name: String = id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.{}", name, BUNDLE_EXTENSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spool;
+    use crate::news::{
+        DEFAULT_LIFETIME, NewsBuilder, NewsBundleBuilder, cancel_news, new_news, reply_news,
+        sender_eid, supersede_news,
+    };
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_spool_threads() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(dir.path()).unwrap();
+
+        let first = new_news(
+            "node1",
+            "de.test",
+            "first",
+            None,
+            vec![],
+            "a",
+            vec![],
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        let second = new_news(
+            "node2",
+            "de.test",
+            "second",
+            None,
+            vec![],
+            "b",
+            vec![],
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        let reply = reply_news(&first, "node2", "re a", true, DEFAULT_LIFETIME).unwrap();
+        let reply2 = reply_news(&reply, "node3", "re re a", true, DEFAULT_LIFETIME).unwrap();
+        let other = new_news(
+            "node1",
+            "de.other",
+            "x",
+            None,
+            vec![],
+            "c",
+            vec![],
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+
+        for news in [&first, &second, &reply, &reply2, &other] {
+            assert!(spool.store(news.clone()).unwrap());
+        }
+        assert!(!spool.store(first.clone()).unwrap());
+        assert_eq!(spool.groups(), vec!["de.other", "de.test"]);
+        assert_eq!(spool.articles("de.test").len(), 4);
+
+        let spool = Spool::open(dir.path()).unwrap();
+        let threads = spool.threads("de.test");
+        assert_eq!(threads.len(), 2);
+        assert_eq!(threads[0].tid, first.tid());
+        assert_eq!(threads[0].len(), 3);
+        let root = &threads[0].roots[0];
+        assert_eq!(root.article.id(), first.id());
+        assert_eq!(root.replies[0].article.id(), reply.id());
+        assert_eq!(root.replies[0].replies[0].article.id(), reply2.id());
+        assert_eq!(threads[1].tid, second.tid());
+    }
+
+    #[test]
+    fn test_spool_control() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(dir.path()).unwrap();
+
+        let first = new_news(
+            "node1",
+            "de.test",
+            "first",
+            None,
+            vec![],
+            "a",
+            vec![],
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        let reply = reply_news(&first, "node2", "re a", true, DEFAULT_LIFETIME).unwrap();
+        spool.store(first.clone()).unwrap();
+        spool.store(reply.clone()).unwrap();
+
+        // only the author may supersede
+        let forged = supersede_news(&first, "node2", "b", true).unwrap();
+        assert!(!spool.store(forged.clone()).unwrap());
+        assert!(spool.find_message(&forged.message_id()).is_none());
+        assert!(spool.find_message(&first.message_id()).is_some());
+
+        let correction = supersede_news(&first, "node1", "a, corrected", true).unwrap();
+        assert!(spool.store(correction.clone()).unwrap());
+        assert!(spool.find_message(&first.message_id()).is_none());
+        let threads = spool.threads("de.test");
+        let root = threads[0]
+            .roots
+            .iter()
+            .find(|r| r.article.id() == correction.id())
+            .unwrap();
+        assert_eq!(root.replies[0].article.id(), reply.id());
+
+        // cancel arriving before the article
+        let other = new_news(
+            "node3",
+            "de.test",
+            "other",
+            None,
+            vec![],
+            "c",
+            vec![],
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        let cancel = cancel_news(&other, "node3").unwrap();
+        assert!(spool.store(cancel.clone()).unwrap());
+        assert!(!spool.store(other).unwrap());
+        assert!(
+            spool
+                .articles("de.test")
+                .iter()
+                .all(|a| a.id() != cancel.id())
+        );
+
+        assert!(spool.store(cancel_news(&reply, "node2").unwrap()).unwrap());
+        assert!(spool.find_message(&reply.message_id()).is_none());
+
+        // forged supersede arriving before the article does not take over its replies
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(dir.path()).unwrap();
+        let forged = supersede_news(&first, "node2", "b", true).unwrap();
+        for news in [&forged, &first, &reply] {
+            assert!(spool.store(news.clone()).unwrap());
+        }
+        let threads = spool.threads("de.test");
+        let root = threads[0]
+            .roots
+            .iter()
+            .find(|r| r.article.id() == first.id())
+            .unwrap();
+        assert_eq!(root.replies[0].article.id(), reply.id());
+    }
+
+    #[test]
+    fn test_spool_message_id_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(dir.path()).unwrap();
+        let tid = Uuid::new_v4();
+        let post = |mid: &str, references: Vec<String>| {
+            let payload = NewsBuilder::new()
+                .topic("t")
+                .message(mid)
+                .thread_id(tid)
+                .message_id(mid)
+                .references(references)
+                .build()
+                .unwrap();
+            NewsBundleBuilder::new()
+                .source(sender_eid("node1").unwrap())
+                .newsgroup("de.test")
+                .unwrap()
+                .payload(payload)
+                .build()
+                .unwrap()
+        };
+        // same message ID replying to itself
+        assert!(spool.store(post("m", vec![])).unwrap());
+        assert!(!spool.store(post("m", vec!["m".into()])).unwrap());
+        // articles replying to each other
+        assert!(spool.store(post("a", vec!["b".into()])).unwrap());
+        assert!(spool.store(post("b", vec!["a".into()])).unwrap());
+        let threads = spool.threads("de.test");
+        assert_eq!(threads.len(), 1);
+        assert_eq!(threads[0].len(), 3);
+
+        assert!(
+            NewsBuilder::new()
+                .topic("t")
+                .message("m")
+                .message_id("a b")
+                .build()
+                .is_err()
+        );
+        assert!(
+            NewsBuilder::new()
+                .topic("t")
+                .message("m")
+                .message_id("<a>")
+                .build()
+                .is_err()
+        );
+        assert!(
+            NewsBuilder::new()
+                .topic("t")
+                .message("m")
+                .reference("a\r\nX: y")
+                .build()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_spool_corrupt_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(dir.path()).unwrap();
+        let news = new_news(
+            "node1",
+            "de.test",
+            "t",
+            None,
+            vec![],
+            "m",
+            vec![],
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        spool.store(news.clone()).unwrap();
+        std::fs::write(
+            dir.path().join("de.test").join("broken.bundle"),
+            [0x9f, 0x01],
+        )
+        .unwrap();
+
+        let spool = Spool::open(dir.path()).unwrap();
+        assert_eq!(spool.articles("de.test"), vec![&news]);
+        assert_eq!(spool.skipped().len(), 1);
+        assert!(spool.skipped()[0].0.ends_with("de.test/broken.bundle"));
+    }
+
+    #[test]
+    fn test_spool_numbers() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(dir.path()).unwrap();
+        let post = |topic: &str| {
+            new_news(
+                "node1",
+                "de.test",
+                topic,
+                None,
+                vec![],
+                "m",
+                vec![],
+                true,
+                DEFAULT_LIFETIME,
+            )
+            .unwrap()
+        };
+        let (first, second, third) = (post("1"), post("2"), post("3"));
+        // numbers follow arrival order, not creation order
+        spool.store(second.clone()).unwrap();
+        spool.store(first.clone()).unwrap();
+        spool.store(third.clone()).unwrap();
+        let numbers = |spool: &Spool| -> Vec<(u64, String)> {
+            spool
+                .numbered("de.test")
+                .iter()
+                .map(|(n, news)| (*n, news.topic()))
+                .collect()
+        };
+        assert_eq!(
+            numbers(&spool),
+            vec![(1, "2".into()), (2, "1".into()), (3, "3".into())]
+        );
+
+        spool.remove("de.test", &third.id()).unwrap();
+        spool.remove("de.test", &second.id()).unwrap();
+        let mut spool = Spool::open(dir.path()).unwrap();
+        assert_eq!(numbers(&spool), vec![(2, "1".into())]);
+        assert_eq!(spool.high_water("de.test"), 3);
+
+        spool.store(post("4")).unwrap();
+        let spool = Spool::open(dir.path()).unwrap();
+        assert_eq!(numbers(&spool), vec![(2, "1".into()), (4, "4".into())]);
+
+        // articles stored before numbering existed are numbered on open
+        std::fs::remove_file(dir.path().join("de.test/numbers")).unwrap();
+        let spool = Spool::open(dir.path()).unwrap();
+        assert_eq!(numbers(&spool).len(), 2);
+        assert_eq!(spool.high_water("de.test"), 2);
+        assert_eq!(spool.high_water("de.nope"), 0);
+    }
+
+    #[test]
+    fn test_spool_expire() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(dir.path()).unwrap();
+        let news = new_news(
+            "node1",
+            "de.test",
+            "t",
+            None,
+            vec![],
+            "m",
+            vec![],
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        spool.store(news.clone()).unwrap();
+
+        assert!(spool.expire().unwrap().is_empty());
+        let later = news.creation_timestamp().dtntime() + 60 * 60 * 1000;
+        assert_eq!(spool.expire_at(later).unwrap(), vec![news.id()]);
+        assert!(spool.groups().is_empty());
+        assert!(
+            Spool::open(dir.path())
+                .unwrap()
+                .articles("de.test")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_spool_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let day = Duration::from_secs(24 * 60 * 60);
+        let mut spool = Spool::open(dir.path()).unwrap().with_retention(7 * day);
+        let short = new_news(
+            "node1",
+            "de.test",
+            "t",
+            None,
+            vec![],
+            "short",
+            vec![],
+            true,
+            DEFAULT_LIFETIME,
+        )
+        .unwrap();
+        let created = short.creation_timestamp().dtntime();
+        let payload = NewsBuilder::new()
+            .topic("t")
+            .message("long")
+            .expires(created + 30 * day.as_millis() as u64)
+            .build()
+            .unwrap();
+        let long = NewsBundleBuilder::new()
+            .source(sender_eid("node1").unwrap())
+            .newsgroup("de.test")
+            .unwrap()
+            .lifetime(3 * day)
+            .payload(payload)
+            .build()
+            .unwrap();
+        spool.store(short.clone()).unwrap();
+        spool.store(long.clone()).unwrap();
+
+        let at = |d: u64| created + d * day.as_millis() as u64;
+        assert_eq!(spool.in_network("de.test", at(1)), vec![&long]);
+        assert_eq!(spool.keep_until(&short), Some(at(7)));
+        assert_eq!(spool.keep_until(&long), Some(at(30)));
+
+        // out of the network, but still kept for readers
+        assert!(spool.expire_at(at(4)).unwrap().is_empty());
+        assert!(spool.in_network("de.test", at(4)).is_empty());
+        assert_eq!(spool.expire_at(at(8)).unwrap(), vec![short.id()]);
+        assert_eq!(spool.expire_at(at(30)).unwrap(), vec![long.id()]);
+    }
+}
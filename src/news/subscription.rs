@@ -0,0 +1,298 @@
+//! Subscriptions to newsgroups by wildcard patterns
+//!
+//! Patterns follow the wildmat format of NNTP ([RFC](https://datatracker.ietf.org/doc/html/rfc3977#section-4)),
+//! e.g., `de.*,!de.test` matches all German groups except `de.test`. The last matching pattern decides.
+//!
+//! There is no group discovery in the network: dtnd only delivers bundles for registered endpoints,
+//! so wildcard patterns only ever match groups learned from bundles of already subscribed groups or
+//! handed to [`Subscriptions::observe_group`], e.g., from a spool. Use [`Subscriptions::exact`] to
+//! subscribe to a fixed list of groups.
+use super::{NewsBundle, NewsError, newsgroup_eid};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// List of comma separated wildcard patterns, each optionally negated with `!`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Wildmat {
+    patterns: Vec<(bool, String)>,
+}
+
+impl Wildmat {
+    pub fn parse(wildmat: &str) -> Self {
+        let patterns = wildmat
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(|p| match p.strip_prefix('!') {
+                Some(negated) => (false, negated.to_string()),
+                None => (true, p.to_string()),
+            })
+            .collect();
+        Wildmat { patterns }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+    /// Result of the last matching pattern, `None` if no pattern matches
+    pub fn evaluate(&self, text: &str) -> Option<bool> {
+        self.patterns
+            .iter()
+            .rev()
+            .find(|(_, pattern)| glob_match(pattern.as_bytes(), text.as_bytes()))
+            .map(|(positive, _)| *positive)
+    }
+    pub fn matches(&self, text: &str) -> bool {
+        self.evaluate(text) == Some(true)
+    }
+    fn has_positive(&self) -> bool {
+        self.patterns.iter().any(|(positive, _)| *positive)
+    }
+}
+
+impl fmt::Display for Wildmat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let patterns: Vec<String> = self
+            .patterns
+            .iter()
+            .map(|(positive, p)| format!("{}{}", if *positive { "" } else { "!" }, p))
+            .collect();
+        write!(f, "{}", patterns.join(","))
+    }
+}
+
+/// Match `*` (any sequence) and `?` (any single character) wildcards
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == b'?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bt)) => {
+                    backtrack = Some((bp, bt + 1));
+                    p = bp + 1;
+                    t = bt + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// Newsgroup subscriptions of a receiver
+///
+/// Groups seen in incoming bundles are remembered, so that matching groups can be registered later on.
+/// As only bundles of registered endpoints arrive, wildcards cannot discover unknown groups.
+#[derive(Debug, Clone, Default)]
+pub struct Subscriptions {
+    groups: Wildmat,
+    tags: Wildmat,
+    known: BTreeSet<String>,
+    registered: BTreeSet<String>,
+}
+
+impl Subscriptions {
+    /// Subscribe to all groups matching the wildmat, e.g., `de.hessen.*`
+    pub fn new(groups: &str) -> Self {
+        let groups = Wildmat::parse(groups);
+        // exact group names are known without having seen them
+        let known = groups
+            .patterns
+            .iter()
+            .filter(|(positive, p)| *positive && !p.contains(['*', '?']))
+            .map(|(_, p)| p.clone())
+            .collect();
+        Subscriptions {
+            groups,
+            known,
+            ..Default::default()
+        }
+    }
+    /// Subscribe to a comma separated list of exact group names, e.g., `de.hessen.darmstadt,en.misc`
+    pub fn exact(groups: &str) -> Result<Self, NewsError> {
+        let subs = Subscriptions::new(groups);
+        for (positive, group) in &subs.groups.patterns {
+            if !positive || group.contains(['*', '?']) {
+                return Err(NewsError::InvalidGroup(group.clone()));
+            }
+            let eid = newsgroup_eid(group).map_err(|_| NewsError::InvalidGroup(group.clone()))?;
+            if eid.node().as_deref() != Some(group.as_str()) {
+                return Err(NewsError::InvalidGroup(group.clone()));
+            }
+        }
+        if subs.groups.is_empty() {
+            return Err(NewsError::InvalidGroup(groups.into()));
+        }
+        Ok(subs)
+    }
+    /// Only accept articles with matching tags, e.g., `*,!spam` or `weather,traffic`
+    ///
+    /// Articles are rejected if any tag is excluded and otherwise need at least one included tag.
+    /// With only exclusions, untagged articles are accepted as well.
+    pub fn with_tags(mut self, tags: &str) -> Self {
+        self.tags = Wildmat::parse(tags);
+        self
+    }
+    pub fn is_subscribed(&self, group: &str) -> bool {
+        self.groups.matches(group)
+    }
+    /// Check group and tags of an incoming article
+    pub fn accepts(&self, news: &NewsBundle) -> bool {
+        if !self.is_subscribed(&news.dst().unwrap_or_default()) {
+            return false;
+        }
+        if self.tags.is_empty() {
+            return true;
+        }
+        let verdicts: Vec<Option<bool>> = news
+            .tags()
+            .iter()
+            .map(|tag| self.tags.evaluate(tag))
+            .collect();
+        if verdicts.contains(&Some(false)) {
+            return false;
+        }
+        verdicts.contains(&Some(true)) || !self.tags.has_positive()
+    }
+    /// Remember the group of an incoming article, returns `true` if it has not been seen before
+    pub fn observe(&mut self, news: &NewsBundle) -> bool {
+        match news.dst() {
+            Some(group) => self.observe_group(&group),
+            None => false,
+        }
+    }
+    /// Remember a group learned elsewhere, e.g., from a spool
+    pub fn observe_group(&mut self, group: &str) -> bool {
+        self.known.insert(group.into())
+    }
+    /// All groups seen so far
+    pub fn known_groups(&self) -> Vec<String> {
+        self.known.iter().cloned().collect()
+    }
+    /// Known groups matching the subscription
+    pub fn subscribed_groups(&self) -> Vec<String> {
+        self.known
+            .iter()
+            .filter(|group| self.is_subscribed(group))
+            .cloned()
+            .collect()
+    }
+    /// Subscribed groups whose endpoints have not been registered yet
+    pub fn pending_groups(&self) -> Vec<String> {
+        self.subscribed_groups()
+            .into_iter()
+            .filter(|group| !self.registered.contains(group))
+            .collect()
+    }
+    /// Register the endpoints of all pending groups on the local dtnd, returns the new endpoints
+    #[cfg(feature = "client")]
+    pub fn register(&mut self, client: &crate::client::DtnClient) -> anyhow::Result<Vec<String>> {
+        let mut endpoints = Vec::new();
+        for group in self.pending_groups() {
+            let endpoint = newsgroup_eid(&group)?.to_string();
+            client.register_application_endpoint(&endpoint)?;
+            self.registered.insert(group);
+            endpoints.push(endpoint);
+        }
+        Ok(endpoints)
+    }
+    /// Endpoints of all subscribed groups
+    pub fn endpoints(&self) -> Result<Vec<String>, NewsError> {
+        self.subscribed_groups()
+            .iter()
+            .map(|group| newsgroup_eid(group).map(|eid| eid.to_string()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Subscriptions, Wildmat};
+    use crate::news::{DEFAULT_LIFETIME, new_news};
+
+    #[test]
+    fn test_wildmat() {
+        let wildmat = Wildmat::parse("de.*,!de.test");
+        assert!(wildmat.matches("de.hessen.darmstadt"));
+        assert!(!wildmat.matches("de.test"));
+        assert!(!wildmat.matches("en.test"));
+        assert_eq!(wildmat.evaluate("de.test"), Some(false));
+        assert_eq!(wildmat.evaluate("en.test"), None);
+        assert_eq!(wildmat.to_string(), "de.*,!de.test");
+
+        assert!(Wildmat::parse("de.?essen.*").matches("de.hessen.darmstadt"));
+        assert!(Wildmat::parse("*").matches("42"));
+        assert!(!Wildmat::parse("de.hessen").matches("de.hessen.darmstadt"));
+        assert!(Wildmat::parse("*.darmstadt").matches("de.hessen.darmstadt"));
+    }
+
+    #[test]
+    fn test_subscriptions() {
+        let post = |group: &str, tags: Vec<&str>| {
+            let tags = tags.into_iter().map(String::from).collect();
+            new_news(
+                "node1",
+                group,
+                "t",
+                None,
+                vec![],
+                "m",
+                tags,
+                true,
+                DEFAULT_LIFETIME,
+            )
+            .unwrap()
+        };
+        let mut subs = Subscriptions::new("de.hessen.*,!de.hessen.test,en.misc");
+        assert_eq!(subs.known_groups(), vec!["en.misc"]);
+
+        assert!(subs.observe(&post("de.hessen.darmstadt", vec![])));
+        assert!(!subs.observe(&post("de.hessen.darmstadt", vec![])));
+        assert!(subs.observe(&post("de.hessen.test", vec![])));
+        assert!(subs.observe(&post("fr.paris", vec![])));
+        assert_eq!(
+            subs.subscribed_groups(),
+            vec!["de.hessen.darmstadt", "en.misc"]
+        );
+        assert_eq!(
+            subs.endpoints().unwrap(),
+            vec!["dtn://de.hessen.darmstadt/~news", "dtn://en.misc/~news"]
+        );
+
+        assert!(subs.accepts(&post("de.hessen.kassel", vec![])));
+        assert!(!subs.accepts(&post("de.hessen.test", vec![])));
+
+        let subs = subs.with_tags("weather,traffic");
+        assert!(subs.accepts(&post("en.misc", vec!["traffic"])));
+        assert!(!subs.accepts(&post("en.misc", vec!["sports"])));
+        assert!(!subs.accepts(&post("en.misc", vec![])));
+
+        let subs = subs.with_tags("!spam");
+        assert!(subs.accepts(&post("en.misc", vec![])));
+        assert!(subs.accepts(&post("en.misc", vec!["sports"])));
+        assert!(!subs.accepts(&post("en.misc", vec!["sports", "spam"])));
+    }
+
+    #[test]
+    fn test_subscriptions_exact() {
+        let subs = Subscriptions::exact("de.hessen.darmstadt, en.misc").unwrap();
+        assert_eq!(
+            subs.subscribed_groups(),
+            vec!["de.hessen.darmstadt", "en.misc"]
+        );
+        assert!(!subs.is_subscribed("de.hessen.kassel"));
+        assert!(Subscriptions::exact("de.hessen.*").is_err());
+        assert!(Subscriptions::exact("de.?essen").is_err());
+        assert!(Subscriptions::exact("en.misc,!de.test").is_err());
+        assert!(Subscriptions::exact("de/evil").is_err());
+        assert!(Subscriptions::exact(" , ").is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! Minimal internet message format helpers ([RFC](https://datatracker.ietf.org/doc/html/rfc5322))
+//!
+//! Only covers what the gateways need: dates, unfolded headers, address extraction
+//! and a plain text body. No MIME decoding is done here.
+use std::fmt;
+
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Convert days since 1970-01-01 into (year, month, day)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+/// Convert (year, month, day) into days since 1970-01-01
+fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = if m > 2 { m - 3 } else { m + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + d as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Format a unix timestamp in seconds as RFC 5322 date in UTC, e.g., `Sun, 18 Oct 2026 13:44:14 +0000`
+pub fn format_date(unix_secs: u64) -> String {
+    let days = (unix_secs / 86400) as i64;
+    let secs = unix_secs % 86400;
+    let (y, m, d) = civil_from_days(days);
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} +0000",
+        DAYS[(days % 7) as usize],
+        d,
+        MONTHS[(m - 1) as usize],
+        y,
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
+
+/// Format a unix timestamp in seconds in the ctime style used by mbox `From ` lines, e.g., `Sun Oct 18 13:44:14 2026`
+pub fn format_asctime(unix_secs: u64) -> String {
+    let days = (unix_secs / 86400) as i64;
+    let secs = unix_secs % 86400;
+    let (y, m, d) = civil_from_days(days);
+    format!(
+        "{} {} {:>2} {:02}:{:02}:{:02} {}",
+        DAYS[(days % 7) as usize],
+        MONTHS[(m - 1) as usize],
+        d,
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60,
+        y
+    )
+}
+
+/// Format a unix timestamp in seconds as RFC 3339 date in UTC as used by Atom, e.g., `2026-10-18T13:44:14Z`
+pub fn format_rfc3339(unix_secs: u64) -> String {
+    let secs = unix_secs % 86400;
+    let (y, m, d) = civil_from_days((unix_secs / 86400) as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        y,
+        m,
+        d,
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
+
+/// Parse an RFC 5322 date into a unix timestamp in seconds
+///
+/// The day of week and seconds are optional, obsolete zone names other than `GMT` and `UT` are not supported.
+pub fn parse_date(date: &str) -> Option<u64> {
+    let date = date.split_once(',').map(|(_, d)| d).unwrap_or(date);
+    let mut parts = date.split_whitespace();
+    let d: u32 = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let m = MONTHS.iter().position(|n| n.eq_ignore_ascii_case(month))? as u32 + 1;
+    let y: i64 = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?.split(':');
+    let hh: i64 = time.next()?.parse().ok()?;
+    let mm: i64 = time.next()?.parse().ok()?;
+    let ss: i64 = time.next().map(|s| s.parse()).unwrap_or(Ok(0)).ok()?;
+    let offset = match parts.next() {
+        None | Some("GMT") | Some("UT") | Some("Z") => 0,
+        Some(zone) => {
+            let (sign, digits) = zone.split_at(1);
+            let hhmm: i64 = digits.parse().ok()?;
+            let offset = (hhmm / 100) * 3600 + (hhmm % 100) * 60;
+            match sign {
+                "+" => offset,
+                "-" => -offset,
+                _ => return None,
+            }
+        }
+    };
+    let secs = days_from_civil(y, m, d) * 86400 + hh * 3600 + mm * 60 + ss - offset;
+    u64::try_from(secs).ok()
+}
+
+/// Extract the plain address from a mailbox, e.g., `Jane Doe <jane@example.org>` becomes `jane@example.org`
+pub fn extract_address(mailbox: &str) -> String {
+    if let (Some(start), Some(end)) = (mailbox.find('<'), mailbox.rfind('>'))
+        && start < end
+    {
+        return mailbox[start + 1..end].trim().to_string();
+    }
+    mailbox.trim().to_string()
+}
+
+/// Internet message consisting of ordered header fields and a body
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Message {
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Message {
+    pub fn new() -> Self {
+        Message::default()
+    }
+    /// Parse a message, accepting both CRLF and LF line endings
+    pub fn parse(raw: &str) -> Message {
+        let mut msg = Message::new();
+        let mut lines = raw.split('\n').map(|l| l.strip_suffix('\r').unwrap_or(l));
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with([' ', '\t']) {
+                if let Some((_, value)) = msg.headers.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+            } else if let Some((name, value)) = line.split_once(':') {
+                msg.headers
+                    .push((name.trim().to_string(), value.trim().to_string()));
+            }
+        }
+        msg.body = lines.collect::<Vec<&str>>().join("\n");
+        msg
+    }
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+    pub fn with_body(mut self, body: &str) -> Self {
+        self.body = body.into();
+        self
+    }
+    /// Return the first header field with the given name, ignoring case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+    pub fn headers(&self) -> &[(String, String)] {
+        self.headers.as_slice()
+    }
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+}
+
+/// Serialize with CRLF line endings as used on the wire
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.headers {
+            write!(f, "{}: {}\r\n", name, value)?;
+        }
+        write!(f, "\r\n")?;
+        for line in self.body.lines() {
+            write!(f, "{}\r\n", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Message, extract_address, format_asctime, format_date, format_rfc3339, parse_date,
+    };
+
+    #[test]
+    fn test_date_roundtrip() {
+        assert_eq!(format_date(0), "Thu, 01 Jan 1970 00:00:00 +0000");
+        assert_eq!(
+            format_date(1_792_331_054),
+            "Sun, 18 Oct 2026 13:44:14 +0000"
+        );
+        assert_eq!(
+            parse_date("Sun, 18 Oct 2026 13:44:14 +0000"),
+            Some(1_792_331_054)
+        );
+        assert_eq!(
+            parse_date("18 Oct 2026 15:44:14 +0200"),
+            Some(1_792_331_054)
+        );
+        assert_eq!(parse_date("29 Feb 2024 00:00 GMT"), Some(1_709_164_800));
+        assert_eq!(parse_date("not a date"), None);
+        assert_eq!(format_asctime(1_792_331_054), "Sun Oct 18 13:44:14 2026");
+        assert_eq!(format_asctime(0), "Thu Jan  1 00:00:00 1970");
+        assert_eq!(format_rfc3339(1_792_331_054), "2026-10-18T13:44:14Z");
+    }
+
+    #[test]
+    fn test_message_parse() {
+        let raw = "From: Jane Doe <jane@example.org>\r\nSubject: hello\r\n  world\r\n\r\nline 1\r\nline 2\r\n";
+        let msg = Message::parse(raw);
+        assert_eq!(
+            extract_address(msg.header("from").unwrap()),
+            "jane@example.org"
+        );
+        assert_eq!(msg.header("Subject"), Some("hello world"));
+        assert_eq!(msg.body().trim_end(), "line 1\nline 2");
+        assert_eq!(Message::parse(&msg.to_string()), msg);
+    }
+}
@@ -69,3 +69,67 @@ pub mod base64_or_bytes {
         }
     }
 }
+
+/// (De)serialize a list of endpoint IDs as their URI strings, e.g. `dtn://node1/sms`
+pub mod eid_strings {
+    use bp7::EndpointID;
+    use serde::{Deserialize, Deserializer, Serializer, ser::SerializeSeq};
+    use std::convert::TryFrom;
+
+    pub fn serialize<S: Serializer>(v: &[EndpointID], s: S) -> Result<S::Ok, S::Error> {
+        let mut seq = s.serialize_seq(Some(v.len()))?;
+        for eid in v {
+            seq.serialize_element(&eid.to_string())?;
+        }
+        seq.end()
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<EndpointID>, D::Error> {
+        Vec::<String>::deserialize(d)?
+            .into_iter()
+            .map(|s| EndpointID::try_from(s).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
+/// (De)serialize a single endpoint ID as its URI string, e.g. `dtn://group/~sms`
+pub mod eid_string {
+    use bp7::EndpointID;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::convert::TryFrom;
+
+    pub fn serialize<S: Serializer>(v: &EndpointID, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&v.to_string())
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<EndpointID, D::Error> {
+        EndpointID::try_from(String::deserialize(d)?).map_err(serde::de::Error::custom)
+    }
+}
+
+/// (De)serialize a list of strings compatible to a former `Option<String>` field
+///
+/// An empty list is encoded as null and a single entry as plain string, longer lists as sequence.
+pub mod one_or_many {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    pub fn serialize<S: Serializer>(v: &[String], s: S) -> Result<S::Ok, S::Error> {
+        match v {
+            [] => s.serialize_none(),
+            [one] => s.serialize_str(one),
+            many => many.serialize(s),
+        }
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+        Ok(match Option::<OneOrMany>::deserialize(d)? {
+            None => Vec::new(),
+            Some(OneOrMany::One(one)) => vec![one],
+            Some(OneOrMany::Many(many)) => many,
+        })
+    }
+}
@@ -0,0 +1,407 @@
+//! Address book mapping human-readable names to sms endpoints
+//!
+//! Contacts can be stored as JSON or as vCard 4.0 ([RFC](https://datatracker.ietf.org/doc/html/rfc6350)).
+//! Endpoints are kept in their URI form and written as `IMPP` properties in vCards,
+//! public keys as base64 encoded `KEY` properties.
+//! Groups are vCards of `KIND:group` with the group endpoint as `IMPP` and
+//! one `MEMBER` property per member endpoint.
+use base64::Engine;
+use bp7::EndpointID;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AddressBookError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("serde json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("failed to create endpoint: {0}")]
+    EndpointIdInvalid(#[from] bp7::eid::EndpointIdError),
+    #[error("invalid base64 data: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("invalid vcard: {0}")]
+    InvalidVCard(String),
+    #[error("contact already exists: {0}")]
+    DuplicateContact(String),
+}
+
+/// Raw public key of a contact, base64 encoded in human-readable formats
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct PublicKey(#[serde(with = "crate::serde::base64_or_bytes")] pub Vec<u8>);
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Contact {
+    name: String,
+    #[serde(with = "crate::serde::eid_strings")]
+    eids: Vec<EndpointID>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    keys: Vec<PublicKey>,
+}
+
+impl Contact {
+    pub fn new(name: &str) -> Self {
+        Contact {
+            name: name.into(),
+            eids: Vec::new(),
+            keys: Vec::new(),
+        }
+    }
+    pub fn with_eid(mut self, eid: EndpointID) -> Self {
+        self.eids.push(eid);
+        self
+    }
+    pub fn with_key(mut self, key: Vec<u8>) -> Self {
+        self.keys.push(PublicKey(key));
+        self
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn eids(&self) -> &[EndpointID] {
+        self.eids.as_slice()
+    }
+    pub fn public_keys(&self) -> &[PublicKey] {
+        self.keys.as_slice()
+    }
+    /// Check whether an endpoint belongs to this contact
+    ///
+    /// Endpoints match if they are equal or if they share the same node ID.
+    pub fn has_eid(&self, eid: &EndpointID) -> bool {
+        self.eids
+            .iter()
+            .any(|e| e == eid || (e.node_id().is_some() && e.node_id() == eid.node_id()))
+    }
+    /// Return the sms endpoint of this contact for the given scheme
+    ///
+    /// Node IDs are extended with the default sms service, `767` for ipn and `sms` for dtn.
+    pub fn sms_endpoint(&self, scheme: &str) -> Option<EndpointID> {
+        let eid = self.eids.iter().find(|e| e.scheme() == scheme)?;
+        if eid.is_node_id() {
+            let service = if scheme == "ipn" { "767" } else { "sms" };
+            eid.new_endpoint(service).ok()
+        } else {
+            Some(eid.clone())
+        }
+    }
+}
+
+/// Group of members sharing a non-singleton endpoint, e.g., `dtn://team-a/~sms`
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Group {
+    name: String,
+    #[serde(with = "crate::serde::eid_string")]
+    eid: EndpointID,
+    #[serde(with = "crate::serde::eid_strings")]
+    members: Vec<EndpointID>,
+}
+
+impl Group {
+    pub fn new(name: &str, eid: EndpointID) -> Self {
+        Group {
+            name: name.into(),
+            eid,
+            members: Vec::new(),
+        }
+    }
+    pub fn with_member(mut self, eid: EndpointID) -> Self {
+        self.members.push(eid);
+        self
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn eid(&self) -> &EndpointID {
+        &self.eid
+    }
+    pub fn members(&self) -> &[EndpointID] {
+        self.members.as_slice()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct AddressBook {
+    contacts: Vec<Contact>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<Group>,
+}
+
+impl AddressBook {
+    pub fn new() -> Self {
+        AddressBook {
+            contacts: Vec::new(),
+            groups: Vec::new(),
+        }
+    }
+    /// Add a new contact, names must be unique
+    pub fn add(&mut self, contact: Contact) -> Result<(), AddressBookError> {
+        if self.get(contact.name()).is_some() {
+            return Err(AddressBookError::DuplicateContact(contact.name));
+        }
+        self.contacts.push(contact);
+        Ok(())
+    }
+    pub fn remove(&mut self, name: &str) -> Option<Contact> {
+        let pos = self.contacts.iter().position(|c| c.name == name)?;
+        Some(self.contacts.remove(pos))
+    }
+    pub fn get(&self, name: &str) -> Option<&Contact> {
+        self.contacts.iter().find(|c| c.name == name)
+    }
+    pub fn contacts(&self) -> &[Contact] {
+        self.contacts.as_slice()
+    }
+    /// Add a new group, names must be unique
+    pub fn add_group(&mut self, group: Group) -> Result<(), AddressBookError> {
+        if self.group(group.name()).is_some() {
+            return Err(AddressBookError::DuplicateContact(group.name));
+        }
+        self.groups.push(group);
+        Ok(())
+    }
+    pub fn remove_group(&mut self, name: &str) -> Option<Group> {
+        let pos = self.groups.iter().position(|g| g.name == name)?;
+        Some(self.groups.remove(pos))
+    }
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|g| g.name == name)
+    }
+    pub fn groups(&self) -> &[Group] {
+        self.groups.as_slice()
+    }
+    /// Find the group with the given group endpoint
+    pub fn lookup_group(&self, eid: &EndpointID) -> Option<&Group> {
+        self.groups.iter().find(|g| &g.eid == eid)
+    }
+    /// Find the contact owning the given endpoint
+    pub fn lookup(&self, eid: &EndpointID) -> Option<&Contact> {
+        self.contacts
+            .iter()
+            .find(|c| c.eids.contains(eid))
+            .or_else(|| self.contacts.iter().find(|c| c.has_eid(eid)))
+    }
+    /// Resolve an endpoint to a contact or group name, falling back to the plain node name
+    pub fn display_name(&self, eid: &EndpointID) -> String {
+        self.lookup_group(eid)
+            .map(|g| g.name.clone())
+            .or_else(|| self.lookup(eid).map(|c| c.name.clone()))
+            .or_else(|| eid.node())
+            .unwrap_or_default()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, AddressBookError> {
+        Ok(serde_json::from_str(json)?)
+    }
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self)
+            .expect("Fatal failure, could not convert address book to JSON")
+    }
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self, AddressBookError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<(), AddressBookError> {
+        Ok(std::fs::write(path, self.to_json())?)
+    }
+
+    /// Parse all vCards contained in the given string
+    pub fn from_vcard(vcf: &str) -> Result<Self, AddressBookError> {
+        let mut book = AddressBook::new();
+        let mut current: Option<Contact> = None;
+        let mut is_group = false;
+        let mut members: Vec<EndpointID> = Vec::new();
+        for line in unfold_vcard_lines(vcf) {
+            let (prop, value) = line
+                .split_once(':')
+                .ok_or_else(|| AddressBookError::InvalidVCard(line.clone()))?;
+            // strip parameters, e.g., `KEY;TYPE=work`
+            let name = prop.split(';').next().unwrap_or_default().to_uppercase();
+            match name.as_str() {
+                "BEGIN" => {
+                    current = Some(Contact::new(""));
+                    is_group = false;
+                    members.clear();
+                }
+                "END" => {
+                    let contact = current
+                        .take()
+                        .ok_or_else(|| AddressBookError::InvalidVCard(line.clone()))?;
+                    if is_group {
+                        let eid = contact.eids.first().cloned().ok_or_else(|| {
+                            AddressBookError::InvalidVCard("group without IMPP".into())
+                        })?;
+                        let mut group = Group::new(&contact.name, eid);
+                        group.members = std::mem::take(&mut members);
+                        book.add_group(group)?;
+                    } else {
+                        book.add(contact)?;
+                    }
+                }
+                "KIND" => is_group = value.eq_ignore_ascii_case("group"),
+                "MEMBER" => members.push(EndpointID::try_from(value)?),
+                "FN" => {
+                    if let Some(c) = current.as_mut() {
+                        c.name = unescape_vcard_value(value);
+                    }
+                }
+                "IMPP" => {
+                    if let Some(c) = current.as_mut() {
+                        c.eids.push(EndpointID::try_from(value)?);
+                    }
+                }
+                "KEY" => {
+                    if let Some(c) = current.as_mut() {
+                        let data = value.rsplit(',').next().unwrap_or_default();
+                        c.keys.push(PublicKey(
+                            base64::engine::general_purpose::STANDARD.decode(data)?,
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if current.is_some() {
+            return Err(AddressBookError::InvalidVCard("missing END:VCARD".into()));
+        }
+        Ok(book)
+    }
+    /// Serialize all contacts as vCard 4.0
+    pub fn to_vcard(&self) -> String {
+        let mut vcf = String::new();
+        for c in &self.contacts {
+            vcf.push_str("BEGIN:VCARD\r\nVERSION:4.0\r\n");
+            vcf.push_str(&format!("FN:{}\r\n", escape_vcard_value(&c.name)));
+            for eid in &c.eids {
+                vcf.push_str(&format!("IMPP:{}\r\n", eid));
+            }
+            for key in &c.keys {
+                vcf.push_str(&format!(
+                    "KEY:data:application/octet-stream;base64,{}\r\n",
+                    base64::engine::general_purpose::STANDARD.encode(&key.0)
+                ));
+            }
+            vcf.push_str("END:VCARD\r\n");
+        }
+        for g in &self.groups {
+            vcf.push_str("BEGIN:VCARD\r\nVERSION:4.0\r\nKIND:group\r\n");
+            vcf.push_str(&format!("FN:{}\r\n", escape_vcard_value(&g.name)));
+            vcf.push_str(&format!("IMPP:{}\r\n", g.eid));
+            for member in &g.members {
+                vcf.push_str(&format!("MEMBER:{}\r\n", member));
+            }
+            vcf.push_str("END:VCARD\r\n");
+        }
+        vcf
+    }
+    pub fn load_vcard<P: AsRef<Path>>(path: P) -> Result<Self, AddressBookError> {
+        Self::from_vcard(&std::fs::read_to_string(path)?)
+    }
+    pub fn save_vcard<P: AsRef<Path>>(&self, path: P) -> Result<(), AddressBookError> {
+        Ok(std::fs::write(path, self.to_vcard())?)
+    }
+}
+
+/// Join folded lines, continuation lines start with a space or tab
+fn unfold_vcard_lines(vcf: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in vcf.lines() {
+        if let Some(cont) = line.strip_prefix([' ', '\t']) {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(cont);
+            }
+        } else if !line.trim().is_empty() {
+            lines.push(line.trim_end().to_string());
+        }
+    }
+    lines
+}
+
+fn escape_vcard_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace('\n', "\\n")
+}
+
+fn unescape_vcard_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => {}
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AddressBook, Contact, Group};
+    use bp7::EndpointID;
+    use std::convert::TryFrom;
+
+    fn test_book() -> AddressBook {
+        let mut book = AddressBook::new();
+        book.add(
+            Contact::new("Alice, Ops")
+                .with_eid(EndpointID::with_ipn(1239468786, 767).unwrap())
+                .with_eid(EndpointID::try_from("dtn://alice/").unwrap())
+                .with_key(vec![1, 2, 3, 4]),
+        )
+        .unwrap();
+        book.add(Contact::new("Bob").with_eid(EndpointID::with_ipn(1239468999, 0).unwrap()))
+            .unwrap();
+        book.add_group(
+            Group::new("Team A", EndpointID::try_from("dtn://team-a/~sms").unwrap())
+                .with_member(EndpointID::try_from("dtn://alice/sms").unwrap())
+                .with_member(EndpointID::try_from("dtn://bob/sms").unwrap()),
+        )
+        .unwrap();
+        book
+    }
+
+    #[test]
+    fn test_addressbook_json_roundtrip() {
+        let book = test_book();
+        let json = book.to_json();
+        assert!(json.contains("ipn:1239468786.767"));
+        assert_eq!(book, AddressBook::from_json(&json).unwrap());
+    }
+
+    #[test]
+    fn test_addressbook_vcard_roundtrip() {
+        let book = test_book();
+        let vcf = book.to_vcard();
+        assert!(vcf.contains("FN:Alice\\, Ops"));
+        assert_eq!(book, AddressBook::from_vcard(&vcf).unwrap());
+    }
+
+    #[test]
+    fn test_addressbook_lookup() {
+        let book = test_book();
+        let eid = EndpointID::with_ipn(1239468999, 767).unwrap();
+        assert_eq!(book.lookup(&eid).unwrap().name(), "Bob");
+        assert_eq!(book.display_name(&eid), "Bob");
+        let unknown = EndpointID::with_ipn(42, 767).unwrap();
+        assert_eq!(book.display_name(&unknown), "42");
+
+        assert_eq!(book.get("Bob").unwrap().sms_endpoint("ipn"), Some(eid));
+        assert_eq!(
+            book.get("Alice, Ops").unwrap().sms_endpoint("dtn"),
+            Some(EndpointID::try_from("dtn://alice/sms").unwrap())
+        );
+        assert!(book.get("Bob").unwrap().sms_endpoint("dtn").is_none());
+
+        let group = EndpointID::try_from("dtn://team-a/~sms").unwrap();
+        assert_eq!(book.display_name(&group), "Team A");
+        assert_eq!(book.lookup_group(&group).unwrap().members().len(), 2);
+    }
+}
@@ -0,0 +1,142 @@
+//! Human-readable JSON representation of sms bundles for web frontends
+//!
+//! ```json
+//! {
+//!   "src": "dtn://node1/sms",
+//!   "dst": "dtn://node2/sms",
+//!   "msg": "The quick brown fox jumps over the lazy dog"
+//! }
+//! ```
+use super::{Attachment, DEFAULT_LIFETIME, SMSBundle, SmsBuilder, SmsError, sms_bundle};
+use bp7::EndpointID;
+use bp7::dtntime::DtnTimeHelpers;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::time::Duration;
+
+fn default_compression() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmsJson {
+    /// Bundle ID, ignored when constructing a bundle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(with = "crate::serde::eid_string")]
+    pub src: EndpointID,
+    #[serde(with = "crate::serde::eid_string")]
+    pub dst: EndpointID,
+    /// Creation time as RFC 3339 string, ignored when constructing a bundle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    /// Lifetime in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifetime: Option<u64>,
+    pub msg: String,
+    #[serde(default, with = "crate::serde::eid_strings")]
+    pub members: Vec<EndpointID>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// Compress the message when constructing a bundle
+    #[serde(default = "default_compression", skip_serializing)]
+    pub compression: bool,
+}
+
+impl From<&SMSBundle> for SmsJson {
+    fn from(sms: &SMSBundle) -> Self {
+        let primary = &sms.bundle().primary;
+        SmsJson {
+            id: Some(sms.id()),
+            src: primary.source.clone(),
+            dst: primary.destination.clone(),
+            created: Some(primary.creation_timestamp.dtntime().string()),
+            lifetime: Some(primary.lifetime.as_secs()),
+            msg: sms.msg(),
+            members: sms.members(),
+            attachments: sms.attachments(),
+            compression: sms.compression(),
+        }
+    }
+}
+
+impl TryFrom<SmsJson> for SMSBundle {
+    type Error = SmsError;
+
+    fn try_from(value: SmsJson) -> Result<Self, Self::Error> {
+        let payload = value
+            .attachments
+            .into_iter()
+            .fold(SmsBuilder::new(), |b, att| b.attachment(att))
+            .compression(value.compression)
+            .members(value.members)
+            .message(&value.msg)
+            .build()?;
+        let lifetime = value
+            .lifetime
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_LIFETIME);
+        sms_bundle(value.src, value.dst, lifetime, &payload)
+    }
+}
+
+impl SMSBundle {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&SmsJson::from(self))
+            .expect("Fatal failure, could not convert sms to JSON")
+    }
+    /// Construct a new bundle from its JSON representation
+    pub fn from_json(json: &str) -> Result<Self, SmsError> {
+        SMSBundle::try_from(serde_json::from_str::<SmsJson>(json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sms::{Attachment, SMSBundle, new_mms};
+    use bp7::EndpointID;
+
+    #[test]
+    fn test_sms_json_roundtrip() {
+        let sms = new_mms(
+            EndpointID::try_from("dtn://alice/sms").unwrap(),
+            EndpointID::try_from("dtn://bob/sms").unwrap(),
+            "Grüße aus Darmstadt",
+            vec![Attachment::new(
+                "text/plain",
+                Some("note.txt"),
+                b"hi".to_vec(),
+            )],
+            true,
+        )
+        .unwrap();
+        let json = sms.to_json();
+        assert!(json.contains("\"msg\":\"Grüße aus Darmstadt\""));
+        assert!(json.contains("\"src\":\"dtn://alice/sms\""));
+        assert!(json.contains("\"data\":\"aGk=\""));
+
+        let sms2 = SMSBundle::from_json(&json).unwrap();
+        assert_eq!(sms2.msg(), sms.msg());
+        assert_eq!(sms2.attachments(), sms.attachments());
+        assert_eq!(
+            sms2.bundle().primary.destination,
+            sms.bundle().primary.destination
+        );
+    }
+
+    #[test]
+    fn test_sms_json_minimal() {
+        let sms = SMSBundle::from_json(
+            r#"{"src": "ipn:23.767", "dst": "ipn:42.767", "msg": "hello", "lifetime": 7200}"#,
+        )
+        .unwrap();
+        assert_eq!(sms.msg(), "hello");
+        assert_eq!(sms.dst().unwrap(), "42");
+        assert_eq!(sms.bundle().primary.lifetime.as_secs(), 7200);
+
+        assert!(
+            SMSBundle::from_json(r#"{"src": "ipn:23.1", "dst": "ipn:42.767", "msg": "x"}"#)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,803 @@
+//! Gateway between sms and email
+//!
+//! Incoming sms are converted to internet messages and delivered to an SMTP relay.
+//! Replies are picked up from a local maildir and turned back into sms.
+//!
+//! Endpoints are mapped to email addresses via the configuration,
+//! unmapped endpoints get generated addresses such as `ipn-23@domain` or `dtn-node1@domain`.
+use super::{SMSBundle, SmsError, new_sms_with_eids};
+use crate::rfc5322::{Message, extract_address, format_date};
+use base64::Engine;
+use bp7::EndpointID;
+use bp7::dtntime::DtnTimeHelpers;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Nesting limit for multipart reply mails
+const MAX_MIME_DEPTH: usize = 4;
+
+#[derive(Error, Debug)]
+pub enum MailError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("serde json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("sms error: {0}")]
+    Sms(#[from] SmsError),
+    #[error("smtp error: {0} {1}")]
+    Smtp(u16, String),
+    #[error("invalid mail: {0}")]
+    InvalidMail(String),
+    #[error("no endpoint for address: {0}")]
+    UnknownAddress(String),
+}
+
+fn default_helo() -> String {
+    "localhost".into()
+}
+fn default_poll_interval() -> u64 {
+    10
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MailGatewayConfig {
+    /// SMTP relay as `host:port`
+    pub smtp_relay: String,
+    /// Name announced in SMTP HELO
+    #[serde(default = "default_helo")]
+    pub helo: String,
+    /// Domain for generated addresses and message IDs
+    pub domain: String,
+    /// Recipients of all forwarded sms
+    pub recipients: Vec<String>,
+    /// Email addresses of known sms endpoints, e.g., `"ipn:23.767": "alice@example.org"`
+    #[serde(default)]
+    pub addresses: HashMap<String, String>,
+    /// Maildir with replies to be sent as sms
+    pub maildir: PathBuf,
+    /// Sms endpoint of the gateway itself, used as source for replies
+    #[serde(with = "crate::serde::eid_string")]
+    pub gateway_eid: EndpointID,
+    /// Maildir polling interval in seconds
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+}
+
+impl MailGatewayConfig {
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self, MailError> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+/// Callback receiving errors that do not stop the gateway
+pub type ErrorHandler = Arc<dyn Fn(&str) + Send + Sync>;
+
+#[derive(Clone)]
+pub struct MailGateway {
+    config: MailGatewayConfig,
+    on_error: Option<ErrorHandler>,
+}
+
+impl MailGateway {
+    pub fn new(config: MailGatewayConfig) -> Self {
+        MailGateway {
+            config,
+            on_error: None,
+        }
+    }
+    /// Report errors that do not stop the gateway, e.g., failed deliveries or unreadable maildirs
+    pub fn on_error<F: Fn(&str) + Send + Sync + 'static>(mut self, handler: F) -> Self {
+        self.on_error = Some(Arc::new(handler));
+        self
+    }
+    fn report(&self, msg: &str) {
+        if let Some(handler) = &self.on_error {
+            handler(msg);
+        }
+    }
+    pub fn config(&self) -> &MailGatewayConfig {
+        &self.config
+    }
+    /// Map an sms endpoint to an email address
+    pub fn address(&self, eid: &EndpointID) -> String {
+        let configured = self.config.addresses.get(&eid.to_string()).or_else(|| {
+            eid.node_id()
+                .and_then(|node| self.config.addresses.get(&node))
+        });
+        if let Some(addr) = configured {
+            return addr.clone();
+        }
+        format!(
+            "{}-{}@{}",
+            eid.scheme(),
+            eid.node().unwrap_or_default(),
+            self.config.domain
+        )
+    }
+    /// Map an email address back to an sms endpoint
+    pub fn endpoint(&self, address: &str) -> Result<EndpointID, MailError> {
+        let address = extract_address(address);
+        let unknown = || MailError::UnknownAddress(address.clone());
+        if let Some((eid, _)) = self
+            .config
+            .addresses
+            .iter()
+            .find(|(_, a)| a.eq_ignore_ascii_case(&address))
+        {
+            let eid = EndpointID::try_from(eid.as_str()).map_err(|_| unknown())?;
+            return if eid.is_node_id() {
+                let service = if eid.scheme() == "ipn" { "767" } else { "sms" };
+                eid.new_endpoint(service).map_err(|_| unknown())
+            } else {
+                Ok(eid)
+            };
+        }
+        let (local, domain) = address.split_once('@').ok_or_else(unknown)?;
+        if !domain.eq_ignore_ascii_case(&self.config.domain) {
+            return Err(unknown());
+        }
+        let eid = match local.split_once('-').ok_or_else(unknown)? {
+            ("ipn", node) => EndpointID::with_ipn(node.parse().map_err(|_| unknown())?, 767),
+            ("dtn", node) => EndpointID::with_dtn(&format!("//{}/sms", node)),
+            _ => return Err(unknown()),
+        };
+        eid.map_err(|_| unknown())
+    }
+
+    /// Convert an sms into an internet message, attachments are added as MIME parts
+    pub fn to_email(&self, sms: &SMSBundle) -> Message {
+        let src = &sms.bundle().primary.source;
+        let from = self.address(src);
+        let date = sms.creation_timestamp().dtntime().unix();
+        let msg_id: String = sms
+            .id()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '.' })
+            .collect();
+        let mut mail = Message::new()
+            .with_header("From", &from)
+            .with_header("To", &self.config.recipients.join(", "))
+            .with_header("Subject", &format!("SMS from {}", src))
+            .with_header("Date", &format_date(date))
+            .with_header(
+                "Message-ID",
+                &format!("<{}@{}>", msg_id, self.config.domain),
+            )
+            .with_header("X-DTN-Source", &src.to_string())
+            .with_header("MIME-Version", "1.0");
+        let attachments = sms.attachments();
+        if attachments.is_empty() {
+            mail = mail
+                .with_header("Content-Type", "text/plain; charset=utf-8")
+                .with_header("Content-Transfer-Encoding", "8bit")
+                .with_body(&sms.msg());
+        } else {
+            let boundary = format!("=_{}", msg_id);
+            let mut body = format!(
+                "--{}\nContent-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: 8bit\n\n{}\n",
+                boundary,
+                sms.msg()
+            );
+            for att in attachments {
+                let encoded = base64::engine::general_purpose::STANDARD.encode(att.data());
+                body.push_str(&format!(
+                    "--{}\nContent-Type: {}\nContent-Transfer-Encoding: base64\nContent-Disposition: attachment; {}\n\n",
+                    boundary,
+                    mime_type(att.mime_type()),
+                    filename_param(att.filename().unwrap_or("attachment"))
+                ));
+                for chunk in encoded.as_bytes().chunks(76) {
+                    body.push_str(&String::from_utf8_lossy(chunk));
+                    body.push('\n');
+                }
+            }
+            body.push_str(&format!("--{}--\n", boundary));
+            mail = mail
+                .with_header(
+                    "Content-Type",
+                    &format!("multipart/mixed; boundary=\"{}\"", boundary),
+                )
+                .with_body(&body);
+        }
+        mail
+    }
+
+    /// Forward an sms to the configured recipients via the SMTP relay
+    pub fn deliver(&self, sms: &SMSBundle) -> Result<(), MailError> {
+        let mail = self.to_email(sms);
+        let from = self.address(&sms.bundle().primary.source);
+        send_mail(
+            &self.config.smtp_relay,
+            &self.config.helo,
+            &from,
+            &self.config.recipients,
+            &mail,
+        )
+    }
+
+    /// Convert a reply email into an sms to the endpoint of the `To` address
+    ///
+    /// For multipart mails the first inline `text/plain` part is used.
+    pub fn to_sms(&self, mail: &Message) -> Result<SMSBundle, MailError> {
+        let to = mail
+            .header("To")
+            .ok_or_else(|| MailError::InvalidMail("missing To header".into()))?;
+        let dst = self.endpoint(to.split(',').next().unwrap_or_default())?;
+        let text = reply_text(&text_part(mail, 0)?);
+        if text.is_empty() {
+            return Err(MailError::InvalidMail("empty message".into()));
+        }
+        Ok(new_sms_with_eids(
+            self.config.gateway_eid.clone(),
+            dst,
+            &text,
+            true,
+        )?)
+    }
+
+    /// Turn all new mails of the maildir into sms, returned together with the path of their mail
+    ///
+    /// Mails that could not be converted are moved to `cur` flagged as trashed. Converted mails stay
+    /// in `new` until they are passed to [`MailGateway::mark_submitted`], so they are read again if
+    /// submitting their sms fails.
+    pub fn ingest_maildir(&self) -> Result<Vec<(PathBuf, SMSBundle)>, MailError> {
+        let new_dir = self.config.maildir.join("new");
+        let cur_dir = self.config.maildir.join("cur");
+        std::fs::create_dir_all(&cur_dir)?;
+        let mut entries: Vec<PathBuf> = std::fs::read_dir(&new_dir)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file())
+            .collect();
+        entries.sort();
+
+        let mut bundles = Vec::new();
+        for path in entries {
+            let raw = std::fs::read(&path)?;
+            match self.to_sms(&Message::parse(&String::from_utf8_lossy(&raw))) {
+                Ok(sms) => bundles.push((path, sms)),
+                Err(_) => self.move_to_cur(&path, "T")?,
+            }
+        }
+        Ok(bundles)
+    }
+
+    /// Move a mail whose sms has been submitted to `cur`, flagged as seen
+    pub fn mark_submitted(&self, path: &Path) -> Result<(), MailError> {
+        self.move_to_cur(path, "S")
+    }
+
+    fn move_to_cur(&self, path: &Path, flag: &str) -> Result<(), MailError> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let cur = self.config.maildir.join("cur");
+        std::fs::rename(path, cur.join(format!("{}:2,{}", name, flag)))?;
+        Ok(())
+    }
+
+    /// Run the gateway, forwarding sms from `endpoint` and polling the maildir for replies
+    #[cfg(feature = "client")]
+    pub fn run(&self, client: &crate::client::DtnClient, endpoint: &str) -> anyhow::Result<()> {
+        use crate::client::Message as WsMessage;
+
+        let poller = self.clone();
+        let poll_client = client.clone();
+        std::thread::spawn(move || {
+            loop {
+                match poller.ingest_maildir() {
+                    Ok(bundles) => {
+                        for (path, mut sms) in bundles {
+                            // unsubmitted mails stay in the maildir and are retried on the next poll
+                            if let Err(err) = poll_client.insert_bundle(sms.to_cbor()) {
+                                poller.report(&format!(
+                                    "Failed to submit reply {}: {}",
+                                    sms.id(),
+                                    err
+                                ));
+                            } else if let Err(err) = poller.mark_submitted(&path) {
+                                poller.report(&format!(
+                                    "Failed to move {}: {}",
+                                    path.display(),
+                                    err
+                                ));
+                            }
+                        }
+                    }
+                    Err(err) => poller.report(&format!("Failed to read maildir: {}", err)),
+                }
+                std::thread::sleep(std::time::Duration::from_secs(poller.config.poll_interval));
+            }
+        });
+
+        client.register_application_endpoint(endpoint)?;
+        let mut wscon = client.ws()?;
+        wscon.write_text("/bundle")?;
+        let msg = wscon.read_text()?;
+        if !msg.starts_with("200 tx mode: bundle") {
+            anyhow::bail!("[!] Failed to set mode to `bundle`");
+        }
+        wscon.write_text(&format!("/subscribe {}", endpoint))?;
+        let msg = wscon.read_text()?;
+        if !msg.starts_with("200 subscribed") {
+            anyhow::bail!("[!] Failed to subscribe to service");
+        }
+
+        loop {
+            match wscon.read_message()? {
+                WsMessage::Binary(bin) => match SMSBundle::try_from(bin.to_vec()) {
+                    Ok(sms) => {
+                        if let Err(err) = self.deliver(&sms) {
+                            self.report(&format!("Failed to deliver {}: {}", sms.id(), err));
+                        }
+                    }
+                    Err(err) => self.report(&format!(
+                        "Not an sms bundle: {:#}",
+                        anyhow::Error::from(err)
+                    )),
+                },
+                WsMessage::Close(_) => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// MIME type of an attachment, invalid types sent by the peer become `application/octet-stream`
+fn mime_type(mime: &str) -> &str {
+    let is_token = |s: &str| {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+    };
+    match mime.split_once('/') {
+        Some((kind, subtype)) if is_token(kind) && is_token(subtype) => mime,
+        _ => "application/octet-stream",
+    }
+}
+
+/// `filename` parameter of a content disposition, quoted or RFC 2231 encoded if necessary
+fn filename_param(name: &str) -> String {
+    if name
+        .chars()
+        .all(|c| (' '..='~').contains(&c) && c != '"' && c != '\\')
+    {
+        return format!("filename=\"{}\"", name);
+    }
+    let encoded: String = name
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect();
+    format!("filename*=utf-8''{}", encoded)
+}
+
+/// Return a parameter of a structured header such as `Content-Type`
+fn header_param(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|param| {
+        let (key, val) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| val.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Find the plain text of a mail, descending into multipart bodies
+fn text_part(mail: &Message, depth: usize) -> Result<String, MailError> {
+    let ctype = mail.header("Content-Type").unwrap_or("text/plain");
+    let kind = ctype.split(';').next().unwrap_or_default().trim();
+    if kind.eq_ignore_ascii_case("text/plain") {
+        return decode_body(mail);
+    }
+    if !kind.to_lowercase().starts_with("multipart/") || depth >= MAX_MIME_DEPTH {
+        return Err(MailError::InvalidMail(format!(
+            "unsupported content type: {}",
+            ctype
+        )));
+    }
+    let boundary = header_param(ctype, "boundary")
+        .ok_or_else(|| MailError::InvalidMail("missing multipart boundary".into()))?;
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut current: Option<Vec<&str>> = None;
+    for line in mail.body().lines() {
+        let trimmed = line.trim_end();
+        if let Some(rest) = trimmed.strip_prefix(&delimiter)
+            && (rest.is_empty() || rest == "--")
+        {
+            parts.extend(current.take().map(|lines| lines.join("\n")));
+            if rest == "--" {
+                break;
+            }
+            current = Some(Vec::new());
+        } else if let Some(lines) = current.as_mut() {
+            lines.push(line);
+        }
+    }
+    parts
+        .iter()
+        .map(|raw| Message::parse(raw))
+        .filter(|part| {
+            !part
+                .header("Content-Disposition")
+                .is_some_and(|d| d.trim().to_lowercase().starts_with("attachment"))
+        })
+        .find_map(|part| text_part(&part, depth + 1).ok())
+        .ok_or_else(|| MailError::InvalidMail("no text/plain part".into()))
+}
+
+/// Undo the content transfer encoding of a text body
+fn decode_body(mail: &Message) -> Result<String, MailError> {
+    let encoding = mail
+        .header("Content-Transfer-Encoding")
+        .unwrap_or("8bit")
+        .trim()
+        .to_lowercase();
+    let bytes = match encoding.as_str() {
+        "quoted-printable" => decode_quoted_printable(mail.body()),
+        "base64" => {
+            let data: String = mail
+                .body()
+                .chars()
+                .filter(|c| !c.is_ascii_whitespace())
+                .collect();
+            base64::engine::general_purpose::STANDARD
+                .decode(data)
+                .map_err(|e| MailError::InvalidMail(format!("invalid base64 body: {}", e)))?
+        }
+        "7bit" | "8bit" | "binary" => mail.body().as_bytes().to_vec(),
+        _ => {
+            return Err(MailError::InvalidMail(format!(
+                "unsupported transfer encoding: {}",
+                encoding
+            )));
+        }
+    };
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn decode_quoted_printable(body: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len());
+    for line in body.lines() {
+        let line = line.trim_end();
+        let (line, soft_break) = match line.strip_suffix('=') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        let bytes = line.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            let escaped = (bytes[i] == b'=')
+                .then(|| bytes.get(i + 1..i + 3))
+                .flatten()
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+            match escaped {
+                Some(b) => {
+                    out.push(b);
+                    i += 3;
+                }
+                None => {
+                    out.push(bytes[i]);
+                    i += 1;
+                }
+            }
+        }
+        if !soft_break {
+            out.push(b'\n');
+        }
+    }
+    out
+}
+
+/// Strip quoted text and signatures from a reply body
+fn reply_text(body: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    for line in body.lines() {
+        if line == "-- " {
+            break;
+        }
+        if line.starts_with('>') {
+            continue;
+        }
+        lines.push(line);
+    }
+    // drop attribution lines such as "On Mon, ... wrote:"
+    while lines
+        .last()
+        .is_some_and(|l| l.trim().is_empty() || l.trim_end().ends_with("wrote:"))
+    {
+        lines.pop();
+    }
+    lines.join("\n").trim().to_string()
+}
+
+fn read_reply<R: BufRead>(reader: &mut R) -> Result<(u16, String), MailError> {
+    let mut text = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(MailError::Smtp(0, "connection closed".into()));
+        }
+        let line = line.trim_end();
+        let code: u16 = line
+            .get(..3)
+            .and_then(|c| c.parse().ok())
+            .ok_or_else(|| MailError::Smtp(0, line.to_string()))?;
+        text.push_str(line.get(4..).unwrap_or_default());
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok((code, text));
+        }
+        text.push('\n');
+    }
+}
+
+fn smtp_command<R: BufRead>(
+    stream: &mut TcpStream,
+    reader: &mut R,
+    cmd: &str,
+    expected: u16,
+) -> Result<(), MailError> {
+    stream.write_all(format!("{}\r\n", cmd).as_bytes())?;
+    let (code, text) = read_reply(reader)?;
+    if code != expected {
+        return Err(MailError::Smtp(code, text));
+    }
+    Ok(())
+}
+
+/// Deliver a message via plain SMTP to a relay such as a local MTA
+pub fn send_mail(
+    relay: &str,
+    helo: &str,
+    from: &str,
+    recipients: &[String],
+    mail: &Message,
+) -> Result<(), MailError> {
+    let mut stream = TcpStream::connect(relay)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let (code, text) = read_reply(&mut reader)?;
+    if code != 220 {
+        return Err(MailError::Smtp(code, text));
+    }
+    smtp_command(&mut stream, &mut reader, &format!("HELO {}", helo), 250)?;
+    smtp_command(
+        &mut stream,
+        &mut reader,
+        &format!("MAIL FROM:<{}>", extract_address(from)),
+        250,
+    )?;
+    for rcpt in recipients {
+        smtp_command(
+            &mut stream,
+            &mut reader,
+            &format!("RCPT TO:<{}>", extract_address(rcpt)),
+            250,
+        )?;
+    }
+    smtp_command(&mut stream, &mut reader, "DATA", 354)?;
+    let mut data = String::new();
+    for line in mail.to_string().lines() {
+        if line.starts_with('.') {
+            data.push('.');
+        }
+        data.push_str(line);
+        data.push_str("\r\n");
+    }
+    data.push('.');
+    smtp_command(&mut stream, &mut reader, &data, 250)?;
+    smtp_command(&mut stream, &mut reader, "QUIT", 221)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MailGateway, MailGatewayConfig};
+    use crate::rfc5322::Message;
+    use crate::sms::{Attachment, new_mms, new_sms};
+    use bp7::EndpointID;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+    use std::sync::mpsc;
+
+    fn test_gateway(relay: String, maildir: PathBuf) -> MailGateway {
+        let mut addresses = HashMap::new();
+        addresses.insert(
+            "ipn:1239468786.0".to_string(),
+            "alice@example.org".to_string(),
+        );
+        MailGateway::new(MailGatewayConfig {
+            smtp_relay: relay,
+            helo: "gateway".into(),
+            domain: "sms.example.org".into(),
+            recipients: vec!["office@example.org".into()],
+            addresses,
+            maildir,
+            gateway_eid: EndpointID::with_ipn(1000, 767).unwrap(),
+            poll_interval: 1,
+        })
+    }
+
+    /// Minimal SMTP server accepting a single message
+    fn smtp_stub() -> (String, mpsc::Receiver<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut transcript = Vec::new();
+            stream.write_all(b"220 stub ESMTP\r\n").unwrap();
+            let mut in_data = false;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                let line = line.trim_end().to_string();
+                let reply: &[u8] = if in_data {
+                    if line == "." {
+                        in_data = false;
+                        b"250 queued\r\n"
+                    } else {
+                        transcript.push(line);
+                        continue;
+                    }
+                } else if line == "DATA" {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                } else if line == "QUIT" {
+                    transcript.push(line);
+                    stream.write_all(b"221 bye\r\n").unwrap();
+                    break;
+                } else {
+                    transcript.push(line);
+                    b"250-ok\r\n250 ok\r\n"
+                };
+                stream.write_all(reply).unwrap();
+            }
+            tx.send(transcript).unwrap();
+        });
+        (addr, rx)
+    }
+
+    #[test]
+    fn test_mail_gateway_deliver() {
+        let (relay, rx) = smtp_stub();
+        let gw = test_gateway(relay, PathBuf::new());
+        let sms = new_sms(1239468786, 1000, "Status: all good\n.hidden dot", true).unwrap();
+        gw.deliver(&sms).unwrap();
+
+        let transcript = rx.recv().unwrap();
+        assert!(transcript.contains(&"MAIL FROM:<alice@example.org>".to_string()));
+        assert!(transcript.contains(&"RCPT TO:<office@example.org>".to_string()));
+        assert!(transcript.contains(&"From: alice@example.org".to_string()));
+        assert!(transcript.contains(&"Status: all good".to_string()));
+        assert!(transcript.contains(&"..hidden dot".to_string()));
+        assert_eq!(transcript.last().unwrap(), "QUIT");
+    }
+
+    #[test]
+    fn test_mail_gateway_attachment_headers() {
+        let gw = test_gateway(String::new(), PathBuf::new());
+        let src = EndpointID::with_ipn(1239468786, 767).unwrap();
+        let dst = EndpointID::with_ipn(1000, 767).unwrap();
+        let attachments = vec![
+            Attachment::new("image/png", Some("photo.png"), vec![1, 2, 3]),
+            Attachment::new(
+                "text/plain\r\nBcc: evil@example.org",
+                Some("a\".txt\r\nX-Injected: yes"),
+                vec![4],
+            ),
+            Attachment::new("application/pdf", Some("Übersicht.pdf"), vec![5]),
+        ];
+        let sms = new_mms(src, dst, "see attached", attachments, true).unwrap();
+        let body = gw.to_email(&sms).body().to_string();
+        assert!(body.contains("Content-Type: image/png\n"));
+        assert!(body.contains("filename=\"photo.png\""));
+        assert!(body.contains("Content-Type: application/octet-stream\n"));
+        assert!(body.contains("filename*=utf-8''a%22.txt%0D%0AX-Injected%3A%20yes"));
+        assert!(body.contains("filename*=utf-8''%C3%9Cbersicht.pdf"));
+        assert!(
+            !body
+                .lines()
+                .any(|l| l.starts_with("Bcc:") || l.starts_with("X-Injected:"))
+        );
+    }
+
+    #[test]
+    fn test_mail_gateway_mime_reply() {
+        let gw = test_gateway(String::new(), PathBuf::new());
+        let multipart = Message::new()
+            .with_header("To", "alice@example.org")
+            .with_header("Content-Type", "multipart/alternative; boundary=\"b1\"")
+            .with_body(
+                "preamble\n--b1\nContent-Type: text/plain; charset=utf-8\n\
+                 Content-Transfer-Encoding: quoted-printable\n\n\
+                 Gr=C3=BC=C3=9Fe, see you =\nsoon =3D)\n> quoted\n\
+                 --b1\nContent-Type: text/html\n\n<p>Gr&uuml;&szlig;e</p>\n--b1--\n",
+            );
+        assert_eq!(
+            gw.to_sms(&multipart).unwrap().msg(),
+            "Grüße, see you soon =)"
+        );
+
+        let nested = Message::new()
+            .with_header("To", "alice@example.org")
+            .with_header("Content-Type", "multipart/mixed; boundary=outer")
+            .with_body(
+                "--outer\nContent-Type: multipart/alternative; boundary=inner\n\n\
+                 --inner\nContent-Type: text/plain\nContent-Transfer-Encoding: base64\n\n\
+                 T24g\naXQh\n--inner--\n--outer\nContent-Type: text/plain\n\
+                 Content-Disposition: attachment; filename=\"log.txt\"\n\nlog\n--outer--\n",
+            );
+        assert_eq!(gw.to_sms(&nested).unwrap().msg(), "On it!");
+
+        let html = Message::new()
+            .with_header("To", "alice@example.org")
+            .with_header("Content-Type", "multipart/alternative; boundary=b1")
+            .with_body("--b1\nContent-Type: text/html\n\n<p>hi</p>\n--b1--\n");
+        assert!(gw.to_sms(&html).is_err());
+    }
+
+    #[test]
+    fn test_mail_gateway_addresses() {
+        let gw = test_gateway(String::new(), PathBuf::new());
+        let alice = EndpointID::with_ipn(1239468786, 767).unwrap();
+        let bob = EndpointID::try_from("dtn://bob/sms").unwrap();
+        assert_eq!(gw.address(&alice), "alice@example.org");
+        assert_eq!(gw.address(&bob), "dtn-bob@sms.example.org");
+        assert_eq!(gw.endpoint("Alice <alice@example.org>").unwrap(), alice);
+        assert_eq!(gw.endpoint("dtn-bob@sms.example.org").unwrap(), bob);
+        assert_eq!(
+            gw.endpoint("ipn-42@sms.example.org").unwrap(),
+            EndpointID::with_ipn(42, 767).unwrap()
+        );
+        assert!(gw.endpoint("someone@elsewhere.org").is_err());
+    }
+
+    #[test]
+    fn test_mail_gateway_maildir() {
+        let maildir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(maildir.path().join("new")).unwrap();
+        let reply = Message::new()
+            .with_header("From", "office@example.org")
+            .with_header("To", "Alice <alice@example.org>")
+            .with_header("Subject", "Re: SMS from ipn:1239468786.767")
+            .with_body(
+                "On it!\n\nOn Sun, 18 Oct 2026, alice wrote:\n> Status: all good\n-- \nOffice",
+            );
+        std::fs::write(maildir.path().join("new/1.mail"), reply.to_string()).unwrap();
+        std::fs::write(
+            maildir.path().join("new/2.mail"),
+            "To: nobody@nowhere\n\nhi",
+        )
+        .unwrap();
+
+        let gw = test_gateway(String::new(), maildir.path().to_path_buf());
+        let bundles = gw.ingest_maildir().unwrap();
+        assert_eq!(bundles.len(), 1);
+        let (path, sms) = &bundles[0];
+        assert_eq!(sms.msg(), "On it!");
+        assert_eq!(sms.dst().unwrap(), "1239468786");
+        assert_eq!(sms.src().unwrap(), "1000");
+        assert!(maildir.path().join("cur/2.mail:2,T").exists());
+
+        // mails are only moved once their sms has been submitted
+        assert_eq!(gw.ingest_maildir().unwrap().len(), 1);
+        gw.mark_submitted(path).unwrap();
+        assert!(maildir.path().join("cur/1.mail:2,S").exists());
+        assert!(gw.ingest_maildir().unwrap().is_empty());
+    }
+}
@@ -1,36 +1,99 @@
+pub mod addressbook;
+pub mod json;
+pub mod mail;
+
+use crate::compression::{Compression, CompressionError};
+use addressbook::{AddressBook, Contact, Group};
 use bp7::flags::BlockControlFlags;
 use bp7::*;
+use core::fmt;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 use std::time::Duration;
 use thiserror::Error;
 
+/// Lifetime of newly created sms bundles
+pub const DEFAULT_LIFETIME: Duration = Duration::from_secs(60 * 60);
+/// Maximum size of a single attachment in bytes
+pub const MAX_ATTACHMENT_SIZE: usize = 64 * 1024;
+/// Maximum combined size of all attachments of an sms in bytes
+pub const MAX_ATTACHMENTS_TOTAL_SIZE: usize = 256 * 1024;
+
 #[derive(Error, Debug)]
 pub enum SmsError {
+    #[error("bundle decoding error: {0}")]
+    BundleDecoding(#[from] bp7::error::Error),
     #[error("message not utf8: {0}")]
     NonUtf8(#[from] std::string::FromUtf8Error),
     #[error("serde cbor error: {0}")]
     Cbor(#[from] serde_cbor::Error),
+    #[error("invalid json: {0}")]
+    Json(#[from] serde_json::Error),
     #[error("failed to decompress message: {0}")]
-    SmazDecompress(#[from] smaz::DecompressError),
+    Decompression(#[from] CompressionError),
     #[error("failed to create endpoint: {0}")]
     EndpointIdInvalid(#[from] bp7::eid::EndpointIdError),
     #[error("SMS is missing message text")]
     NoMessage,
     #[error("invalid endpoint supplied")]
     InvalidEndpoint,
+    #[error("unknown endpoint service: {0}")]
+    UnknownService(String),
+    #[error("source must be a singleton endpoint")]
+    NonSingletonSource,
     #[error("payload missing")]
     PayloadMissing,
-    #[error("invalid sms bundle")]
-    InvalidSmsBundle,
+    #[error("attachment too large: {0} bytes")]
+    AttachmentTooLarge(usize),
+    #[error("attachment is missing a mime type")]
+    AttachmentMimeMissing,
+    #[error("invalid sms bundle, {field}")]
+    InvalidSmsBundle {
+        field: SmsField,
+        #[source]
+        source: Box<SmsError>,
+    },
 }
 
-fn smaz_compress(indata: &[u8]) -> Vec<u8> {
-    smaz::compress(indata)
+/// Part of an sms bundle that failed validation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmsField {
+    Source,
+    Destination,
+    Payload,
+    Compression,
+    Message,
+    Attachments,
+}
+
+impl fmt::Display for SmsField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SmsField::Source => "source",
+            SmsField::Destination => "destination",
+            SmsField::Payload => "payload",
+            SmsField::Compression => "compression",
+            SmsField::Message => "message",
+            SmsField::Attachments => "attachments",
+        };
+        f.write_str(name)
+    }
 }
 
-fn smaz_decompress(indata: &[u8]) -> Result<Vec<u8>, SmsError> {
-    Ok(smaz::decompress(indata)?)
+impl SmsError {
+    fn in_field(self, field: SmsField) -> SmsError {
+        SmsError::InvalidSmsBundle {
+            field,
+            source: Box::new(self),
+        }
+    }
+    /// Field of the bundle that caused a validation error
+    pub fn field(&self) -> Option<SmsField> {
+        match self {
+            SmsError::InvalidSmsBundle { field, .. } => Some(*field),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -41,52 +104,99 @@ impl TryFrom<Bundle> for SMSBundle {
 
     fn try_from(value: Bundle) -> Result<Self, Self::Error> {
         let sms_bundle = SMSBundle(value);
-        if sms_bundle.is_valid().is_err() {
-            Err(SmsError::InvalidSmsBundle)
-        } else {
-            Ok(sms_bundle)
+        sms_bundle.validate(false)?;
+        Ok(sms_bundle)
+    }
+}
+
+impl TryFrom<Vec<u8>> for SMSBundle {
+    type Error = SmsError;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        let bundle = Bundle::try_from(value)?;
+        SMSBundle::try_from(bundle)
+    }
+}
+
+impl fmt::Display for SMSBundle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "ID: {}", self.id())?;
+        writeln!(f, "From: {}", self.src().unwrap_or_default())?;
+        writeln!(f, "To: {}", self.dst().unwrap_or_default())?;
+        writeln!(f, "Creation TS: {}", self.creation_timestamp())?;
+        for att in self.attachments() {
+            writeln!(f, "Attachment: {}", att)?;
         }
+        writeln!(f, "\n{}", self.msg())
     }
 }
 
 impl SMSBundle {
+    /// Accept bundles with unknown endpoint services, returning them as warnings
+    ///
+    /// All other validation errors are still fatal.
+    pub fn try_from_lenient(value: Bundle) -> Result<(Self, Vec<SmsError>), SmsError> {
+        let sms_bundle = SMSBundle(value);
+        let warnings = sms_bundle.validate(true)?;
+        Ok((sms_bundle, warnings))
+    }
     fn is_eid_valid(&self, eid: &EndpointID) -> Result<(), SmsError> {
         match eid {
             EndpointID::Ipn(_, ipn) => {
                 if ipn.service_number() == 767 {
                     Ok(())
                 } else {
-                    Err(SmsError::InvalidEndpoint)
+                    Err(SmsError::UnknownService(eid.to_string()))
                 }
             }
             EndpointID::Dtn(_, ssp) => {
                 if ssp.service_name() == Some("sms") || ssp.service_name() == Some("~sms") {
                     Ok(())
                 } else {
-                    Err(SmsError::InvalidEndpoint)
+                    Err(SmsError::UnknownService(eid.to_string()))
                 }
             }
             _ => Err(SmsError::InvalidEndpoint),
         }
     }
-    fn is_valid(&self) -> Result<(), SmsError> {
-        self.is_eid_valid(&self.0.primary.source)?;
-        self.is_eid_valid(&self.0.primary.destination)?;
+    /// Validate the bundle, returning the cause and offending field on error
+    ///
+    /// In lenient mode unknown endpoint services are collected as warnings instead.
+    fn validate(&self, lenient: bool) -> Result<Vec<SmsError>, SmsError> {
+        let mut warnings = Vec::new();
+        for (eid, field) in [
+            (&self.0.primary.source, SmsField::Source),
+            (&self.0.primary.destination, SmsField::Destination),
+        ] {
+            match self.is_eid_valid(eid) {
+                Ok(()) => {}
+                Err(err @ SmsError::UnknownService(_)) if lenient => {
+                    warnings.push(err.in_field(field))
+                }
+                Err(err) => return Err(err.in_field(field)),
+            }
+        }
 
         if self.0.primary.source.is_non_singleton() {
-            return Err(SmsError::InvalidEndpoint);
+            return Err(SmsError::NonSingletonSource.in_field(SmsField::Source));
         }
         // Validate general payload
-        let payload = self.0.payload().ok_or(SmsError::PayloadMissing)?;
-        let sms: SMS = serde_cbor::from_slice(payload)?;
+        let payload = self
+            .0
+            .payload()
+            .ok_or_else(|| SmsError::PayloadMissing.in_field(SmsField::Payload))?;
+        let sms: SMS = serde_cbor::from_slice(payload)
+            .map_err(|err| SmsError::from(err).in_field(SmsField::Payload))?;
 
         // Validate payload message and compression
-        if sms.comp {
-            String::from_utf8(smaz_decompress(&sms.msg)?)?;
-        } else {
-            String::from_utf8(sms.msg)?;
-        }
-        Ok(())
+        let msg = sms
+            .comp
+            .decompress(&sms.msg)
+            .map_err(|err| SmsError::from(err).in_field(SmsField::Compression))?;
+        String::from_utf8(msg).map_err(|err| SmsError::from(err).in_field(SmsField::Message))?;
+
+        validate_attachments(&sms.att).map_err(|err| err.in_field(SmsField::Attachments))?;
+        Ok(warnings)
     }
     pub fn id(&self) -> String {
         self.0.id()
@@ -112,6 +222,38 @@ impl SMSBundle {
     pub fn dst(&self) -> Option<String> {
         self.0.primary.destination.node()
     }
+    /// Check whether this sms was sent to a non-singleton group endpoint
+    pub fn is_group(&self) -> bool {
+        self.0.primary.destination.is_non_singleton()
+    }
+    /// Return the group endpoint if this is a group sms
+    pub fn group(&self) -> Option<&EndpointID> {
+        if self.is_group() {
+            Some(&self.0.primary.destination)
+        } else {
+            None
+        }
+    }
+    /// Return the conversation this sms belongs to from a receiver's point of view
+    pub fn conversation(&self) -> Conversation {
+        if self.is_group() {
+            Conversation::Group(self.0.primary.destination.clone())
+        } else {
+            Conversation::Direct(self.0.primary.source.clone())
+        }
+    }
+    /// Group members carried in the sms, empty for direct messages
+    pub fn members(&self) -> Vec<EndpointID> {
+        self.sms().members().to_vec()
+    }
+    /// Resolve the sender to a contact name, falling back to the plain node name
+    pub fn src_name(&self, book: &AddressBook) -> String {
+        book.display_name(&self.0.primary.source)
+    }
+    /// Resolve the receiver to a contact name, falling back to the plain node name
+    pub fn dst_name(&self, book: &AddressBook) -> String {
+        book.display_name(&self.0.primary.destination)
+    }
     pub fn creation_timestamp(&self) -> &CreationTimestamp {
         &self.0.primary.creation_timestamp
     }
@@ -123,6 +265,9 @@ impl SMSBundle {
     pub fn compression(&self) -> bool {
         self.sms().compression()
     }
+    pub fn compression_algorithm(&self) -> Compression {
+        self.sms().compression_algorithm()
+    }
     pub fn encryption(&self) -> bool {
         self.sms().encryption()
     }
@@ -132,6 +277,12 @@ impl SMSBundle {
     pub fn msg(&self) -> String {
         self.sms().msg()
     }
+    pub fn has_attachments(&self) -> bool {
+        !self.sms().attachments().is_empty()
+    }
+    pub fn attachments(&self) -> Vec<Attachment> {
+        self.sms().att
+    }
     pub fn bundle(&self) -> &Bundle {
         &self.0
     }
@@ -141,17 +292,99 @@ impl SMSBundle {
     }
 }
 
+/// Conversation an sms belongs to, either with a single peer or within a group
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum Conversation {
+    /// Direct message, identified by the sender
+    Direct(EndpointID),
+    /// Group message, identified by the non-singleton group endpoint
+    Group(EndpointID),
+}
+
+impl fmt::Display for Conversation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Conversation::Direct(eid) => write!(f, "{}", eid),
+            Conversation::Group(eid) => write!(f, "group:{}", eid),
+        }
+    }
+}
+
+/// Typed binary attachment of an sms, e.g., a small image or sensor file
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    mime: String,
+    name: Option<String>,
+    #[serde(with = "crate::serde::base64_or_bytes")]
+    data: Vec<u8>,
+}
+
+impl Attachment {
+    pub fn new(mime: &str, name: Option<&str>, data: Vec<u8>) -> Self {
+        Attachment {
+            mime: mime.into(),
+            name: name.map(|n| n.into()),
+            data,
+        }
+    }
+    pub fn mime_type(&self) -> &str {
+        &self.mime
+    }
+    pub fn filename(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    pub fn data(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+}
+
+impl fmt::Display for Attachment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, {} bytes)",
+            self.filename().unwrap_or("unnamed"),
+            self.mime,
+            self.data.len()
+        )
+    }
+}
+
+fn validate_attachments(attachments: &[Attachment]) -> Result<(), SmsError> {
+    let mut total = 0;
+    for att in attachments {
+        if att.mime.is_empty() {
+            return Err(SmsError::AttachmentMimeMissing);
+        }
+        if att.data.len() > MAX_ATTACHMENT_SIZE {
+            return Err(SmsError::AttachmentTooLarge(att.data.len()));
+        }
+        total += att.data.len();
+    }
+    if total > MAX_ATTACHMENTS_TOTAL_SIZE {
+        return Err(SmsError::AttachmentTooLarge(total));
+    }
+    Ok(())
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct SMS {
-    comp: bool,
+    comp: Compression,
     enc: bool,
     #[serde(with = "serde_bytes")]
     msg: Vec<u8>,
     sig: Option<Vec<u8>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    grp: Vec<EndpointID>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    att: Vec<Attachment>,
 }
 
 impl SMS {
     pub fn compression(&self) -> bool {
+        self.comp != Compression::None
+    }
+    pub fn compression_algorithm(&self) -> Compression {
         self.comp
     }
     pub fn encryption(&self) -> bool {
@@ -160,36 +393,55 @@ impl SMS {
     pub fn signature(&self) -> Option<Vec<u8>> {
         self.sig.clone()
     }
+    pub fn members(&self) -> &[EndpointID] {
+        self.grp.as_slice()
+    }
+    pub fn attachments(&self) -> &[Attachment] {
+        self.att.as_slice()
+    }
     pub fn msg(&self) -> String {
-        if self.compression() {
-            String::from_utf8_lossy(&smaz_decompress(&self.msg).expect("decompressing msg failed"))
-                .to_string()
-        } else {
-            String::from_utf8_lossy(&self.msg).to_string()
-        }
+        String::from_utf8_lossy(
+            &self
+                .comp
+                .decompress(&self.msg)
+                .expect("decompressing msg failed"),
+        )
+        .to_string()
     }
 }
 
 pub struct SmsBuilder {
     comp: bool,
+    algo: Option<Compression>,
     enc: bool,
     msg: Option<String>,
     sig: Option<Vec<u8>>,
+    members: Vec<EndpointID>,
+    attachments: Vec<Attachment>,
 }
 
 impl SmsBuilder {
     pub fn new() -> Self {
         SmsBuilder {
             comp: true,
+            algo: None,
             enc: false,
             msg: None,
             sig: None,
+            members: Vec::new(),
+            attachments: Vec::new(),
         }
     }
+    /// Compress with the algorithm producing the smallest output
     pub fn compression(mut self, comp: bool) -> Self {
         self.comp = comp;
         self
     }
+    /// Force a specific compression algorithm
+    pub fn compression_algorithm(mut self, algo: Compression) -> Self {
+        self.algo = Some(algo);
+        self
+    }
     pub fn encryption(mut self, enc: bool) -> Self {
         self.enc = enc;
         self
@@ -202,18 +454,36 @@ impl SmsBuilder {
         self.sig = Some(sig);
         self
     }
+    /// Carry the member list of a group sms in the payload
+    pub fn members(mut self, members: Vec<EndpointID>) -> Self {
+        self.members = members;
+        self
+    }
+    /// Add a binary attachment, the message text may be omitted if attachments are present
+    pub fn attachment(mut self, attachment: Attachment) -> Self {
+        self.attachments.push(attachment);
+        self
+    }
     pub fn build(self) -> Result<SMS, SmsError> {
-        if let Some(msg) = self.msg {
-            let msg_bytes = if self.comp {
-                smaz_compress(msg.as_bytes())
-            } else {
-                msg.as_bytes().to_vec()
+        validate_attachments(&self.attachments)?;
+        let msg = if self.msg.is_none() && !self.attachments.is_empty() {
+            Some(String::new())
+        } else {
+            self.msg
+        };
+        if let Some(msg) = msg {
+            let comp = match self.algo {
+                Some(algo) => algo,
+                None if self.comp => Compression::smallest(&[msg.as_bytes()]),
+                None => Compression::None,
             };
             Ok(SMS {
-                comp: self.comp,
+                comp,
                 enc: self.enc,
-                msg: msg_bytes,
+                msg: comp.compress(msg.as_bytes()),
                 sig: self.sig,
+                grp: self.members,
+                att: self.attachments,
             })
         } else {
             Err(SmsError::NoMessage)
@@ -231,31 +501,146 @@ pub fn new_sms(src: u64, dst: u64, msg: &str, compression: bool) -> Result<SMSBu
     let src_eid = EndpointID::with_ipn(src, 767)?;
     let dst_eid = EndpointID::with_ipn(dst, 767)?;
 
+    new_sms_with_eids(src_eid, dst_eid, msg, compression)
+}
+
+/// Create a new sms bundle to a contact from the address book
+///
+/// The contact endpoint is chosen to match the addressing scheme of `src`.
+pub fn new_sms_to_contact(
+    src: EndpointID,
+    contact: &Contact,
+    msg: &str,
+    compression: bool,
+) -> Result<SMSBundle, SmsError> {
+    let dst_eid = contact
+        .sms_endpoint(&src.scheme())
+        .ok_or(SmsError::InvalidEndpoint)?;
+
+    new_sms_with_eids(src, dst_eid, msg, compression)
+}
+
+/// Create a new sms bundle for arbitrary sms endpoints
+pub fn new_sms_with_eids(
+    src_eid: EndpointID,
+    dst_eid: EndpointID,
+    msg: &str,
+    compression: bool,
+) -> Result<SMSBundle, SmsError> {
+    let payload = SmsBuilder::new()
+        .compression(compression)
+        .message(msg)
+        .build()?;
+
+    sms_bundle(src_eid, dst_eid, DEFAULT_LIFETIME, &payload)
+}
+
+/// Create a new sms bundle with binary attachments for arbitrary sms endpoints
+pub fn new_mms(
+    src_eid: EndpointID,
+    dst_eid: EndpointID,
+    msg: &str,
+    attachments: Vec<Attachment>,
+    compression: bool,
+) -> Result<SMSBundle, SmsError> {
+    let payload = attachments
+        .into_iter()
+        .fold(SmsBuilder::new(), |b, att| b.attachment(att))
+        .compression(compression)
+        .message(msg)
+        .build()?;
+
+    sms_bundle(src_eid, dst_eid, DEFAULT_LIFETIME, &payload)
+}
+
+/// Create a new sms bundle to a non-singleton group endpoint, e.g., `dtn://team-a/~sms`
+///
+/// The member list is carried in the payload so receivers can reply to all members,
+/// pass an empty list if members are resolved locally.
+pub fn new_group_sms(
+    src_eid: EndpointID,
+    group_eid: EndpointID,
+    members: Vec<EndpointID>,
+    msg: &str,
+    compression: bool,
+) -> Result<SMSBundle, SmsError> {
+    if !group_eid.is_non_singleton() {
+        return Err(SmsError::InvalidEndpoint);
+    }
+    let payload = SmsBuilder::new()
+        .compression(compression)
+        .message(msg)
+        .members(members)
+        .build()?;
+
+    sms_bundle(src_eid, group_eid, DEFAULT_LIFETIME, &payload)
+}
+
+/// Create a new sms bundle to a group from the address book
+pub fn new_sms_to_group(
+    src_eid: EndpointID,
+    group: &Group,
+    msg: &str,
+    compression: bool,
+) -> Result<SMSBundle, SmsError> {
+    new_group_sms(
+        src_eid,
+        group.eid().clone(),
+        group.members().to_vec(),
+        msg,
+        compression,
+    )
+}
+
+/// Create a reply to an sms
+///
+/// Replies to group messages go to the group, carrying the original member list.
+/// Replies to direct messages go back to the sender.
+pub fn reply_sms(
+    parent: &SMSBundle,
+    src_eid: EndpointID,
+    msg: &str,
+    compression: bool,
+) -> Result<SMSBundle, SmsError> {
+    match parent.conversation() {
+        Conversation::Group(group_eid) => {
+            new_group_sms(src_eid, group_eid, parent.members(), msg, compression)
+        }
+        Conversation::Direct(dst_eid) => new_sms_with_eids(src_eid, dst_eid, msg, compression),
+    }
+}
+
+fn sms_bundle(
+    src_eid: EndpointID,
+    dst_eid: EndpointID,
+    lifetime: Duration,
+    payload: &SMS,
+) -> Result<SMSBundle, SmsError> {
     let pblock = primary::PrimaryBlockBuilder::default()
         .destination(dst_eid)
         .source(src_eid)
         .report_to(EndpointID::none())
         .creation_timestamp(CreationTimestamp::now())
-        .lifetime(Duration::from_secs(60 * 60))
+        .lifetime(lifetime)
         .build()
         .unwrap();
 
-    let payload = SmsBuilder::new()
-        .compression(compression)
-        .message(msg)
-        .build()?;
     let cblocks = vec![canonical::new_payload_block(
         BlockControlFlags::empty(),
-        serde_cbor::to_vec(&payload).expect("Fatal failure, could not convert sms payload to CBOR"),
+        serde_cbor::to_vec(payload).expect("Fatal failure, could not convert sms payload to CBOR"),
     )];
 
-    Ok(SMSBundle::try_from(bundle::Bundle::new(pblock, cblocks))
-        .expect("error creating sms bundle"))
+    SMSBundle::try_from(bundle::Bundle::new(pblock, cblocks))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::sms::{SMSBundle, new_sms};
+    use crate::compression::Compression;
+    use crate::sms::addressbook::{AddressBook, Contact};
+    use crate::sms::{
+        Attachment, Conversation, MAX_ATTACHMENT_SIZE, SMSBundle, SmsBuilder, SmsError, SmsField,
+        new_group_sms, new_mms, new_sms, new_sms_to_contact, new_sms_with_eids, reply_sms,
+    };
     use std::convert::TryFrom;
     #[test]
     fn test_sms_new_uncompressed() {
@@ -367,4 +752,165 @@ mod tests {
 
         assert!(smsbundle.is_pure("dtn"));
     }
+
+    #[test]
+    fn test_sms_to_contact() {
+        let mut book = AddressBook::new();
+        book.add(Contact::new("Alice").with_eid(bp7::EndpointID::with_ipn(1239468786, 0).unwrap()))
+            .unwrap();
+        book.add(Contact::new("Bob").with_eid(bp7::EndpointID::with_ipn(1239468999, 0).unwrap()))
+            .unwrap();
+
+        let src = bp7::EndpointID::with_ipn(1239468786, 767).unwrap();
+        let sms = new_sms_to_contact(src, book.get("Bob").unwrap(), "hello bob", true).unwrap();
+        assert_eq!(sms.dst().unwrap(), "1239468999");
+        assert_eq!(sms.src_name(&book), "Alice");
+        assert_eq!(sms.dst_name(&book), "Bob");
+
+        let dtn_src = bp7::EndpointID::try_from("dtn://alice/sms").unwrap();
+        assert!(new_sms_to_contact(dtn_src, book.get("Bob").unwrap(), "hello", true).is_err());
+    }
+
+    #[test]
+    fn test_group_sms() {
+        let group = bp7::EndpointID::try_from("dtn://team-a/~sms").unwrap();
+        let alice = bp7::EndpointID::try_from("dtn://alice/sms").unwrap();
+        let bob = bp7::EndpointID::try_from("dtn://bob/sms").unwrap();
+        let members = vec![alice.clone(), bob.clone()];
+
+        let sms = new_group_sms(
+            alice.clone(),
+            group.clone(),
+            members.clone(),
+            "hi team",
+            true,
+        )
+        .unwrap();
+        assert!(sms.is_group());
+        assert_eq!(sms.group(), Some(&group));
+        assert_eq!(sms.members(), members);
+        assert_eq!(sms.conversation(), Conversation::Group(group.clone()));
+
+        let reply = reply_sms(&sms, bob.clone(), "hi alice", true).unwrap();
+        assert_eq!(reply.conversation(), Conversation::Group(group.clone()));
+        assert_eq!(reply.members(), members);
+
+        let direct = new_sms_with_eids(alice.clone(), bob.clone(), "only to bob", true).unwrap();
+        assert_eq!(direct.conversation(), Conversation::Direct(alice.clone()));
+        let direct_reply = reply_sms(&direct, bob.clone(), "ok", true).unwrap();
+        assert!(!direct_reply.is_group());
+        assert_eq!(direct_reply.dst().unwrap(), "alice");
+        assert_eq!(
+            direct_reply.conversation(),
+            Conversation::Direct(bob.clone())
+        );
+
+        assert!(new_group_sms(alice.clone(), bob, Vec::new(), "not a group", true).is_err());
+        // group members can not be used as sender
+        assert!(new_group_sms(group.clone(), group, Vec::new(), "invalid", true).is_err());
+    }
+
+    #[test]
+    fn test_legacy_smaz_payload() {
+        #[derive(serde::Serialize)]
+        struct LegacySMS {
+            comp: bool,
+            enc: bool,
+            #[serde(with = "serde_bytes")]
+            msg: Vec<u8>,
+            sig: Option<Vec<u8>>,
+        }
+        let legacy = LegacySMS {
+            comp: true,
+            enc: false,
+            msg: smaz::compress(b"The quick brown fox jumps over the lazy dog"),
+            sig: None,
+        };
+        let sms = new_sms(1239468786, 1239468999, "placeholder", false).unwrap();
+        let mut raw_bundle = sms.bundle().clone();
+        raw_bundle.set_payload(serde_cbor::to_vec(&legacy).unwrap());
+
+        let sms = SMSBundle::try_from(raw_bundle).unwrap();
+        assert_eq!(sms.compression_algorithm(), Compression::Smaz);
+        assert_eq!(sms.msg(), "The quick brown fox jumps over the lazy dog");
+    }
+
+    #[test]
+    fn test_sms_attachments() {
+        let alice = bp7::EndpointID::try_from("dtn://alice/sms").unwrap();
+        let bob = bp7::EndpointID::try_from("dtn://bob/sms").unwrap();
+        let image = Attachment::new("image/png", Some("map.png"), vec![0x89, 0x50, 0x4e, 0x47]);
+
+        let mut mms = new_mms(
+            alice.clone(),
+            bob.clone(),
+            "see attached map",
+            vec![image.clone()],
+            true,
+        )
+        .unwrap();
+        let mms = SMSBundle::try_from(mms.to_cbor()).unwrap();
+        assert!(mms.has_attachments());
+        assert_eq!(mms.attachments(), vec![image.clone()]);
+        assert_eq!(mms.attachments()[0].filename(), Some("map.png"));
+        assert_eq!(mms.msg(), "see attached map");
+
+        let sms = SmsBuilder::new().attachment(image).build().unwrap();
+        assert_eq!(sms.msg(), "");
+
+        let too_large = Attachment::new("text/csv", None, vec![0; MAX_ATTACHMENT_SIZE + 1]);
+        assert!(new_mms(alice, bob, "", vec![too_large], true).is_err());
+        assert!(SmsBuilder::new().build().is_err());
+    }
+
+    #[test]
+    fn test_text_only_encoding_unchanged() {
+        let sms = SmsBuilder::new()
+            .compression(false)
+            .message("hello")
+            .build()
+            .unwrap();
+        let buf = serde_cbor::to_vec(&sms).unwrap();
+        let map: std::collections::BTreeMap<String, serde_cbor::Value> =
+            serde_cbor::from_slice(&buf).unwrap();
+        assert_eq!(
+            map.keys().collect::<Vec<_>>(),
+            vec!["comp", "enc", "msg", "sig"]
+        );
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let sms = new_sms(1239468786, 1239468999, "hello", true).unwrap();
+        let mut raw_bundle = sms.bundle().clone();
+        raw_bundle.primary.destination = bp7::EndpointID::with_ipn(1239468999, 42).unwrap();
+
+        let err = SMSBundle::try_from(raw_bundle.clone()).unwrap_err();
+        assert_eq!(err.field(), Some(SmsField::Destination));
+        assert!(matches!(
+            err,
+            SmsError::InvalidSmsBundle { ref source, .. } if matches!(**source, SmsError::UnknownService(_))
+        ));
+
+        let (lenient, warnings) = SMSBundle::try_from_lenient(raw_bundle.clone()).unwrap();
+        assert_eq!(lenient.msg(), "hello");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].field(), Some(SmsField::Destination));
+
+        raw_bundle.set_payload(vec![0xff, 0x00]);
+        let err = SMSBundle::try_from_lenient(raw_bundle.clone()).unwrap_err();
+        assert_eq!(err.field(), Some(SmsField::Payload));
+
+        let mut payload = crate::sms::SmsBuilder::new()
+            .compression(false)
+            .message("x")
+            .build()
+            .unwrap();
+        payload.msg = vec![0xc3, 0x28];
+        raw_bundle.set_payload(serde_cbor::to_vec(&payload).unwrap());
+        let err = SMSBundle::try_from_lenient(raw_bundle).unwrap_err();
+        assert_eq!(err.field(), Some(SmsField::Message));
+        assert_eq!(err.to_string(), "invalid sms bundle, message");
+        assert!(std::error::Error::source(&err).is_some());
+    }
 }